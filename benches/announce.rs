@@ -0,0 +1,127 @@
+// Benchmarks for the announce hot path
+//
+// Run with `cargo bench`; criterion keeps previous results under target/criterion and
+// reports the change against them.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use tracker::bencode::response::{build_announce_response, AnnounceResponseOptions};
+use tracker::models::peer::Peer;
+use tracker::stores::peer_store::{PeerOrder, PeerStore};
+use tracker::utils::hex::url_decode_binary;
+
+const INFO_HASH: [u8; 20] = [0xab; 20];
+
+fn peer_id(i: u32) -> [u8; 20] {
+    let mut peer_id = [0u8; 20];
+    peer_id[..4].copy_from_slice(&i.to_be_bytes());
+    peer_id
+}
+
+/// A peer with its own user and address; every fourth peer is an IPv6 seeder
+fn make_peer(i: u32) -> Peer {
+    let seeder = i.is_multiple_of(4);
+    let ip = if seeder {
+        IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, (i >> 16) as u16, i as u16))
+    } else {
+        IpAddr::V4(Ipv4Addr::from(0x0a00_0000 | i))
+    };
+    let left = if seeder { 0 } else { 1000 };
+    Peer::new(i, 1, peer_id(i), ip, 6881, 0, 0, left, 1_700_000_000, "qBittorrent/4.6.0".to_string())
+}
+
+fn populated_store(peers: u32) -> PeerStore {
+    let store = PeerStore::new();
+    for i in 0..peers {
+        store.add_peer(INFO_HASH, make_peer(i)).unwrap();
+    }
+    store
+}
+
+fn bench_build_announce_response(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_announce_response");
+    for count in [0u32, 50, 200] {
+        let peers: Vec<Peer> = (0..count).map(make_peer).collect();
+        for compact in [true, false] {
+            let label = if compact { "compact" } else { "dict" };
+            let options = AnnounceResponseOptions {
+                interval: 1800,
+                compact,
+                emit_peers6: true,
+                ..Default::default()
+            };
+            group.bench_with_input(BenchmarkId::new(label, count), &peers, |b, peers| {
+                b.iter(|| build_announce_response(black_box(peers), 10, 40, &options))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_get_peers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_peers");
+    for swarm in [1_000u32, 10_000, 50_000] {
+        let store = populated_store(swarm);
+        group.bench_with_input(BenchmarkId::new("numwant_50", swarm), &store, |b, store| {
+            b.iter(|| store.get_peers_and_stats(INFO_HASH, 50, black_box(peer_id(u32::MAX)), PeerOrder::Random))
+        });
+    }
+    group.finish();
+}
+
+fn bench_add_and_update_peer(c: &mut Criterion) {
+    const PEERS: u32 = 1_000;
+    let mut group = c.benchmark_group("peer_store");
+    group.throughput(Throughput::Elements(PEERS as u64));
+
+    group.bench_function("add_peer", |b| {
+        b.iter_batched(
+            || ((0..PEERS).map(make_peer).collect::<Vec<_>>(), PeerStore::new()),
+            |(peers, store)| {
+                for peer in peers {
+                    store.add_peer(INFO_HASH, peer).unwrap();
+                }
+                store
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let store = populated_store(PEERS);
+    group.bench_function("update_peer", |b| {
+        b.iter_batched(
+            || (0..PEERS).map(make_peer).collect::<Vec<_>>(),
+            |peers| {
+                for peer in peers {
+                    store.update_peer(INFO_HASH, peer.peer_id, peer).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_url_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("url_decode_binary");
+    let inputs = [
+        ("plain", "ABCDEFGHIJKLMNOPQRST".to_string()),
+        ("mixed", "%12%34VX%9a%bc-_.~%de%f0%11%22%33%44".to_string()),
+        ("escaped", "%ab".repeat(20)),
+    ];
+    for (label, input) in &inputs {
+        group.bench_with_input(BenchmarkId::from_parameter(label), input, |b, input| {
+            b.iter(|| url_decode_binary(black_box(input)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_build_announce_response,
+    bench_get_peers,
+    bench_add_and_update_peer,
+    bench_url_decode,
+);
+criterion_main!(benches);
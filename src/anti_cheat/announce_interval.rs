@@ -1,5 +1,5 @@
 use crate::core::error::AntiCheatError;
-use tracing::warn;
+use tracing::{debug, warn};
 
 pub fn check_announce_interval(
     user_id: u32,
@@ -13,6 +13,19 @@ pub fn check_announce_interval(
         return Ok(());
     };
     
+    // The clock stepped backwards since the last announce (e.g. an NTP correction), so
+    // the real interval is unknown; don't count it against the peer
+    if current_time < last_announce_time {
+        debug!(
+            user_id = user_id,
+            torrent_id = torrent_id,
+            last_announce = last_announce_time,
+            current_time = current_time,
+            "Clock went backwards since last announce, skipping interval check"
+        );
+        return Ok(());
+    }
+
     // Calculate elapsed time since last announce
     let elapsed = current_time - last_announce_time;
     
@@ -106,4 +119,17 @@ mod tests {
         );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_announce_interval_clock_went_backwards() {
+        // A backward clock step leaves the interval unknown and must not be flagged
+        let result = check_announce_interval(
+            1,
+            1,
+            Some(1000),
+            940,
+            900,
+        );
+        assert!(result.is_ok());
+    }
 }
@@ -2,13 +2,22 @@ use crate::core::error::AntiCheatError;
 use crate::stores::peer_store::PeerStore;
 use tracing::warn;
 
+/// Check the number of IPs a user announces a torrent from
+///
+/// With `dedupe_by_key`, IPs announced with the same client `key` count as one, so a
+/// single client roaming between networks is not flagged.
 pub fn check_duplicate_peer(
     peer_store: &PeerStore,
     user_id: u32,
     torrent_id: u32,
     max_ips: u32,
+    dedupe_by_key: bool,
 ) -> Result<(), AntiCheatError> {
-    let ip_count = peer_store.get_user_ip_count(user_id, torrent_id);
+    let ip_count = if dedupe_by_key {
+        peer_store.get_user_client_count(user_id, torrent_id)
+    } else {
+        peer_store.get_user_ip_count(user_id, torrent_id)
+    };
     
     if ip_count > max_ips as usize {
         warn!(
@@ -29,6 +38,37 @@ pub fn check_duplicate_peer(
     Ok(())
 }
 
+/// Check the number of peer IDs a user runs in one swarm (session multiplexing)
+///
+/// Catches many clients behind a single IP, which the IP count does not.
+pub fn check_peer_id_count(
+    peer_store: &PeerStore,
+    info_hash: [u8; 20],
+    user_id: u32,
+    torrent_id: u32,
+    max_peer_ids: u32,
+) -> Result<(), AntiCheatError> {
+    let peer_count = peer_store.get_user_peer_count(info_hash, user_id);
+    
+    if peer_count > max_peer_ids as usize {
+        warn!(
+            user_id = user_id,
+            torrent_id = torrent_id,
+            peer_count = peer_count,
+            max_peer_ids = max_peer_ids,
+            severity = "medium",
+            "Session multiplexing detected: user runs too many peer IDs in one swarm"
+        );
+        
+        return Err(AntiCheatError::TooManyPeerIds {
+            count: peer_count,
+            max: max_peer_ids,
+        });
+    }
+    
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,7 +111,7 @@ mod tests {
         store.add_peer(info_hash, peer2).unwrap();
         
         // Should pass with max_ips = 3
-        let result = check_duplicate_peer(&store, 1, 1, 3);
+        let result = check_duplicate_peer(&store, 1, 1, 3, false);
         assert!(result.is_ok());
     }
 
@@ -88,7 +128,7 @@ mod tests {
         }
         
         // Should fail with max_ips = 3
-        let result = check_duplicate_peer(&store, 1, 1, 3);
+        let result = check_duplicate_peer(&store, 1, 1, 3, false);
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(err.to_string().contains("Too many IPs"));
@@ -110,10 +150,50 @@ mod tests {
         store.add_peer(info_hash, peer2).unwrap();
         
         // Each user has only 1 IP, should pass
-        let result1 = check_duplicate_peer(&store, 1, 1, 1);
-        let result2 = check_duplicate_peer(&store, 2, 1, 1);
+        let result1 = check_duplicate_peer(&store, 1, 1, 1, false);
+        let result2 = check_duplicate_peer(&store, 2, 1, 1, false);
         
         assert!(result1.is_ok());
         assert!(result2.is_ok());
     }
+
+    #[test]
+    fn test_duplicate_peer_dedupe_by_key() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        
+        // One client roaming across 3 IPs with a stable key, plus a keyless client
+        for i in 0..3 {
+            let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, i + 1));
+            let mut peer = create_test_peer(1, 1, [1u8; 20], ip);
+            peer.key = Some("abcd1234".to_string());
+            store.add_peer(info_hash, peer).unwrap();
+        }
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        store.add_peer(info_hash, create_test_peer(1, 1, [2u8; 20], ip)).unwrap();
+        
+        assert!(check_duplicate_peer(&store, 1, 1, 2, false).is_err());
+        assert!(check_duplicate_peer(&store, 1, 1, 2, true).is_ok());
+        assert!(check_duplicate_peer(&store, 1, 1, 1, true).is_err());
+    }
+
+    #[test]
+    fn test_peer_id_count() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        
+        // Several clients behind a single IP
+        for i in 0..3 {
+            store.add_peer(info_hash, create_test_peer(1, 1, [i; 20], ip)).unwrap();
+        }
+        store.add_peer(info_hash, create_test_peer(2, 1, [9u8; 20], ip)).unwrap();
+        
+        assert!(check_duplicate_peer(&store, 1, 1, 1, false).is_ok());
+        assert!(check_peer_id_count(&store, info_hash, 1, 1, 3).is_ok());
+        
+        let result = check_peer_id_count(&store, info_hash, 1, 1, 2);
+        assert!(matches!(result, Err(AntiCheatError::TooManyPeerIds { count: 3, max: 2 })));
+        assert!(check_peer_id_count(&store, info_hash, 2, 1, 1).is_ok());
+    }
 }
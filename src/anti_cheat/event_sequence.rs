@@ -0,0 +1,77 @@
+use crate::core::error::AntiCheatError;
+use crate::validation::params::AnnounceEvent;
+use tracing::warn;
+
+/// Validate an announce event against the last event seen in the peer's session
+///
+/// `last_event` is `None` when the peer is not in the swarm, and `Some(None)` when it
+/// is but has only sent regular announces.
+pub fn check_event_sequence(
+    user_id: u32,
+    torrent_id: u32,
+    last_event: Option<Option<AnnounceEvent>>,
+    event: Option<AnnounceEvent>,
+) -> Result<(), AntiCheatError> {
+    let violation = match (event, last_event) {
+        (Some(AnnounceEvent::Started), Some(_)) => "started sent for a peer already in the swarm",
+        (Some(AnnounceEvent::Completed), None) => "completed sent by a peer that was never seen",
+        (Some(AnnounceEvent::Completed), Some(Some(AnnounceEvent::Completed))) => {
+            "completed sent twice in one session"
+        }
+        _ => return Ok(()),
+    };
+
+    warn!(
+        user_id = user_id,
+        torrent_id = torrent_id,
+        event = ?event,
+        last_event = ?last_event.flatten(),
+        severity = "medium",
+        "Invalid event sequence: {}",
+        violation
+    );
+
+    Err(AntiCheatError::InvalidEventSequence(violation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_sequence_normal_session() {
+        assert!(check_event_sequence(1, 1, None, Some(AnnounceEvent::Started)).is_ok());
+        assert!(check_event_sequence(1, 1, Some(Some(AnnounceEvent::Started)), None).is_ok());
+        assert!(check_event_sequence(1, 1, Some(None), Some(AnnounceEvent::Completed)).is_ok());
+        assert!(check_event_sequence(1, 1, Some(Some(AnnounceEvent::Completed)), Some(AnnounceEvent::Stopped)).is_ok());
+    }
+
+    #[test]
+    fn test_event_sequence_regular_announce_from_unknown_peer() {
+        // Peers reappear without started after a tracker restart
+        assert!(check_event_sequence(1, 1, None, None).is_ok());
+    }
+
+    #[test]
+    fn test_event_sequence_started_twice() {
+        let result = check_event_sequence(1, 1, Some(Some(AnnounceEvent::Started)), Some(AnnounceEvent::Started));
+        assert!(matches!(result, Err(AntiCheatError::InvalidEventSequence(_))));
+    }
+
+    #[test]
+    fn test_event_sequence_completed_without_session() {
+        let result = check_event_sequence(1, 1, None, Some(AnnounceEvent::Completed));
+        assert!(matches!(result, Err(AntiCheatError::InvalidEventSequence(_))));
+    }
+
+    #[test]
+    fn test_event_sequence_completed_twice() {
+        let result = check_event_sequence(
+            1,
+            1,
+            Some(Some(AnnounceEvent::Completed)),
+            Some(AnnounceEvent::Completed),
+        );
+        assert!(matches!(result, Err(AntiCheatError::InvalidEventSequence(_))));
+    }
+}
@@ -7,12 +7,18 @@ pub fn check_ghost_seeder(
     uploaded: u64,
     min_upload: u64,
     is_completed_event: bool,
+    freeleech_exempt: bool,
 ) -> Result<(), AntiCheatError> {
     // Skip check if not a seeder
     if !is_seeder {
         return Ok(());
     }
     
+    // Low upload is expected on freeleech torrents, which encourage downloading first
+    if freeleech_exempt {
+        return Ok(());
+    }
+    
     // Skip check if this is a completed event (peer just finished downloading)
     if is_completed_event {
         return Ok(());
@@ -47,6 +53,7 @@ mod tests {
             10_000_000,
             1_048_576, // 1 MB
             false,
+            false,
         );
         assert!(result.is_ok());
     }
@@ -62,6 +69,7 @@ mod tests {
             100_000,
             1_048_576, // 1 MB
             false,
+            false,
         );
         // Ghost seeder check logs warnings but doesn't fail
         assert!(result.is_ok());
@@ -77,6 +85,7 @@ mod tests {
             100_000,
             1_048_576,
             false,
+            false,
         );
         assert!(result.is_ok());
     }
@@ -91,6 +100,7 @@ mod tests {
             100_000,
             1_048_576,
             true, // completed event
+            false,
         );
         assert!(result.is_ok());
     }
@@ -105,6 +115,7 @@ mod tests {
             0,
             1_048_576,
             false,
+            false,
         );
         // Should log warning but not fail
         assert!(result.is_ok());
@@ -120,6 +131,22 @@ mod tests {
             1_048_576,
             1_048_576,
             false,
+            false,
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_ghost_seeder_freeleech_exempt() {
+        // Low upload on a freeleech torrent is skipped when exempted
+        let result = check_ghost_seeder(
+            1,
+            1,
+            true,
+            100_000,
+            1_048_576,
+            false,
+            true,
         );
         assert!(result.is_ok());
     }
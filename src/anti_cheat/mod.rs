@@ -2,6 +2,9 @@
 
 pub mod announce_interval;
 pub mod duplicate_peer;
+pub mod event_sequence;
 pub mod ghost_seeder;
 pub mod ratio_check;
+pub mod size_sanity;
 pub mod speed_check;
+pub mod violations;
@@ -0,0 +1,64 @@
+use crate::core::error::AntiCheatError;
+use crate::validation::params::AnnounceEvent;
+use tracing::warn;
+
+/// Check that a leecher does not report more bytes left than the torrent holds
+///
+/// Only started and regular announces are checked; `torrent_size` is 0 when the size is
+/// unknown, in which case the check is skipped.
+pub fn check_left_vs_size(
+    user_id: u32,
+    torrent_id: u32,
+    event: Option<AnnounceEvent>,
+    left: u64,
+    torrent_size: u64,
+) -> Result<(), AntiCheatError> {
+    if !matches!(event, None | Some(AnnounceEvent::Started)) {
+        return Ok(());
+    }
+
+    if torrent_size == 0 || left <= torrent_size {
+        return Ok(());
+    }
+
+    warn!(
+        user_id = user_id,
+        torrent_id = torrent_id,
+        left = left,
+        torrent_size = torrent_size,
+        severity = "medium",
+        "Leecher reports more bytes left than the torrent size"
+    );
+
+    Err(AntiCheatError::LeftExceedsSize { left, size: torrent_size })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_left_within_size() {
+        assert!(check_left_vs_size(1, 1, None, 500, 1000).is_ok());
+        assert!(check_left_vs_size(1, 1, Some(AnnounceEvent::Started), 1000, 1000).is_ok());
+        assert!(check_left_vs_size(1, 1, None, 0, 1000).is_ok());
+    }
+
+    #[test]
+    fn test_left_exceeds_size() {
+        let result = check_left_vs_size(1, 1, Some(AnnounceEvent::Started), 1001, 1000);
+        assert!(matches!(result, Err(AntiCheatError::LeftExceedsSize { left: 1001, size: 1000 })));
+        assert!(check_left_vs_size(1, 1, None, u64::MAX, 1000).is_err());
+    }
+
+    #[test]
+    fn test_unknown_size_skipped() {
+        assert!(check_left_vs_size(1, 1, None, u64::MAX, 0).is_ok());
+    }
+
+    #[test]
+    fn test_other_events_skipped() {
+        assert!(check_left_vs_size(1, 1, Some(AnnounceEvent::Completed), 5000, 1000).is_ok());
+        assert!(check_left_vs_size(1, 1, Some(AnnounceEvent::Stopped), 5000, 1000).is_ok());
+    }
+}
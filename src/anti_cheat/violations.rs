@@ -0,0 +1,123 @@
+// Per-user anti-cheat violation counts
+
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Anti-cheat check that flagged an announce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    AnnounceInterval,
+    DuplicatePeer,
+    PeerIdCount,
+    Speed,
+    Ratio,
+    GhostSeeder,
+    EventSequence,
+    SizeSanity,
+}
+
+/// Violations recorded for one user since startup, one counter per check
+#[derive(Debug, Default)]
+pub struct ViolationCounts {
+    announce_interval: AtomicU64,
+    duplicate_peer: AtomicU64,
+    peer_id_count: AtomicU64,
+    speed: AtomicU64,
+    ratio: AtomicU64,
+    ghost_seeder: AtomicU64,
+    event_sequence: AtomicU64,
+    size_sanity: AtomicU64,
+}
+
+/// Point-in-time copy of a user's violation counts
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ViolationSnapshot {
+    pub announce_interval: u64,
+    pub duplicate_peer: u64,
+    pub peer_id_count: u64,
+    pub speed: u64,
+    pub ratio: u64,
+    pub ghost_seeder: u64,
+    pub event_sequence: u64,
+    pub size_sanity: u64,
+}
+
+impl ViolationCounts {
+    fn counter(&self, kind: ViolationKind) -> &AtomicU64 {
+        match kind {
+            ViolationKind::AnnounceInterval => &self.announce_interval,
+            ViolationKind::DuplicatePeer => &self.duplicate_peer,
+            ViolationKind::PeerIdCount => &self.peer_id_count,
+            ViolationKind::Speed => &self.speed,
+            ViolationKind::Ratio => &self.ratio,
+            ViolationKind::GhostSeeder => &self.ghost_seeder,
+            ViolationKind::EventSequence => &self.event_sequence,
+            ViolationKind::SizeSanity => &self.size_sanity,
+        }
+    }
+
+    pub fn increment(&self, kind: ViolationKind) {
+        self.counter(kind).fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ViolationSnapshot {
+        ViolationSnapshot {
+            announce_interval: self.announce_interval.load(Ordering::Relaxed),
+            duplicate_peer: self.duplicate_peer.load(Ordering::Relaxed),
+            peer_id_count: self.peer_id_count.load(Ordering::Relaxed),
+            speed: self.speed.load(Ordering::Relaxed),
+            ratio: self.ratio.load(Ordering::Relaxed),
+            ghost_seeder: self.ghost_seeder.load(Ordering::Relaxed),
+            event_sequence: self.event_sequence.load(Ordering::Relaxed),
+            size_sanity: self.size_sanity.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl ViolationSnapshot {
+    /// Violations across all checks
+    pub fn total(&self) -> u64 {
+        self.announce_interval
+            + self.duplicate_peer
+            + self.peer_id_count
+            + self.speed
+            + self.ratio
+            + self.ghost_seeder
+            + self.event_sequence
+            + self.size_sanity
+    }
+}
+
+/// Count a violation for a user
+///
+/// Users seen before only take a shard read lock; the write lock is needed once per
+/// user, for their first violation.
+pub fn record_violation(counts: &DashMap<u32, ViolationCounts>, user_id: u32, kind: ViolationKind) {
+    if let Some(user_counts) = counts.get(&user_id) {
+        user_counts.increment(kind);
+        return;
+    }
+    counts.entry(user_id).or_default().increment(kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_violation() {
+        let counts = DashMap::new();
+        record_violation(&counts, 7, ViolationKind::Ratio);
+        record_violation(&counts, 7, ViolationKind::Ratio);
+        record_violation(&counts, 7, ViolationKind::GhostSeeder);
+        record_violation(&counts, 8, ViolationKind::Speed);
+
+        let snapshot = counts.get(&7).unwrap().snapshot();
+        assert_eq!(snapshot.ratio, 2);
+        assert_eq!(snapshot.ghost_seeder, 1);
+        assert_eq!(snapshot.speed, 0);
+        assert_eq!(snapshot.total(), 3);
+        assert_eq!(counts.get(&8).unwrap().snapshot().total(), 1);
+    }
+}
@@ -1,6 +1,10 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use tracing::warn;
+
+/// Consecutive bad pages after which `fetch_data` gives up on the sync
+const MAX_CONSECUTIVE_PAGE_FAILURES: u32 = 3;
 
 /// API client for communicating with the external backend
 pub struct ApiClient {
@@ -17,6 +21,9 @@ pub struct ApiData {
     pub pagination: Option<ApiPagination>,
     #[serde(default)]
     pub timestamp: Option<i64>,
+    /// Pages `fetch_data` skipped after they failed to load, leaving the data incomplete
+    #[serde(skip)]
+    pub skipped_pages: Vec<u32>,
 }
 
 /// Pagination information from API
@@ -33,6 +40,15 @@ pub struct ApiTorrent {
     pub id: u32,
     pub info_hash: String, // hex-encoded
     pub is_freeleech: bool,
+    /// Total size in bytes; older backends omit it
+    #[serde(default)]
+    pub size: u64,
+    /// Per-torrent announce interval override in seconds
+    #[serde(default)]
+    pub announce_interval: Option<i64>,
+    /// Per-torrent peer timeout override in seconds
+    #[serde(default)]
+    pub peer_timeout: Option<i64>,
     #[serde(default)]
     pub seeders: u32,
     #[serde(default)]
@@ -97,33 +113,40 @@ impl ApiClient {
 
     /// Fetch user and torrent data from the external API
     /// Handles pagination automatically by fetching all pages
+    ///
+    /// A page that fails to load (error status or malformed JSON) is logged and skipped;
+    /// the fetch only fails after `MAX_CONSECUTIVE_PAGE_FAILURES` bad pages in a row.
+    /// Skipped pages are listed in `ApiData::skipped_pages` for the caller to act on.
     pub async fn fetch_data(&self) -> Result<ApiData> {
         let mut all_torrents = Vec::new();
         let mut all_users = Vec::new();
         let mut page = 1;
         let mut last_pagination = None;
         let mut last_timestamp = None;
+        let mut consecutive_failures = 0;
+        let mut skipped_pages = Vec::new();
 
         loop {
-            let response = self
-                .client
-                .get(&self.endpoint)
-                .query(&[("api_key", &self.api_key), ("page", &page.to_string())])
-                .send()
-                .await
-                .context("Failed to send request to external API")?;
-
-            if !response.status().is_success() {
-                bail!(
-                    "External API returned error status: {}",
-                    response.status()
-                );
-            }
+            let data = match self.fetch_page(page, None).await {
+                Ok(data) => {
+                    consecutive_failures = 0;
+                    data
+                }
+                Err(e) => {
+                    consecutive_failures += 1;
+                    if consecutive_failures >= MAX_CONSECUTIVE_PAGE_FAILURES {
+                        return Err(e.context(format!(
+                            "Giving up after {} consecutive bad pages",
+                            consecutive_failures
+                        )));
+                    }
 
-            let data = response
-                .json::<ApiData>()
-                .await
-                .context("Failed to parse JSON response from external API")?;
+                    warn!(page = page, error = %format!("{:#}", e), "Skipping bad page from external API");
+                    skipped_pages.push(page);
+                    page += 1;
+                    continue;
+                }
+            };
 
             let has_more = !data.torrents.is_empty() || !data.users.is_empty();
             
@@ -149,14 +172,79 @@ impl ApiClient {
             }
         }
 
+        if !skipped_pages.is_empty() {
+            warn!(
+                skipped_pages = ?skipped_pages,
+                "External API sync completed with skipped pages, data is incomplete"
+            );
+        }
+
         Ok(ApiData {
             torrents: all_torrents,
             users: all_users,
             pagination: last_pagination,
             timestamp: last_timestamp,
+            skipped_pages,
         })
     }
 
+    async fn fetch_page(&self, page: u32, filter: Option<(&str, &str)>) -> Result<ApiData> {
+        let mut request = self
+            .client
+            .get(&self.endpoint)
+            .query(&[("api_key", &self.api_key), ("page", &page.to_string())]);
+        if let Some(filter) = filter {
+            request = request.query(&[filter]);
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to send request to external API")?;
+
+        if !response.status().is_success() {
+            bail!(
+                "External API returned error status: {}",
+                response.status()
+            );
+        }
+
+        let body = response
+            .text()
+            .await
+            .context("Failed to read response from external API")?;
+        parse_page(&body).context("Failed to parse JSON response from external API")
+    }
+
+    /// Fetch the first page and check it against the schema the tracker expects
+    ///
+    /// Used by `--verify-api` to catch schema drift at deploy time. Returns the number
+    /// of torrents and users on the page.
+    pub async fn verify_schema(&self) -> Result<(usize, usize)> {
+        let data = self.fetch_page(1, None).await?;
+        Ok((data.torrents.len(), data.users.len()))
+    }
+
+    /// Look up a single user by passkey
+    ///
+    /// Requests the first page with an extra `passkey` parameter, which the backend may
+    /// use to return only that user. Users with other passkeys in the response are
+    /// ignored, so a backend that ignores the filter still works for small datasets.
+    pub async fn fetch_user(&self, passkey: &str) -> Result<Option<ApiUser>> {
+        let data = self.fetch_page(1, Some(("passkey", passkey))).await?;
+        Ok(data.users.into_iter().find(|user| user.passkey == passkey))
+    }
+
+    /// Look up a single torrent by hex info_hash, like `fetch_user` with an `info_hash`
+    /// parameter
+    pub async fn fetch_torrent(&self, info_hash: &str) -> Result<Option<ApiTorrent>> {
+        let data = self.fetch_page(1, Some(("info_hash", info_hash))).await?;
+        Ok(data
+            .torrents
+            .into_iter()
+            .find(|torrent| torrent.info_hash.eq_ignore_ascii_case(info_hash)))
+    }
+
     /// Upload peer data to the external API
     pub async fn upload_peer_data(&self, data: UpdateData) -> Result<()> {
         let response = self
@@ -179,6 +267,19 @@ impl ApiClient {
     }
 }
 
+/// Parse one page of the external API, naming the offending field on failure
+///
+/// serde_json alone only reports a line and column, which is of little help in a
+/// single-line response of thousands of entries.
+fn parse_page(body: &str) -> Result<ApiData> {
+    let deserializer = &mut serde_json::Deserializer::from_str(body);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| match e.path().to_string().as_str() {
+        // Syntax errors before any field is reached have no useful path
+        "." | "?" => anyhow!("{}", e.inner()),
+        path => anyhow!("field '{}': {}", path, e.inner()),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -220,4 +321,95 @@ mod tests {
         let json = serde_json::to_string(&update);
         assert!(json.is_ok());
     }
+
+    /// Serve `pages[n - 1]` as the body of page `n` and an empty page past the end
+    async fn spawn_api_server(pages: Vec<&'static str>) -> String {
+        use axum::{extract::Query, routing::get, Router};
+        use std::collections::HashMap;
+
+        let app = Router::new().route(
+            "/api",
+            get(move |Query(params): Query<HashMap<String, String>>| async move {
+                let page: usize = params["page"].parse().unwrap();
+                pages
+                    .get(page - 1)
+                    .copied()
+                    .unwrap_or(r#"{"torrents":[],"users":[]}"#)
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{}/api", addr)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_data_skips_bad_page() {
+        let torrent = r#"{"torrents":[{"id":1,"info_hash":"0101010101010101010101010101010101010101","is_freeleech":false}],"users":[]}"#;
+        let user = r#"{"torrents":[],"users":[{"id":2,"passkey":"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa","user_class_id":1,"can_download":true}]}"#;
+        let endpoint = spawn_api_server(vec![torrent, "{not json", user]).await;
+
+        let client = ApiClient::new(endpoint, "key".to_string()).unwrap();
+        let data = client.fetch_data().await.unwrap();
+
+        assert_eq!(data.torrents.len(), 1);
+        assert_eq!(data.users.len(), 1);
+        assert_eq!(data.skipped_pages, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_data_gives_up_after_consecutive_failures() {
+        let endpoint = spawn_api_server(vec!["{not json"; MAX_CONSECUTIVE_PAGE_FAILURES as usize]).await;
+
+        let client = ApiClient::new(endpoint, "key".to_string()).unwrap();
+        assert!(client.fetch_data().await.is_err());
+    }
+
+    #[test]
+    fn test_parse_page_names_field() {
+        let mistyped = r#"{"torrents":[{"id":1,"info_hash":"01","is_freeleech":false},{"id":2,"info_hash":5,"is_freeleech":false}],"users":[]}"#;
+        let error = parse_page(mistyped).unwrap_err().to_string();
+        assert!(error.starts_with("field 'torrents[1].info_hash': invalid type: integer `5`"), "{}", error);
+
+        let missing = r#"{"torrents":[],"users":[{"id":2,"passkey":"aa","can_download":true}]}"#;
+        let error = parse_page(missing).unwrap_err().to_string();
+        assert!(error.starts_with("field 'users[0]': missing field `user_class_id`"), "{}", error);
+
+        let error = parse_page("{not json").unwrap_err().to_string();
+        assert!(error.starts_with("key must be a string"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_user() {
+        let users = r#"{"torrents":[],"users":[{"id":2,"passkey":"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa","user_class_id":1,"can_download":true}]}"#;
+        let client = ApiClient::new(spawn_api_server(vec![users]).await, "key".to_string()).unwrap();
+
+        let user = client.fetch_user(&"a".repeat(32)).await.unwrap().unwrap();
+        assert_eq!(user.id, 2);
+        assert!(client.fetch_user(&"b".repeat(32)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_torrent() {
+        let torrents = r#"{"torrents":[{"id":1,"info_hash":"0101010101010101010101010101010101010101","is_freeleech":false}],"users":[]}"#;
+        let client = ApiClient::new(spawn_api_server(vec![torrents]).await, "key".to_string()).unwrap();
+
+        let torrent = client.fetch_torrent(&"01".repeat(20)).await.unwrap().unwrap();
+        assert_eq!(torrent.id, 1);
+        assert!(client.fetch_torrent(&"02".repeat(20)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_verify_schema() {
+        let page = r#"{"torrents":[{"id":1,"info_hash":"0101010101010101010101010101010101010101","is_freeleech":false}],"users":[]}"#;
+        let client = ApiClient::new(spawn_api_server(vec![page]).await, "key".to_string()).unwrap();
+        assert_eq!(client.verify_schema().await.unwrap(), (1, 0));
+
+        let page = r#"{"torrents":[{"id":1,"info_hash":"01","is_freeleech":"no"}],"users":[]}"#;
+        let client = ApiClient::new(spawn_api_server(vec![page]).await, "key".to_string()).unwrap();
+        let error = format!("{:#}", client.verify_schema().await.unwrap_err());
+        assert!(error.contains("field 'torrents[0].is_freeleech'"), "{}", error);
+    }
 }
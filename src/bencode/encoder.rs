@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 pub trait BencodeEncode {
     fn bencode(&self, buf: &mut Vec<u8>);
 }
@@ -53,6 +55,72 @@ pub fn encode_dict<K: BencodeEncode, V: BencodeEncode>(
     buf.extend_from_slice(b"e");
 }
 
+/// Builds a bencode dictionary with keys emitted in sorted order
+///
+/// Bencode requires dictionary keys in lexicographic byte order and strict clients reject
+/// anything else, so keys may be inserted in any order. Values are encoded into a shared
+/// buffer as they are inserted and reordered once in `finish`.
+pub struct BencodeDictBuilder<'a> {
+    values: Vec<u8>,
+    entries: Vec<(&'a str, Range<usize>)>,
+}
+
+impl<'a> BencodeDictBuilder<'a> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Create a builder with room for `capacity` bytes of encoded values
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(capacity),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn insert<V: BencodeEncode>(&mut self, key: &'a str, value: V) -> &mut Self {
+        self.insert_with(key, |buf| value.bencode(buf))
+    }
+
+    /// Insert a value written by `encode`, for values that are not a single `BencodeEncode`
+    /// (compact peer strings, nested lists). `encode` must write exactly one bencoded value.
+    pub fn insert_with(&mut self, key: &'a str, encode: impl FnOnce(&mut Vec<u8>)) -> &mut Self {
+        let start = self.values.len();
+        encode(&mut self.values);
+        self.entries.push((key, start..self.values.len()));
+        self
+    }
+
+    /// Append the dictionary to `buf`
+    pub fn encode_into(mut self, buf: &mut Vec<u8>) {
+        self.entries.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+        debug_assert!(
+            self.entries.windows(2).all(|pair| pair[0].0 != pair[1].0),
+            "duplicate bencode dictionary key"
+        );
+
+        buf.reserve(self.values.len() + self.entries.len() * 16 + 2);
+        buf.extend_from_slice(b"d");
+        for (key, range) in &self.entries {
+            key.bencode(buf);
+            buf.extend_from_slice(&self.values[range.clone()]);
+        }
+        buf.extend_from_slice(b"e");
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_into(&mut buf);
+        buf
+    }
+}
+
+impl Default for BencodeDictBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,4 +179,44 @@ mod tests {
         encode_dict(&[("bar", 100i64), ("foo", 42i64)], &mut buf);
         assert_eq!(buf, b"d3:bari100e3:fooi42ee");
     }
+
+    #[test]
+    fn test_dict_builder_sorts_keys() {
+        let mut dict = BencodeDictBuilder::new();
+        dict.insert("peers", "xyz")
+            .insert("min interval", 900i64)
+            .insert("interval", 1800i64)
+            .insert_with("peers6", |buf| buf.extend_from_slice(b"0:"))
+            .insert("complete", 1i64);
+
+        assert_eq!(
+            dict.finish(),
+            b"d8:completei1e8:intervali1800e12:min intervali900e5:peers3:xyz6:peers60:e".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_dict_builder_byte_order() {
+        // Byte order, not case-insensitive: uppercase sorts before lowercase
+        let mut dict = BencodeDictBuilder::new();
+        dict.insert("b", 2i64).insert("a", 1i64).insert("B", 0i64);
+        assert_eq!(dict.finish(), b"d1:Bi0e1:ai1e1:bi2ee".to_vec());
+
+        assert_eq!(BencodeDictBuilder::new().finish(), b"de".to_vec());
+    }
+
+    #[test]
+    fn test_dict_builder_nested() {
+        let mut inner = BencodeDictBuilder::new();
+        inner.insert("port", 6881i64).insert("ip", "1.2.3.4");
+
+        let mut outer = BencodeDictBuilder::new();
+        outer.insert_with("peers", |buf| {
+            buf.extend_from_slice(b"l");
+            inner.encode_into(buf);
+            buf.extend_from_slice(b"e");
+        });
+
+        assert_eq!(outer.finish(), b"d5:peersld2:ip7:1.2.3.44:porti6881eeee".to_vec());
+    }
 }
@@ -1,7 +1,50 @@
 use crate::models::peer::Peer;
-use std::net::IpAddr;
+use axum::{
+    http::{header, StatusCode},
+    response::Response,
+};
+
+use super::encoder::{BencodeDictBuilder, BencodeEncode};
+use crate::utils::auth::hmac_hex;
+
+/// Content type of bencoded responses unless `announce.content_type` overrides it
+pub const DEFAULT_CONTENT_TYPE: &str = "text/plain";
+
+/// Response extension marking a bencoded body, so the announce route can apply the
+/// configured content type to successes and failures alike
+#[derive(Debug, Clone, Copy)]
+pub struct BencodeBody;
+
+/// Wrap a bencoded body in a 200 response with the default content type
+pub fn bencode_http_response(body: Vec<u8>) -> Response {
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, DEFAULT_CONTENT_TYPE)
+        .body(body.into())
+        .unwrap();
+    response.extensions_mut().insert(BencodeBody);
+    response
+}
 
-use super::encoder::BencodeEncode;
+/// How an announce response is laid out, beyond the peers and counts it carries
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnnounceResponseOptions {
+    /// Announce interval in seconds; `min interval` is sent as half of it
+    pub interval: i64,
+    /// Compact format (true) or dictionary format (false)
+    pub compact: bool,
+    /// Include the `peers6` key in compact format
+    pub emit_peers6: bool,
+    /// Leave out the compact `peers`/`peers6` keys when they would be empty, for clients
+    /// that choke on an empty `0:` string
+    pub omit_empty_peers: bool,
+    /// Add a non-standard `freeleech` key set to 1
+    pub freeleech: bool,
+    /// Most IPv4 peers listed, 0 for no cap; in compact format it applies to `peers`
+    pub max_peers_ipv4: u32,
+    /// Most IPv6 peers listed, 0 for no cap; in compact format it applies to `peers6`
+    pub max_peers_ipv6: u32,
+}
 
 /// Build a bencode-encoded announce response
 ///
@@ -9,7 +52,7 @@ use super::encoder::BencodeEncode;
 /// * `peers` - List of peers to include in response
 /// * `seeders` - Total number of seeders for this torrent
 /// * `leechers` - Total number of leechers for this torrent
-/// * `compact` - Whether to use compact format (true) or dictionary format (false)
+/// * `options` - Interval, format and per-family caps
 ///
 /// # Returns
 /// A bencode-encoded response as bytes
@@ -17,48 +60,98 @@ pub fn build_announce_response(
     peers: &[Peer],
     seeders: u32,
     leechers: u32,
-    compact: bool,
+    options: &AnnounceResponseOptions,
 ) -> Vec<u8> {
+    let AnnounceResponseOptions {
+        interval,
+        compact,
+        emit_peers6,
+        omit_empty_peers,
+        freeleech,
+        max_peers_ipv4,
+        max_peers_ipv6,
+    } = *options;
+    let cap = |max: u32| if max == 0 { usize::MAX } else { max as usize };
+    let (max_ipv4, max_ipv6) = (cap(max_peers_ipv4), cap(max_peers_ipv6));
+
     let capacity = if compact {
         100 + (peers.len() * 6)
     } else {
         100 + (peers.len() * 50)
     };
-    let mut buf = Vec::with_capacity(capacity);
+    let mut dict = BencodeDictBuilder::with_capacity(capacity);
 
-    buf.extend_from_slice(b"d");
+    dict.insert("complete", seeders as i64)
+        .insert("incomplete", leechers as i64)
+        .insert("interval", interval)
+        .insert("min interval", interval / 2);
 
-    "complete".bencode(&mut buf);
-    (seeders as i64).bencode(&mut buf);
+    if freeleech {
+        dict.insert("freeleech", 1i64);
+    }
 
-    "incomplete".bencode(&mut buf);
-    (leechers as i64).bencode(&mut buf);
+    if compact {
+        // Dual-stack peers appear in both lists
+        let has_ipv4 = peers.iter().any(|p| p.ipv4().is_some());
+        let has_ipv6 = peers.iter().any(|p| p.ipv6().is_some());
 
-    "interval".bencode(&mut buf);
-    1800i64.bencode(&mut buf);
+        if has_ipv4 || !omit_empty_peers {
+            dict.insert_with("peers", |buf| encode_compact_peers(peers, max_ipv4, buf));
+        }
 
-    "min interval".bencode(&mut buf);
-    900i64.bencode(&mut buf);
+        if emit_peers6 && (has_ipv6 || !omit_empty_peers) {
+            dict.insert_with("peers6", |buf| encode_compact_peers_ipv6(peers, max_ipv6, buf));
+        }
+    } else {
+        dict.insert_with("peers", |buf| encode_dict_peers(peers, max_ipv4, max_ipv6, buf));
+    }
 
-    if compact {
-        "peers".bencode(&mut buf);
-        encode_compact_peers(peers, &mut buf);
+    dict.finish()
+}
 
-        "peers6".bencode(&mut buf);
-        encode_compact_peers_ipv6(peers, &mut buf);
-    } else {
-        "peers".bencode(&mut buf);
-        encode_dict_peers(peers, &mut buf);
+/// Append a `signature` key to a bencoded announce response
+///
+/// The value is the hex HMAC-SHA256 of the response as encoded without the key, so a
+/// client holding the secret strips it and recomputes. Dictionary keys must stay sorted;
+/// `signature` sorts after every key an announce response carries, so it goes right
+/// before the closing `e`.
+pub fn sign_response(response: &mut Vec<u8>, secret: &str) {
+    if !response.starts_with(b"d") || response.last() != Some(&b'e') {
+        return;
     }
 
-    buf.extend_from_slice(b"e");
+    let signature = hmac_hex(secret, response);
+    response.pop();
+    "signature".bencode(response);
+    signature.as_str().bencode(response);
+    response.push(b'e');
+}
+
+/// Build an empty announce response for a retired torrent or a shed announce
+///
+/// Carries no peers, a long `interval` so clients back off, and a `warning message`
+/// explaining why. Unlike a failure, clients treat this as a successful announce.
+pub fn build_retired_response(interval: i64, warning: &str, compact: bool) -> Vec<u8> {
+    let mut dict = BencodeDictBuilder::with_capacity(64 + warning.len());
+
+    dict.insert("complete", 0i64)
+        .insert("incomplete", 0i64)
+        .insert("interval", interval)
+        .insert("min interval", interval)
+        .insert("warning message", warning);
+
+    if compact {
+        dict.insert("peers", b"".as_slice());
+    } else {
+        dict.insert_with("peers", |buf| buf.extend_from_slice(b"le"));
+    }
 
-    buf
+    dict.finish()
 }
 
-/// Encode IPv4 peers in compact format (6 bytes per peer: 4 for IP, 2 for port)
-fn encode_compact_peers(peers: &[Peer], buf: &mut Vec<u8>) {
-    let ipv4_count = peers.iter().filter(|p| matches!(p.ip, IpAddr::V4(_))).count();
+/// Encode up to `max` IPv4 peers in compact format (6 bytes per peer: 4 for IP, 2 for port)
+fn encode_compact_peers(peers: &[Peer], max: usize, buf: &mut Vec<u8>) {
+    let ipv4_count = peers.iter().filter(|p| p.ipv4().is_some()).count().min(max);
 
     if ipv4_count == 0 {
         buf.extend_from_slice(b"0:");
@@ -73,16 +166,15 @@ fn encode_compact_peers(peers: &[Peer], buf: &mut Vec<u8>) {
 
     buf.reserve(peer_bytes);
 
-    for peer in peers.iter().filter(|p| matches!(p.ip, IpAddr::V4(_))) {
-        if let IpAddr::V4(ip) = peer.ip {
-            buf.extend_from_slice(&ip.octets());
-            buf.extend_from_slice(&peer.port.to_be_bytes());
-        }
+    for (ip, port) in peers.iter().filter_map(|p| Some((p.ipv4()?, p.port))).take(ipv4_count) {
+        buf.extend_from_slice(&ip.octets());
+        buf.extend_from_slice(&port.to_be_bytes());
     }
 }
 
-fn encode_compact_peers_ipv6(peers: &[Peer], buf: &mut Vec<u8>) {
-    let ipv6_count = peers.iter().filter(|p| matches!(p.ip, IpAddr::V6(_))).count();
+/// Encode up to `max` IPv6 peers in compact format (18 bytes per peer)
+fn encode_compact_peers_ipv6(peers: &[Peer], max: usize, buf: &mut Vec<u8>) {
+    let ipv6_count = peers.iter().filter(|p| p.ipv6().is_some()).count().min(max);
 
     if ipv6_count == 0 {
         buf.extend_from_slice(b"0:");
@@ -97,30 +189,33 @@ fn encode_compact_peers_ipv6(peers: &[Peer], buf: &mut Vec<u8>) {
 
     buf.reserve(peer_bytes);
 
-    for peer in peers.iter().filter(|p| matches!(p.ip, IpAddr::V6(_))) {
-        if let IpAddr::V6(ip) = peer.ip {
-            buf.extend_from_slice(&ip.octets());
-            buf.extend_from_slice(&peer.port.to_be_bytes());
-        }
+    for (ip, port) in peers.iter().filter_map(|p| Some((p.ipv6()?, p.port))).take(ipv6_count) {
+        buf.extend_from_slice(&ip.octets());
+        buf.extend_from_slice(&port.to_be_bytes());
     }
 }
 
-fn encode_dict_peers(peers: &[Peer], buf: &mut Vec<u8>) {
+/// Encode peers as a list of dictionaries, at most `max_ipv4`/`max_ipv6` by `ip` family
+fn encode_dict_peers(peers: &[Peer], max_ipv4: usize, max_ipv6: usize, buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"l");
 
+    let (mut ipv4_count, mut ipv6_count) = (0, 0);
     for peer in peers {
-        buf.extend_from_slice(b"d");
-
-        "ip".bencode(buf);
-        peer.ip.to_string().as_str().bencode(buf);
-
-        "peer id".bencode(buf);
-        peer.peer_id.as_slice().bencode(buf);
-
-        "port".bencode(buf);
-        (peer.port as i64).bencode(buf);
+        let (count, max) = if peer.ip.is_ipv4() {
+            (&mut ipv4_count, max_ipv4)
+        } else {
+            (&mut ipv6_count, max_ipv6)
+        };
+        if *count == max {
+            continue;
+        }
+        *count += 1;
 
-        buf.extend_from_slice(b"e");
+        let mut dict = BencodeDictBuilder::with_capacity(64);
+        dict.insert("ip", peer.ip.to_string().as_str())
+            .insert("peer id", peer.peer_id.as_slice())
+            .insert("port", peer.port as i64);
+        dict.encode_into(buf);
     }
 
     buf.extend_from_slice(b"e");
@@ -131,6 +226,16 @@ mod tests {
     use super::*;
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
+    /// Compact responses with `peers6` and a 30 minute interval
+    fn compact_options() -> AnnounceResponseOptions {
+        AnnounceResponseOptions {
+            interval: 1800,
+            compact: true,
+            emit_peers6: true,
+            ..Default::default()
+        }
+    }
+
     fn create_test_peer_ipv4(ip: Ipv4Addr, port: u16) -> Peer {
         Peer::new(
             1,
@@ -168,7 +273,7 @@ mod tests {
             create_test_peer_ipv4(Ipv4Addr::new(10, 0, 0, 1), 51413),
         ];
 
-        let response = build_announce_response(&peers, 5, 3, true);
+        let response = build_announce_response(&peers, 5, 3, &compact_options());
         let response_str = String::from_utf8_lossy(&response);
 
         // Check that response is a valid bencode dictionary
@@ -184,11 +289,28 @@ mod tests {
         assert!(response_str.contains("peers6"));
     }
 
+    #[test]
+    fn test_build_announce_response_without_peers6() {
+        let peers = vec![
+            create_test_peer_ipv4(Ipv4Addr::new(192, 168, 1, 1), 6881),
+            create_test_peer_ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 6881),
+        ];
+
+        let options = AnnounceResponseOptions { emit_peers6: false, ..compact_options() };
+        let response = build_announce_response(&peers, 1, 1, &options);
+        let response_str = String::from_utf8_lossy(&response);
+
+        // Only the single IPv4 peer is encoded, under "peers"
+        assert!(response_str.contains("5:peers6:"));
+        assert!(!response_str.contains("6:peers6"));
+    }
+
     #[test]
     fn test_build_announce_response_dict() {
         let peers = vec![create_test_peer_ipv4(Ipv4Addr::new(192, 168, 1, 1), 6881)];
 
-        let response = build_announce_response(&peers, 5, 3, false);
+        let options = AnnounceResponseOptions { compact: false, ..compact_options() };
+        let response = build_announce_response(&peers, 5, 3, &options);
         let response_str = String::from_utf8_lossy(&response);
 
         // Check that response is a valid bencode dictionary
@@ -202,6 +324,101 @@ mod tests {
         assert!(response_str.contains("peer id"));
     }
 
+    #[test]
+    fn test_build_announce_response_omit_empty_all_ipv4() {
+        let peers = vec![create_test_peer_ipv4(Ipv4Addr::new(192, 168, 1, 1), 6881)];
+
+        let options = AnnounceResponseOptions { omit_empty_peers: true, ..compact_options() };
+        let response = build_announce_response(&peers, 1, 0, &options);
+        let mut expected = b"d8:completei1e10:incompletei0e8:intervali1800e12:min intervali900e5:peers6:".to_vec();
+        expected.extend_from_slice(&[192, 168, 1, 1, 0x1a, 0xe1]);
+        expected.push(b'e');
+        assert_eq!(response, expected);
+
+        // Without the flag the empty peers6 string is still sent
+        let response = build_announce_response(&peers, 1, 0, &compact_options());
+        assert!(response.ends_with(b"6:peers60:e"));
+    }
+
+    #[test]
+    fn test_build_announce_response_omit_empty_all_ipv6() {
+        let peers = vec![create_test_peer_ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 6881)];
+
+        let options = AnnounceResponseOptions { omit_empty_peers: true, ..compact_options() };
+        let response = build_announce_response(&peers, 1, 0, &options);
+        let response_str = String::from_utf8_lossy(&response);
+        assert!(!response_str.contains("5:peers"));
+        assert!(response_str.contains("6:peers618:"));
+
+        let response = build_announce_response(&peers, 1, 0, &compact_options());
+        assert!(String::from_utf8_lossy(&response).contains("5:peers0:6:peers618:"));
+    }
+
+    #[test]
+    fn test_build_announce_response_keys_sorted() {
+        let peers = vec![
+            create_test_peer_ipv4(Ipv4Addr::new(192, 168, 1, 1), 6881),
+            create_test_peer_ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 6881),
+        ];
+
+        for compact in [true, false] {
+            let options = AnnounceResponseOptions { compact, ..compact_options() };
+            let response = build_announce_response(&peers, 1, 1, &options);
+            let keys = ["8:complete", "10:incomplete", "8:interval", "12:min interval", "5:peers"];
+            let positions: Vec<usize> = keys
+                .iter()
+                .map(|key| {
+                    response
+                        .windows(key.len())
+                        .position(|w| w == key.as_bytes())
+                        .unwrap()
+                })
+                .collect();
+
+            assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+
+        // Peer dicts in non-compact mode are sorted too
+        let options = AnnounceResponseOptions { compact: false, ..compact_options() };
+        let response = build_announce_response(&peers[..1], 1, 0, &options);
+        assert!(String::from_utf8_lossy(&response).contains("d2:ip11:192.168.1.17:peer id20:"));
+    }
+
+    #[test]
+    fn test_build_announce_response_interval() {
+        let peers = vec![create_test_peer_ipv4(Ipv4Addr::new(192, 168, 1, 1), 6881)];
+
+        let options = AnnounceResponseOptions { interval: 3600, ..compact_options() };
+        let response = build_announce_response(&peers, 1, 0, &options);
+        assert!(String::from_utf8_lossy(&response).contains("8:intervali3600e12:min intervali1800e"));
+    }
+
+    #[test]
+    fn test_build_announce_response_freeleech() {
+        let options = AnnounceResponseOptions { emit_peers6: false, freeleech: true, ..compact_options() };
+        let response = build_announce_response(&[], 1, 0, &options);
+        assert_eq!(
+            response,
+            b"d8:completei1e9:freeleechi1e10:incompletei0e8:intervali1800e12:min intervali900e5:peers0:e".to_vec()
+        );
+
+        let options = AnnounceResponseOptions { emit_peers6: false, ..compact_options() };
+        let response = build_announce_response(&[], 1, 0, &options);
+        assert!(!response.windows(9).any(|w| w == b"freeleech"));
+    }
+
+    #[test]
+    fn test_build_retired_response() {
+        let response = build_retired_response(604800, "Torrent retired", true);
+        assert_eq!(
+            response,
+            b"d8:completei0e10:incompletei0e8:intervali604800e12:min intervali604800e5:peers0:15:warning message15:Torrent retirede".to_vec()
+        );
+
+        let response = build_retired_response(60, "Bye", false);
+        assert!(String::from_utf8_lossy(&response).contains("5:peersle"));
+    }
+
     #[test]
     fn test_encode_compact_peers_ipv4() {
         let peers = vec![
@@ -210,7 +427,7 @@ mod tests {
         ];
 
         let mut buf = Vec::new();
-        encode_compact_peers(&peers, &mut buf);
+        encode_compact_peers(&peers, usize::MAX, &mut buf);
 
         // Should be: "12:" followed by 12 bytes (2 peers * 6 bytes each)
         assert_eq!(&buf[0..3], b"12:");
@@ -233,7 +450,7 @@ mod tests {
         )];
 
         let mut buf = Vec::new();
-        encode_compact_peers_ipv6(&peers, &mut buf);
+        encode_compact_peers_ipv6(&peers, usize::MAX, &mut buf);
 
         // Should be: "18:" followed by 18 bytes (1 peer * 18 bytes)
         assert_eq!(&buf[0..3], b"18:");
@@ -252,7 +469,7 @@ mod tests {
         let peers: Vec<Peer> = vec![];
 
         let mut buf = Vec::new();
-        encode_compact_peers(&peers, &mut buf);
+        encode_compact_peers(&peers, usize::MAX, &mut buf);
 
         // Should be empty byte string
         assert_eq!(buf, b"0:");
@@ -267,15 +484,63 @@ mod tests {
 
         // IPv4 encoding should only include IPv4 peers
         let mut buf = Vec::new();
-        encode_compact_peers(&peers, &mut buf);
+        encode_compact_peers(&peers, usize::MAX, &mut buf);
         assert_eq!(&buf[0..2], b"6:"); // Only 1 IPv4 peer = 6 bytes
 
         // IPv6 encoding should only include IPv6 peers
         let mut buf = Vec::new();
-        encode_compact_peers_ipv6(&peers, &mut buf);
+        encode_compact_peers_ipv6(&peers, usize::MAX, &mut buf);
         assert_eq!(&buf[0..3], b"18:"); // Only 1 IPv6 peer = 18 bytes
     }
 
+    #[test]
+    fn test_encode_compact_peers_dual_stack() {
+        let mut peer = create_test_peer_ipv4(Ipv4Addr::new(192, 168, 1, 1), 6881);
+        peer.alt_ip = Some(IpAddr::V6(Ipv6Addr::LOCALHOST));
+        let peers = vec![peer];
+
+        let mut buf = Vec::new();
+        encode_compact_peers(&peers, usize::MAX, &mut buf);
+        assert_eq!(&buf[..2], b"6:");
+        assert_eq!(&buf[2..6], &[192, 168, 1, 1]);
+
+        let mut buf = Vec::new();
+        encode_compact_peers_ipv6(&peers, usize::MAX, &mut buf);
+        assert_eq!(&buf[..3], b"18:");
+        assert_eq!(&buf[3..19], &Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(&buf[19..21], &6881u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_response_caps_peers_per_family() {
+        let peers: Vec<Peer> = (1..=4u16)
+            .flat_map(|i| {
+                [
+                    create_test_peer_ipv4(Ipv4Addr::new(10, 0, 0, i as u8), 6881),
+                    create_test_peer_ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, i), 6881),
+                ]
+            })
+            .collect();
+
+        // 3 of 4 IPv4 peers, 1 of 4 IPv6 peers
+        let options = AnnounceResponseOptions { max_peers_ipv4: 3, max_peers_ipv6: 1, ..compact_options() };
+        let response = build_announce_response(&peers, 0, 8, &options);
+        assert!(response.windows(10).any(|w| w == b"5:peers18:"));
+        assert!(response.windows(11).any(|w| w == b"6:peers618:"));
+
+        // Dictionary format caps by each peer's ip
+        let options = AnnounceResponseOptions { compact: false, ..options };
+        let response = build_announce_response(&peers, 0, 8, &options);
+        let count = |needle: &[u8]| response.windows(needle.len()).filter(|w| *w == needle).count();
+        assert_eq!(count(b"2:ip8:10.0.0."), 3);
+        assert_eq!(count(b"2:ip11:2001:db8::"), 1);
+
+        // 0 leaves a family uncapped
+        let response = build_announce_response(&peers, 0, 8, &compact_options());
+        assert!(response.windows(10).any(|w| w == b"5:peers24:"));
+        assert!(response.windows(11).any(|w| w == b"6:peers672:"));
+    }
+
     #[test]
     fn test_encode_dict_peers() {
         let mut peer = create_test_peer_ipv4(Ipv4Addr::new(192, 168, 1, 1), 6881);
@@ -284,7 +549,7 @@ mod tests {
         let peers = vec![peer];
 
         let mut buf = Vec::new();
-        encode_dict_peers(&peers, &mut buf);
+        encode_dict_peers(&peers, usize::MAX, usize::MAX, &mut buf);
 
         let result = String::from_utf8_lossy(&buf);
 
@@ -296,4 +561,22 @@ mod tests {
         assert!(result.contains("7:peer id"));
         assert!(result.contains("4:port"));
     }
+
+    #[test]
+    fn test_sign_response() {
+        let options = AnnounceResponseOptions { freeleech: true, ..compact_options() };
+        let unsigned = build_announce_response(&[], 1, 2, &options);
+        let mut signed = unsigned.clone();
+        sign_response(&mut signed, "secret");
+
+        let signature = hmac_hex("secret", &unsigned);
+        let expected = format!("9:signature64:{}e", signature);
+        assert_eq!(&signed[..unsigned.len() - 1], &unsigned[..unsigned.len() - 1]);
+        assert_eq!(&signed[unsigned.len() - 1..], expected.as_bytes());
+
+        // Anything but a dictionary is left alone
+        let mut empty = Vec::new();
+        sign_response(&mut empty, "secret");
+        assert!(empty.is_empty());
+    }
 }
@@ -0,0 +1,138 @@
+// API key authentication for admin endpoints
+
+use crate::core::error::{AdminError, BlacklistError, MonitoringError};
+use crate::core::state::AppState;
+use crate::utils::auth::verify_api_key;
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Error types that can report a missing or invalid API key
+pub trait InvalidApiKey {
+    fn invalid_api_key() -> Self;
+}
+
+impl InvalidApiKey for AdminError {
+    fn invalid_api_key() -> Self {
+        AdminError::InvalidApiKey
+    }
+}
+
+impl InvalidApiKey for BlacklistError {
+    fn invalid_api_key() -> Self {
+        BlacklistError::InvalidApiKey
+    }
+}
+
+impl InvalidApiKey for MonitoringError {
+    fn invalid_api_key() -> Self {
+        MonitoringError::InvalidApiKey
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiKeyParam {
+    api_key: Option<String>,
+}
+
+/// Extractor that rejects requests without a valid API key
+///
+/// The key is read from an `Authorization: Bearer <key>` header, falling back to the
+/// `api_key` query parameter. Rejections use the error type `E` so each handler family
+/// keeps its own response body.
+///
+/// Applied as route middleware in `build_router` rather than taken by handlers directly.
+pub struct ApiKeyAuth<E = AdminError>(PhantomData<fn() -> E>);
+
+impl<E> FromRequestParts<Arc<AppState>> for ApiKeyAuth<E>
+where
+    E: InvalidApiKey + IntoResponse,
+{
+    type Rejection = E;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, E> {
+        match provided_api_key(parts) {
+            Some(key) if verify_api_key(&key, &state.config.sync.api_key) => Ok(Self(PhantomData)),
+            _ => {
+                warn!(path = %parts.uri.path(), "Unauthorized admin request");
+                Err(E::invalid_api_key())
+            }
+        }
+    }
+}
+
+/// Read the API key from the bearer token header, or the `api_key` query parameter
+fn provided_api_key(parts: &Parts) -> Option<String> {
+    if let Some(value) = parts.headers.get(AUTHORIZATION) {
+        // A malformed header is treated as missing rather than falling back to the query
+        return value
+            .to_str()
+            .ok()
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|key| key.trim().to_string());
+    }
+
+    let query = parts.uri.query()?;
+    serde_urlencoded::from_str::<ApiKeyParam>(query)
+        .ok()
+        .and_then(|param| param.api_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Request, StatusCode};
+
+    fn parts(uri: &str, authorization: Option<&str>) -> Parts {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(value) = authorization {
+            builder = builder.header(AUTHORIZATION, value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_provided_api_key_from_header() {
+        let parts = parts("/metrics", Some("Bearer secret"));
+        assert_eq!(provided_api_key(&parts), Some("secret".to_string()));
+    }
+
+    #[test]
+    fn test_provided_api_key_from_query() {
+        let parts = parts("/metrics?foo=1&api_key=secret", None);
+        assert_eq!(provided_api_key(&parts), Some("secret".to_string()));
+    }
+
+    #[test]
+    fn test_provided_api_key_header_takes_precedence() {
+        let parts = parts("/metrics?api_key=query", Some("Bearer header"));
+        assert_eq!(provided_api_key(&parts), Some("header".to_string()));
+    }
+
+    #[test]
+    fn test_provided_api_key_rejects_other_schemes() {
+        let parts = parts("/metrics?api_key=secret", Some("Basic c2VjcmV0"));
+        assert_eq!(provided_api_key(&parts), None);
+    }
+
+    #[test]
+    fn test_provided_api_key_missing() {
+        assert_eq!(provided_api_key(&parts("/metrics", None)), None);
+        assert_eq!(provided_api_key(&parts("/metrics?foo=1", None)), None);
+    }
+
+    #[test]
+    fn test_rejection_uses_handler_error_type() {
+        let admin = AdminError::invalid_api_key().into_response();
+        assert_eq!(admin.status(), StatusCode::UNAUTHORIZED);
+
+        let monitoring = MonitoringError::invalid_api_key().into_response();
+        assert_eq!(monitoring.status(), StatusCode::UNAUTHORIZED);
+    }
+}
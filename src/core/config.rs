@@ -1,8 +1,10 @@
 use anyhow::{bail, Context, Result};
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize, Serializer};
+use axum::http::{header, HeaderName, HeaderValue};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub memory: MemoryConfig,
@@ -12,19 +14,43 @@ pub struct Config {
     pub anti_cheat: AntiCheatConfig,
     #[serde(default)]
     pub security: SecurityConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub announce: AnnounceConfig,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ServerConfig {
     pub port: Option<u16>,
+    /// Port for an IPv4-only listener on 0.0.0.0
+    pub ipv4_port: Option<u16>,
+    /// Port for an IPv6 listener on [::]
+    pub ipv6_port: Option<u16>,
     pub unix_socket: Option<PathBuf>,
     #[serde(default = "default_num_threads")]
     pub num_threads: usize,
     #[serde(default = "default_max_connections")]
     pub max_connections: usize,
+    /// Plain-text body served at `/`; defaults to the tracker name and version
+    pub root_message: Option<String>,
+    /// Largest request body accepted on any endpoint, in bytes
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// `Server` header sent on every response; none is sent when unset or empty
+    pub server_header: Option<String>,
+    /// Extra headers sent on every response, overriding any set by a handler
+    #[serde(default)]
+    pub response_headers: BTreeMap<String, String>,
+    /// Answer browsers with a plain-text page on `/announce` and unknown paths; when off
+    /// they get the same bencoded failures as clients
+    #[serde(default = "default_browser_responses")]
+    pub browser_responses: bool,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MemoryConfig {
     #[serde(default = "default_peer_capacity")]
     pub peer_capacity: usize,
@@ -32,9 +58,15 @@ pub struct MemoryConfig {
     pub torrent_cache_size: usize,
     #[serde(default = "default_user_cache_size")]
     pub user_cache_size: usize,
+    #[serde(default = "default_max_tracked_ips_per_user")]
+    pub max_tracked_ips_per_user: usize,
+    /// Shards per DashMap in the peer store and caches; a power of two, or 0 to derive
+    /// it from `server.num_threads`
+    #[serde(default)]
+    pub shard_amount: usize,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PerformanceConfig {
     #[serde(default = "default_min_announce_interval")]
     pub min_announce_interval: i64,
@@ -44,30 +76,75 @@ pub struct PerformanceConfig {
     pub cleanup_interval: u64,
     #[serde(default = "default_peer_timeout")]
     pub peer_timeout: i64,
+    #[serde(default = "default_max_query_length")]
+    pub max_query_length: usize,
+    /// Most IPs the rate limiter tracks at once (0 means unbounded)
+    #[serde(default = "default_rate_limiter_max_entries")]
+    pub rate_limiter_max_entries: usize,
+    /// What happens to new IPs once the rate limiter is full: evict_oldest, deny or allow
+    #[serde(default = "default_rate_limiter_overflow")]
+    pub rate_limiter_overflow: String,
+    /// Announces per second accepted across all clients before shedding load (0 disables)
+    #[serde(default)]
+    pub global_announce_rate: u32,
+    /// Announces accepted at once after a quiet period (0 means global_announce_rate)
+    #[serde(default)]
+    pub global_announce_burst: u32,
+    /// Interval in seconds sent to clients whose announces were shed
+    #[serde(default = "default_shed_interval")]
+    pub shed_interval: i64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SyncConfig {
+    #[serde(serialize_with = "redact_url")]
     pub data_endpoint: String,
+    #[serde(serialize_with = "redact")]
     pub api_key: String,
+    #[serde(default = "default_torrent_conflict_policy")]
+    pub torrent_conflict_policy: String,
+    /// Optional file of `id,info_hash_hex,freeleech` lines loaded on startup and reload
+    pub torrents_file: Option<PathBuf>,
+    /// Seconds after the last successful sync before /health reports stale (0 disables)
+    #[serde(default)]
+    pub max_sync_age: u64,
+    /// On-demand API lookups per second for passkeys missing from the user cache (0 disables)
+    #[serde(default)]
+    pub lazy_user_lookups_per_second: u32,
+    /// On-demand API lookups per second for info_hashes missing from the torrent cache (0 disables)
+    #[serde(default)]
+    pub lazy_torrent_lookups_per_second: u32,
+    /// Seconds an info_hash the API did not know is answered without another lookup
+    #[serde(default = "default_unknown_torrent_ttl")]
+    pub unknown_torrent_ttl: u64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
     pub level: String,
     #[serde(default = "default_log_format")]
     pub format: String,
-    #[allow(dead_code)]
     pub path: Option<PathBuf>,
+    #[serde(default = "default_log_rotation")]
+    pub rotation: String,
     #[serde(default = "default_console")]
     pub console: bool,
+    /// Log one in this many successful announces at INFO (1 logs all of them)
+    #[serde(default = "default_announce_log_sample_rate")]
+    pub announce_log_sample_rate: u64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AntiCheatConfig {
     #[serde(default = "default_max_ips_per_user")]
     pub max_ips_per_user: u32,
+    /// Count IPs announced with the same client `key` as one in the duplicate-peer check
+    #[serde(default)]
+    pub dedupe_ips_by_key: bool,
+    /// Maximum peer IDs per user in one swarm (0 disables the check)
+    #[serde(default)]
+    pub max_peer_ids_per_user: u32,
     #[serde(default = "default_max_ratio")]
     pub max_ratio: f64,
     #[serde(default = "default_max_upload_speed")]
@@ -76,14 +153,178 @@ pub struct AntiCheatConfig {
     pub max_download_speed: f64,
     #[serde(default = "default_min_seeder_upload")]
     pub min_seeder_upload: u64,
+    /// Skip the ghost seeder check on freeleech torrents
+    #[serde(default)]
+    pub skip_ghost_seeder_on_freeleech: bool,
+    /// How out-of-order announce events are handled: off, warn or block
+    #[serde(default = "default_event_sequence_policy")]
+    pub event_sequence_policy: String,
+    /// How leechers reporting more bytes left than the torrent size are handled: off, warn or block
+    #[serde(default = "default_size_sanity_policy")]
+    pub size_sanity_policy: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct SecurityConfig {
     #[serde(default)]
     pub banned_ips: Vec<String>,
     #[serde(default)]
     pub banned_clients: Vec<String>,
+    /// File with additional banned IPs, one per line, merged into `banned_ips` at startup
+    pub banned_ips_file: Option<PathBuf>,
+    /// File with additional banned client strings, one per line, merged into `banned_clients`
+    pub banned_clients_file: Option<PathBuf>,
+    #[serde(default)]
+    pub reject_non_routable: bool,
+    /// Reject announces without a User-Agent header, or with a blank one
+    #[serde(default)]
+    pub reject_empty_user_agent: bool,
+    /// User IDs that bypass rate limiting and anti-cheat checks (seedboxes, monitoring)
+    #[serde(default)]
+    pub exempt_user_ids: Vec<u32>,
+    /// Serve stats for every torrent on /scrape (still requires the API key)
+    #[serde(default)]
+    pub allow_full_scrape: bool,
+    /// Most torrents a user of each class may be active in at once; signed passkeys carry
+    /// no class and count as class 0
+    #[serde(default)]
+    pub torrent_quotas: Vec<TorrentQuota>,
+    /// Failure reason sent to users over their quota, replacing the built-in one
+    pub quota_message: Option<String>,
+}
+
+/// Torrent quota for one user class
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TorrentQuota {
+    pub class: u8,
+    pub max_torrents: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuthConfig {
+    #[serde(default = "default_passkey_mode")]
+    pub passkey_mode: String,
+    /// Character set stored passkeys are issued in: alphanumeric or hex
+    #[serde(default = "default_passkey_format")]
+    pub passkey_format: String,
+    #[serde(serialize_with = "redact_option")]
+    pub passkey_secret: Option<String>,
+    /// Seconds a rejected stored passkey is answered from cache without a lookup (0 disables)
+    #[serde(default = "default_rejected_passkey_ttl")]
+    pub rejected_passkey_ttl: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            passkey_mode: default_passkey_mode(),
+            passkey_format: default_passkey_format(),
+            passkey_secret: None,
+            rejected_passkey_ttl: default_rejected_passkey_ttl(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct MetricsConfig {
+    /// Seconds between periodic metrics snapshots (0 disables)
+    #[serde(default)]
+    pub snapshot_interval: u64,
+    /// Optional URL to POST each snapshot to as JSON; redacted when serialized, since
+    /// webhook URLs often embed a token
+    #[serde(serialize_with = "redact_option")]
+    pub snapshot_webhook: Option<String>,
+    /// Optional file the cumulative counters are saved to periodically and on shutdown, and
+    /// loaded from on startup
+    pub persist_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AnnounceConfig {
+    /// Interval in seconds sent to clients, unless the torrent overrides it
+    #[serde(default = "default_announce_interval")]
+    pub interval: i64,
+    /// Interval in seconds sent for an empty swarm when scaling by swarm size
+    #[serde(default)]
+    pub scaled_interval_min: i64,
+    /// Interval in seconds sent for the largest swarms; 0 disables scaling
+    #[serde(default)]
+    pub scaled_interval_max: i64,
+    /// Swarm size (seeders plus leechers) at which `scaled_interval_max` is reached
+    #[serde(default = "default_scaled_interval_swarm_size")]
+    pub scaled_interval_swarm_size: u32,
+    #[serde(default = "default_emit_peers6")]
+    pub emit_peers6: bool,
+    /// Add a non-standard `freeleech` key to responses for freeleech torrents
+    #[serde(default)]
+    pub emit_freeleech: bool,
+    /// Leave out empty compact `peers`/`peers6` keys instead of sending `0:`
+    #[serde(default)]
+    pub omit_empty_peers: bool,
+    /// Most IPv4 peers listed per response (0 means numwant alone limits them)
+    #[serde(default)]
+    pub max_numwant_ipv4: u32,
+    /// Most IPv6 peers listed per response, usually kept below the IPv4 cap since each
+    /// takes 18 bytes instead of 6 (0 means numwant alone limits them)
+    #[serde(default)]
+    pub max_numwant_ipv6: u32,
+    /// Fill peer lists from the announcing peer's address family first
+    #[serde(default)]
+    pub prefer_same_family: bool,
+    /// Share of each peer list reserved for the peers most useful to the requester:
+    /// seeders for leechers, leechers for seeders. 0 keeps selection uniformly random.
+    #[serde(default)]
+    pub preferred_peer_ratio: f64,
+    /// Replace a user's peer on the same IP and port when a new peer_id shows up there
+    #[serde(default)]
+    pub dedupe_peer_endpoints: bool,
+    /// Answer `event=stopped` with an empty body instead of a bencoded response
+    #[serde(default)]
+    pub empty_stopped_response: bool,
+    /// Skip anti-cheat checks for stats-only announces (numwant=0, no event)
+    #[serde(default)]
+    pub skip_anti_cheat_on_stats_only: bool,
+    /// Answer announces for inactive torrents with an empty response instead of a failure
+    #[serde(default)]
+    pub retire_inactive_torrents: bool,
+    /// Interval in seconds sent to clients of retired torrents
+    #[serde(default = "default_retired_interval")]
+    pub retired_interval: i64,
+    /// Warning message sent to clients of retired torrents
+    #[serde(default = "default_retired_message")]
+    pub retired_message: String,
+    /// Content type of bencoded announce responses, successes and failures alike
+    #[serde(default = "default_announce_content_type")]
+    pub content_type: String,
+    /// Shared secret for an HMAC `signature` key on announce responses (unset disables)
+    #[serde(serialize_with = "redact_option")]
+    pub response_signing_secret: Option<String>,
+}
+
+impl Default for AnnounceConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_announce_interval(),
+            scaled_interval_min: 0,
+            scaled_interval_max: 0,
+            scaled_interval_swarm_size: default_scaled_interval_swarm_size(),
+            emit_peers6: default_emit_peers6(),
+            emit_freeleech: false,
+            omit_empty_peers: false,
+            max_numwant_ipv4: 0,
+            max_numwant_ipv6: 0,
+            prefer_same_family: false,
+            preferred_peer_ratio: 0.0,
+            dedupe_peer_endpoints: false,
+            empty_stopped_response: false,
+            skip_anti_cheat_on_stats_only: false,
+            retire_inactive_torrents: false,
+            retired_interval: default_retired_interval(),
+            retired_message: default_retired_message(),
+            content_type: default_announce_content_type(),
+            response_signing_secret: None,
+        }
+    }
 }
 
 // Default value functions
@@ -95,6 +336,14 @@ fn default_max_connections() -> usize {
     10000
 }
 
+fn default_max_body_size() -> usize {
+    1024
+}
+
+fn default_browser_responses() -> bool {
+    true
+}
+
 fn default_peer_capacity() -> usize {
     1_000_000
 }
@@ -107,6 +356,10 @@ fn default_user_cache_size() -> usize {
     50_000
 }
 
+fn default_max_tracked_ips_per_user() -> usize {
+    16
+}
+
 fn default_min_announce_interval() -> i64 {
     900 // 15 minutes
 }
@@ -123,6 +376,54 @@ fn default_peer_timeout() -> i64 {
     3600 // 1 hour
 }
 
+fn default_max_query_length() -> usize {
+    2048
+}
+
+fn default_rate_limiter_max_entries() -> usize {
+    100_000
+}
+
+fn default_rate_limiter_overflow() -> String {
+    "evict_oldest".to_string()
+}
+
+fn default_shed_interval() -> i64 {
+    3600
+}
+
+fn default_torrent_conflict_policy() -> String {
+    "warn".to_string()
+}
+
+fn default_announce_interval() -> i64 {
+    1800 // 30 minutes
+}
+
+fn default_scaled_interval_swarm_size() -> u32 {
+    1000
+}
+
+fn default_emit_peers6() -> bool {
+    true
+}
+
+fn default_retired_interval() -> i64 {
+    604_800 // 1 week
+}
+
+fn default_retired_message() -> String {
+    "This torrent has been retired from the tracker".to_string()
+}
+
+fn default_announce_content_type() -> String {
+    crate::bencode::response::DEFAULT_CONTENT_TYPE.to_string()
+}
+
+fn default_unknown_torrent_ttl() -> u64 {
+    60
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
@@ -131,10 +432,18 @@ fn default_log_format() -> String {
     "json".to_string()
 }
 
+fn default_log_rotation() -> String {
+    "daily".to_string()
+}
+
 fn default_console() -> bool {
     false
 }
 
+fn default_announce_log_sample_rate() -> u64 {
+    1
+}
+
 fn default_max_ips_per_user() -> u32 {
     3
 }
@@ -155,13 +464,49 @@ fn default_min_seeder_upload() -> u64 {
     1_048_576 // 1 MB
 }
 
+fn default_event_sequence_policy() -> String {
+    "warn".to_string()
+}
+
+fn default_size_sanity_policy() -> String {
+    "warn".to_string()
+}
+
+fn default_passkey_mode() -> String {
+    "stored".to_string()
+}
+
+fn default_passkey_format() -> String {
+    "alphanumeric".to_string()
+}
+
+fn default_rejected_passkey_ttl() -> u64 {
+    5
+}
+
 impl Config {
-    /// Load configuration from a TOML file
-    pub fn from_file(path: &PathBuf) -> Result<Self> {
-        let content = std::fs::read_to_string(path)
-            .context(format!("Failed to read config file: {}", path.display()))?;
+    /// Load configuration from a TOML file and the files it includes
+    ///
+    /// A top-level `include` (a path or a list of paths, relative to this file) names
+    /// further TOML files, or directories whose `*.toml` files are read in name order.
+    /// Each is merged over the result so far: tables merge key by key, anything else
+    /// (scalars and arrays) is replaced. Included files cannot include further files.
+    /// The merged configuration is validated as a whole.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut table = read_toml_table(path)?;
+        
+        if let Some(include) = table.remove("include") {
+            let base_dir = path.parent().unwrap_or(Path::new(""));
+            for include_path in include_paths(include, base_dir)? {
+                let mut overlay = read_toml_table(&include_path)?;
+                if overlay.remove("include").is_some() {
+                    bail!("Included config file {} cannot include further files", include_path.display());
+                }
+                merge_toml_tables(&mut table, overlay);
+            }
+        }
         
-        let config: Config = toml::from_str(&content)
+        let config: Config = toml::Value::Table(table).try_into()
             .context("Failed to parse config file")?;
         
         config.validate()?;
@@ -169,19 +514,57 @@ impl Config {
         Ok(config)
     }
 
+    /// Shard count for the peer store and caches
+    ///
+    /// Defaults to four shards per worker thread, rounded up to a power of two.
+    pub fn shard_amount(&self) -> usize {
+        match self.memory.shard_amount {
+            0 => (self.server.num_threads * 4).next_power_of_two().max(2),
+            shard_amount => shard_amount,
+        }
+    }
+
+    /// Headers added to every response: `Server` first, then `server.response_headers`
+    pub fn response_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        let mut headers = Vec::new();
+        if let Some(server) = self.server.server_header.as_deref().filter(|server| !server.is_empty()) {
+            let value = HeaderValue::from_str(server).context("Invalid server_header value")?;
+            headers.push((header::SERVER, value));
+        }
+
+        for (name, value) in &self.server.response_headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid response header name '{}'", name))?;
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for response header '{}'", name))?;
+            headers.push((name, value));
+        }
+
+        Ok(headers)
+    }
+
     /// Validate configuration values
     pub fn validate(&self) -> Result<()> {
         // Validate server config
-        if self.server.port.is_none() && self.server.unix_socket.is_none() {
-            bail!("Either port or unix_socket must be specified in server config");
+        let tcp_ports: Vec<u16> = [self.server.port, self.server.ipv4_port, self.server.ipv6_port]
+            .into_iter()
+            .flatten()
+            .collect();
+        
+        if tcp_ports.is_empty() && self.server.unix_socket.is_none() {
+            bail!("At least one of port, ipv4_port, ipv6_port or unix_socket must be specified in server config");
         }
         
-        if let Some(port) = self.server.port {
-            if port == 0 {
-                bail!("Server port must be greater than 0");
+        for (i, port) in tcp_ports.iter().enumerate() {
+            if tcp_ports[i + 1..].contains(port) {
+                bail!("TCP port {} is configured more than once in server config", port);
             }
         }
         
+        if tcp_ports.contains(&0) {
+            bail!("Server port must be greater than 0");
+        }
+        
         if self.server.num_threads == 0 {
             bail!("num_threads must be greater than 0");
         }
@@ -189,6 +572,8 @@ impl Config {
         if self.server.max_connections == 0 {
             bail!("max_connections must be greater than 0");
         }
+
+        self.response_headers()?;
         
         // Validate memory config
         if self.memory.peer_capacity == 0 {
@@ -202,6 +587,10 @@ impl Config {
         if self.memory.user_cache_size == 0 {
             bail!("user_cache_size must be greater than 0");
         }
+
+        if self.memory.shard_amount != 0 && (self.memory.shard_amount < 2 || !self.memory.shard_amount.is_power_of_two()) {
+            bail!("shard_amount must be a power of two greater than 1");
+        }
         
         // Validate performance config
         if self.performance.min_announce_interval < 0 {
@@ -220,6 +609,22 @@ impl Config {
             bail!("peer_timeout must be non-negative");
         }
 
+        if self.performance.max_query_length == 0 {
+            bail!("max_query_length must be greater than 0");
+        }
+
+        if self.performance.shed_interval <= 0 {
+            bail!("shed_interval must be greater than 0");
+        }
+
+        let valid_overflow_policies = ["evict_oldest", "deny", "allow"];
+        if !valid_overflow_policies.contains(&self.performance.rate_limiter_overflow.as_str()) {
+            bail!(
+                "Invalid rate_limiter_overflow '{}'. Must be one of: evict_oldest, deny, allow",
+                self.performance.rate_limiter_overflow
+            );
+        }
+
         // Validate that peer_timeout is greater than cleanup_interval
         if self.performance.peer_timeout <= self.performance.cleanup_interval as i64 {
             bail!(
@@ -238,6 +643,14 @@ impl Config {
             bail!("api_key must not be empty");
         }
         
+        let valid_conflict_policies = ["warn", "reject"];
+        if !valid_conflict_policies.contains(&self.sync.torrent_conflict_policy.as_str()) {
+            bail!(
+                "Invalid torrent_conflict_policy '{}'. Must be one of: warn, reject",
+                self.sync.torrent_conflict_policy
+            );
+        }
+        
         // Validate logging config
         let valid_levels = ["trace", "debug", "info", "warn", "error"];
         if !valid_levels.contains(&self.logging.level.as_str()) {
@@ -255,11 +668,32 @@ impl Config {
             );
         }
         
+        let valid_rotations = ["daily", "hourly", "never"];
+        if !valid_rotations.contains(&self.logging.rotation.as_str()) {
+            bail!(
+                "Invalid log rotation '{}'. Must be one of: daily, hourly, never",
+                self.logging.rotation
+            );
+        }
+        
+        if self.logging.announce_log_sample_rate == 0 {
+            bail!("announce_log_sample_rate must be greater than 0");
+        }
+        
         // Validate anti-cheat config
         if self.anti_cheat.max_ips_per_user == 0 {
             bail!("max_ips_per_user must be greater than 0");
         }
         
+        // The tracked IP set must be able to exceed the limit for violations to be detected
+        if self.memory.max_tracked_ips_per_user <= self.anti_cheat.max_ips_per_user as usize {
+            bail!(
+                "max_tracked_ips_per_user ({}) must be greater than max_ips_per_user ({})",
+                self.memory.max_tracked_ips_per_user,
+                self.anti_cheat.max_ips_per_user
+            );
+        }
+        
         if self.anti_cheat.max_ratio <= 0.0 {
             bail!("max_ratio must be greater than 0");
         }
@@ -276,10 +710,299 @@ impl Config {
             bail!("min_seeder_upload must be greater than 0");
         }
         
+        let valid_event_sequence_policies = ["off", "warn", "block"];
+        if !valid_event_sequence_policies.contains(&self.anti_cheat.event_sequence_policy.as_str()) {
+            bail!(
+                "Invalid event_sequence_policy '{}'. Must be one of: off, warn, block",
+                self.anti_cheat.event_sequence_policy
+            );
+        }
+        
+        let valid_size_sanity_policies = ["off", "warn", "block"];
+        if !valid_size_sanity_policies.contains(&self.anti_cheat.size_sanity_policy.as_str()) {
+            bail!(
+                "Invalid size_sanity_policy '{}'. Must be one of: off, warn, block",
+                self.anti_cheat.size_sanity_policy
+            );
+        }
+        
+        // Validate auth config
+        let valid_passkey_modes = ["stored", "signed"];
+        if !valid_passkey_modes.contains(&self.auth.passkey_mode.as_str()) {
+            bail!(
+                "Invalid passkey_mode '{}'. Must be one of: stored, signed",
+                self.auth.passkey_mode
+            );
+        }
+        
+        let valid_passkey_formats = ["alphanumeric", "hex"];
+        if !valid_passkey_formats.contains(&self.auth.passkey_format.as_str()) {
+            bail!(
+                "Invalid passkey_format '{}'. Must be one of: alphanumeric, hex",
+                self.auth.passkey_format
+            );
+        }
+        
+        if self.auth.passkey_mode == "signed"
+            && self.auth.passkey_secret.as_deref().is_none_or(str::is_empty)
+        {
+            bail!("passkey_secret must be set when passkey_mode is 'signed'");
+        }
+
+        let mut quota_classes = std::collections::HashSet::new();
+        for quota in &self.security.torrent_quotas {
+            if quota.max_torrents == 0 {
+                bail!("torrent quota max_torrents for class {} must be greater than 0", quota.class);
+            }
+            if !quota_classes.insert(quota.class) {
+                bail!("torrent quota for class {} is configured more than once", quota.class);
+            }
+        }
+
+        // Validate metrics config
+        if let Some(webhook) = &self.metrics.snapshot_webhook {
+            if !webhook.starts_with("http://") && !webhook.starts_with("https://") {
+                bail!("snapshot_webhook must be an http:// or https:// URL");
+            }
+        }
+        
+        // Validate announce config
+        if self.announce.interval <= 0 {
+            bail!("announce interval must be greater than 0");
+        }
+
+        if self.announce.scaled_interval_max != 0 {
+            if self.announce.scaled_interval_min <= 0 {
+                bail!("scaled_interval_min must be greater than 0 when scaled_interval_max is set");
+            }
+            if self.announce.scaled_interval_max < self.announce.scaled_interval_min {
+                bail!("scaled_interval_max must not be less than scaled_interval_min");
+            }
+            if self.announce.scaled_interval_swarm_size == 0 {
+                bail!("scaled_interval_swarm_size must be greater than 0");
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.announce.preferred_peer_ratio) {
+            bail!("preferred_peer_ratio must be between 0 and 1");
+        }
+
+        if self.announce.retired_interval <= 0 {
+            bail!("retired_interval must be greater than 0");
+        }
+
+        if axum::http::HeaderValue::from_str(&self.announce.content_type).is_err() {
+            bail!("Invalid announce content_type '{}'", self.announce.content_type);
+        }
+
+        if self.announce.response_signing_secret.as_deref() == Some("") {
+            bail!("response_signing_secret must not be empty");
+        }
+        
         Ok(())
     }
 }
 
+/// Placeholder serialized in place of secrets, e.g. on /config
+const REDACTED: &str = "[redacted]";
+
+fn redact<S: Serializer>(_secret: &str, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(REDACTED)
+}
+
+/// Keeps an unset secret visible as null
+fn redact_option<S: Serializer>(secret: &Option<String>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match secret {
+        Some(_) => serializer.serialize_str(REDACTED),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Drops credentials and the query string, which may carry tokens, from a URL; one
+/// that does not parse is redacted whole
+fn redact_url<S: Serializer>(url: &str, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match reqwest::Url::parse(url) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.set_query(None);
+            url.set_fragment(None);
+            serializer.serialize_str(url.as_str())
+        }
+        Err(_) => serializer.serialize_str(REDACTED),
+    }
+}
+
+fn read_toml_table(path: &Path) -> Result<toml::Table> {
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read config file: {}", path.display()))?;
+    
+    toml::from_str(&content).context(format!("Failed to parse config file: {}", path.display()))
+}
+
+/// Expand the `include` value into the files to merge, in order
+fn include_paths(include: toml::Value, base_dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match include {
+        toml::Value::String(entry) => vec![entry],
+        toml::Value::Array(entries) => entries
+            .into_iter()
+            .map(|entry| match entry {
+                toml::Value::String(entry) => Ok(entry),
+                other => bail!("include entries must be strings, got {}", other.type_str()),
+            })
+            .collect::<Result<_>>()?,
+        other => bail!("include must be a path or a list of paths, got {}", other.type_str()),
+    };
+    
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = base_dir.join(entry);
+        if path.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(&path)
+                .context(format!("Failed to read config directory: {}", path.display()))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<_>>()
+                .context(format!("Failed to read config directory: {}", path.display()))?;
+            files.retain(|file| file.is_file() && file.extension().is_some_and(|ext| ext == "toml"));
+            files.sort();
+            paths.extend(files);
+        } else {
+            paths.push(path);
+        }
+    }
+    
+    Ok(paths)
+}
+
+/// Merge `overlay` into `base`: nested tables merge, every other value replaces
+fn merge_toml_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_toml_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// Valid configuration for handler and store tests; tests adjust fields as needed
+    pub fn for_tests() -> Self {
+    Config {
+        server: ServerConfig {
+            port: Some(8080),
+            ipv4_port: None,
+            ipv6_port: None,
+            unix_socket: None,
+            num_threads: 4,
+            max_connections: 1000,
+            root_message: None,
+            max_body_size: 1024,
+            server_header: None,
+            response_headers: Default::default(),
+            browser_responses: true,
+        },
+        memory: MemoryConfig {
+            peer_capacity: 10000,
+            torrent_cache_size: 1000,
+            user_cache_size: 1000,
+            max_tracked_ips_per_user: 16,
+            shard_amount: 0,
+        },
+        performance: PerformanceConfig {
+            min_announce_interval: 1800,
+            max_requests_per_minute: 60,
+            cleanup_interval: 300,
+            peer_timeout: 3600,
+            max_query_length: 2048,
+            rate_limiter_max_entries: 100_000,
+            rate_limiter_overflow: "evict_oldest".to_string(),
+            global_announce_rate: 0,
+            global_announce_burst: 0,
+            shed_interval: 3600,
+        },
+        sync: SyncConfig {
+            data_endpoint: "http://localhost:8000/api".to_string(),
+            api_key: "test-api-key".to_string(),
+            torrent_conflict_policy: "warn".to_string(),
+            torrents_file: None,
+            max_sync_age: 0,
+            lazy_user_lookups_per_second: 0,
+            lazy_torrent_lookups_per_second: 0,
+            unknown_torrent_ttl: 60,
+        },
+        logging: LoggingConfig {
+            level: "info".to_string(),
+            format: "json".to_string(),
+            path: None,
+            rotation: "daily".to_string(),
+            console: true,
+            announce_log_sample_rate: 1,
+        },
+        anti_cheat: AntiCheatConfig {
+            max_ips_per_user: 3,
+            dedupe_ips_by_key: false,
+            max_peer_ids_per_user: 0,
+            max_ratio: 10.0,
+            max_upload_speed: 100.0,
+            max_download_speed: 100.0,
+            min_seeder_upload: 1024,
+            skip_ghost_seeder_on_freeleech: false,
+            event_sequence_policy: "warn".to_string(),
+            size_sanity_policy: "warn".to_string(),
+        },
+        security: SecurityConfig {
+            banned_ips: vec![],
+            banned_clients: vec![],
+            reject_non_routable: false,
+            reject_empty_user_agent: false,
+            banned_ips_file: None,
+            banned_clients_file: None,
+            exempt_user_ids: vec![],
+            allow_full_scrape: false,
+            torrent_quotas: vec![],
+            quota_message: None,
+        },
+        auth: AuthConfig {
+            passkey_mode: "stored".to_string(),
+            passkey_format: "alphanumeric".to_string(),
+            passkey_secret: None,
+            rejected_passkey_ttl: 5,
+        },
+        metrics: MetricsConfig {
+            snapshot_interval: 0,
+            snapshot_webhook: None,
+            persist_path: None,
+        },
+        announce: AnnounceConfig {
+            interval: 1800,
+            emit_peers6: true,
+            emit_freeleech: false,
+            omit_empty_peers: false,
+            max_numwant_ipv4: 0,
+            max_numwant_ipv6: 0,
+            prefer_same_family: false,
+            preferred_peer_ratio: 0.0,
+            dedupe_peer_endpoints: false,
+            scaled_interval_min: 0,
+            scaled_interval_max: 0,
+            scaled_interval_swarm_size: 1000,
+            empty_stopped_response: false,
+            skip_anti_cheat_on_stats_only: false,
+            retire_inactive_torrents: false,
+            retired_interval: 604800,
+            retired_message: "Torrent retired".to_string(),
+            content_type: "text/plain".to_string(),
+            response_signing_secret: None,
+        },
+    }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -325,11 +1048,72 @@ mod tests {
         assert_eq!(config.security.banned_clients.len(), 0);
     }
 
+    #[test]
+    fn test_auth_config_default() {
+        let auth = AuthConfig::default();
+        assert_eq!(auth.passkey_mode, "stored");
+        assert!(auth.passkey_secret.is_none());
+    }
+
+    #[test]
+    fn test_metrics_config_default() {
+        let metrics = MetricsConfig::default();
+        assert_eq!(metrics.snapshot_interval, 0);
+        assert!(metrics.snapshot_webhook.is_none());
+        assert!(metrics.persist_path.is_none());
+    }
+
+    #[test]
+    fn test_announce_config_default() {
+        let announce = AnnounceConfig::default();
+        assert_eq!(announce.scaled_interval_max, 0);
+        assert_eq!(announce.scaled_interval_swarm_size, 1000);
+        assert!(announce.emit_peers6);
+        assert!(!announce.emit_freeleech);
+        assert!(!announce.omit_empty_peers);
+        assert!(!announce.empty_stopped_response);
+        assert!(!announce.prefer_same_family);
+        assert_eq!(announce.preferred_peer_ratio, 0.0);
+        assert!(!announce.dedupe_peer_endpoints);
+        assert!(!announce.skip_anti_cheat_on_stats_only);
+        assert!(!announce.retire_inactive_torrents);
+        assert_eq!(announce.retired_interval, 604_800);
+    }
+
+    #[test]
+    fn test_from_file_merges_includes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let base = format!(
+            "include = [\"secrets.toml\", \"conf.d\"]\n{}",
+            include_str!("../../config.example.toml")
+        );
+        std::fs::write(dir.join("config.toml"), base).unwrap();
+        std::fs::write(dir.join("secrets.toml"), "[sync]\napi_key = \"from-secrets\"\n").unwrap();
+        std::fs::create_dir(dir.join("conf.d")).unwrap();
+        std::fs::write(dir.join("conf.d/10-security.toml"), "[security]\nbanned_ips = [\"192.0.2.1\"]\n").unwrap();
+        std::fs::write(dir.join("conf.d/20-server.toml"), "[server]\nport = 9000\n[sync]\napi_key = \"from-conf-d\"\n").unwrap();
+        std::fs::write(dir.join("conf.d/README"), "not toml").unwrap();
+
+        let config = Config::from_file(&dir.join("config.toml")).unwrap();
+        // Later files win, and tables merge rather than replace
+        assert_eq!(config.sync.api_key, "from-conf-d");
+        assert_eq!(config.server.port, Some(9000));
+        assert_eq!(config.server.max_body_size, 1024);
+        // Arrays are replaced, not appended to
+        assert_eq!(config.security.banned_ips, vec!["192.0.2.1".to_string()]);
+
+        std::fs::write(dir.join("conf.d/30-nested.toml"), "include = \"other.toml\"\n").unwrap();
+        assert!(Config::from_file(&dir.join("config.toml")).is_err());
+    }
+
     #[test]
     fn test_security_config_default() {
         let security = SecurityConfig::default();
         assert!(security.banned_ips.is_empty());
         assert!(security.banned_clients.is_empty());
+        assert!(security.banned_ips_file.is_none());
+        assert!(security.banned_clients_file.is_none());
     }
 
     #[test]
@@ -22,9 +22,15 @@ pub enum AnnounceError {
     #[error("Invalid passkey provided")]
     InvalidPasskey,
 
+    #[error("Passkey has expired, please re-download the torrent file")]
+    PasskeyExpired,
+
     #[error("User account is disabled")]
     UserDisabled,
 
+    #[error("User is banned")]
+    UserBanned,
+
     #[error("Torrent not registered")]
     TorrentNotFound,
 
@@ -34,6 +40,9 @@ pub enum AnnounceError {
     #[error("IP address is banned")]
     IpBanned,
 
+    #[error("IP address is not globally routable")]
+    NonRoutableIp,
+
     #[error("Client is banned")]
     ClientBanned,
 
@@ -49,6 +58,16 @@ pub enum AnnounceError {
     #[error("Suspicious client detected")]
     SuspiciousClient,
 
+    #[error("Announce event out of sequence")]
+    InvalidEventSequence,
+
+    /// The user is active in as many torrents as their class allows
+    #[error("{0}")]
+    QuotaExceeded(String),
+
+    #[error("Reported bytes left exceed torrent size")]
+    LeftExceedsSize,
+
     #[error("Internal server error")]
     InternalError(#[from] anyhow::Error),
 }
@@ -67,28 +86,49 @@ impl IntoResponse for AnnounceError {
         }
 
         // For all other errors, return bencode response
-        use crate::bencode::encoder::BencodeEncode;
+        use crate::bencode::encoder::BencodeDictBuilder;
         
         let message = self.to_string();
 
         // Build bencode error response: d14:failure reason<len>:<message>e
-        let mut buf = Vec::with_capacity(128);
+        let mut dict = BencodeDictBuilder::with_capacity(128);
+        dict.insert("failure reason", message.as_str());
+        let buf = dict.finish();
+
+        crate::bencode::response::bencode_http_response(buf)
+    }
+}
 
-        buf.extend_from_slice(b"d");
+/// Peer store failure
+///
+/// `TorrentNotFound` and `PeerNotFound` are expected absences, e.g. a stop sent after a
+/// tracker restart; `StatsMissing` means the store's internal state is inconsistent.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerStoreError {
+    #[error("Torrent not found in peer store")]
+    TorrentNotFound,
 
-        "failure reason".bencode(&mut buf);
-        message.as_str().bencode(&mut buf);
+    #[error("Peer not found in peer store")]
+    PeerNotFound,
 
-        buf.extend_from_slice(b"e");
+    #[error("Stats not found for torrent")]
+    StatsMissing,
+}
 
-        Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "text/plain")
-            .body(buf.into())
-            .unwrap()
+impl PeerStoreError {
+    /// Whether the torrent or peer was simply absent, as opposed to a bug
+    pub fn is_not_found(self) -> bool {
+        matches!(self, PeerStoreError::TorrentNotFound | PeerStoreError::PeerNotFound)
     }
 }
 
+/// A user or torrent cache refused a new entry because it holds its configured maximum
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Cache is full ({capacity} entries)")]
+pub struct CacheFullError {
+    pub capacity: usize,
+}
+
 #[derive(Error, Debug)]
 pub enum AdminError {
     #[error("Invalid API key")]
@@ -100,6 +140,9 @@ pub enum AdminError {
     #[error("Resource not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Failed to parse hex: {0}")]
     HexDecodeError(String),
 
@@ -115,12 +158,16 @@ pub enum AdminError {
     #[error("Failed to write to WAL: {0}")]
     WalError(String),
 
+    #[error(transparent)]
+    CacheFull(#[from] CacheFullError),
+
     #[error("Internal server error: {0}")]
     InternalError(String),
 }
 
 impl IntoResponse for AdminError {
     fn into_response(self) -> Response {
+        use crate::core::request_id::current_request_id;
         use crate::models::admin::ErrorResponse;
         use axum::response::Json;
 
@@ -128,11 +175,13 @@ impl IntoResponse for AdminError {
             AdminError::InvalidApiKey => (StatusCode::UNAUTHORIZED, self.to_string()),
             AdminError::InvalidParameter(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             AdminError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            AdminError::Conflict(_) => (StatusCode::CONFLICT, self.to_string()),
             AdminError::HexDecodeError(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             AdminError::InvalidLength { .. } => (StatusCode::BAD_REQUEST, self.to_string()),
             AdminError::ApiClientError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             AdminError::ExternalApiError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             AdminError::WalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            AdminError::CacheFull(_) => (StatusCode::INSUFFICIENT_STORAGE, self.to_string()),
             AdminError::InternalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
         };
 
@@ -141,6 +190,7 @@ impl IntoResponse for AdminError {
             Json(ErrorResponse {
                 success: false,
                 error: error_message,
+                request_id: current_request_id(),
             }),
         )
             .into_response()
@@ -164,6 +214,7 @@ pub enum BlacklistError {
 
 impl IntoResponse for BlacklistError {
     fn into_response(self) -> Response {
+        use crate::core::request_id::current_request_id;
         use crate::models::admin::ErrorResponse;
         use axum::response::Json;
 
@@ -179,6 +230,7 @@ impl IntoResponse for BlacklistError {
             Json(ErrorResponse {
                 success: false,
                 error: error_message,
+                request_id: current_request_id(),
             }),
         )
             .into_response()
@@ -214,6 +266,9 @@ pub enum AntiCheatError {
     #[error("Too many IPs for this torrent: {count} > {max}")]
     TooManyIps { count: usize, max: u32 },
 
+    #[error("Too many peer IDs for this torrent: {count} > {max}")]
+    TooManyPeerIds { count: usize, max: u32 },
+
     #[error("Suspicious upload speed: {speed_mbps:.2} MB/s > {max_mbps:.2} MB/s")]
     SuspiciousUploadSpeed { speed_mbps: f64, max_mbps: f64 },
 
@@ -225,6 +280,12 @@ pub enum AntiCheatError {
 
     #[error("Ghost seeder detected: uploaded {uploaded} bytes < {min_upload} bytes")]
     GhostSeeder { uploaded: u64, min_upload: u64 },
+
+    #[error("Invalid event sequence: {0}")]
+    InvalidEventSequence(&'static str),
+
+    #[error("Bytes left exceed torrent size: {left} > {size}")]
+    LeftExceedsSize { left: u64, size: u64 },
 }
 
 #[derive(Error, Debug)]
@@ -1,5 +1,7 @@
+pub mod auth;
 pub mod config;
 pub mod error;
+pub mod request_id;
 pub mod routes;
 pub mod startup;
 pub mod state;
@@ -0,0 +1,101 @@
+// Request ID propagation for log correlation
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info_span, Instrument};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-supplied request ID that is accepted as is
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Middleware that tags every request with an `X-Request-Id`
+///
+/// A well-formed ID sent by the client (or a proxy in front of the tracker) is reused,
+/// otherwise a random one is generated. The ID is recorded on a span wrapping the request
+/// so every log line carries it, and echoed back in the response headers.
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(generate_request_id);
+
+    // Generated and accepted IDs are visible ASCII, so this cannot fail
+    let header_value = HeaderValue::from_str(&request_id).expect("request ID is a valid header value");
+    request.headers_mut().insert(REQUEST_ID_HEADER, header_value.clone());
+
+    // The path only: announce query strings carry passkeys
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+
+    let mut response = REQUEST_ID
+        .scope(request_id, next.run(request))
+        .instrument(span)
+        .await;
+
+    response.headers_mut().insert(REQUEST_ID_HEADER, header_value);
+    response
+}
+
+/// Request ID of the request being handled, if called from within the middleware
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+fn generate_request_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_request_id_is_valid() {
+        let id = generate_request_id();
+        assert_eq!(id.len(), 32);
+        assert!(is_valid_request_id(&id));
+        assert_ne!(id, generate_request_id());
+    }
+
+    #[test]
+    fn test_is_valid_request_id() {
+        assert!(is_valid_request_id("abc-123_def.4"));
+        assert!(is_valid_request_id("550e8400-e29b-41d4-a716-446655440000"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id("line\nbreak"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_current_request_id_scope() {
+        assert_eq!(current_request_id(), None);
+
+        let id = REQUEST_ID
+            .scope("abc".to_string(), async { current_request_id() })
+            .await;
+        assert_eq!(id, Some("abc".to_string()));
+    }
+}
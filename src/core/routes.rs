@@ -1,37 +1,94 @@
 // HTTP routes configuration
 
+use crate::core::auth::ApiKeyAuth;
+use crate::core::error::{AdminError, BlacklistError, MonitoringError};
 use crate::core::state::AppState;
 use axum::{
+    middleware::{from_extractor_with_state, from_fn, map_response_with_state},
     routing::{get, post},
     Router,
 };
 use std::sync::Arc;
+use tower_http::{limit::RequestBodyLimitLayer, set_header::SetResponseHeaderLayer};
 
 pub fn build_router(state: Arc<AppState>) -> Router {
-    Router::new()
-        // Public endpoints
-        .route("/announce", get(crate::handlers::announce::announce_handler))
-        .route("/health", get(crate::handlers::health::health_handler))
-        
-        // Admin endpoints (require API key)
+    // Protected groups check the API key before any handler runs, rejecting with the
+    // error type their handlers use. route_layer keeps unknown paths on the fallback.
+    let monitoring = Router::new()
         .route("/metrics", get(crate::handlers::metrics::metrics_handler))
         .route("/update", get(crate::handlers::update::update_handler))
+        .route("/update/users", get(crate::handlers::update::user_totals_handler))
+        .route("/export", get(crate::handlers::export::export_handler))
+        .route(
+            "/scrape",
+            get(crate::handlers::scrape::full_scrape_handler).layer(map_response_with_state(
+                Arc::clone(&state),
+                crate::handlers::announce::apply_announce_content_type,
+            )),
+        )
+        .route_layer(from_extractor_with_state::<ApiKeyAuth<MonitoringError>, _>(Arc::clone(&state)));
+
+    let admin = Router::new()
         .route("/reload", post(crate::handlers::admin::reload_handler))
+        .route("/reload/status", get(crate::handlers::admin::reload_status_handler))
+        .route("/config", get(crate::handlers::admin::config_handler))
+        .route("/wal/compact", post(crate::handlers::admin::wal_compact_handler))
         .route("/torrent/add", get(crate::handlers::admin::torrent_add_handler))
         .route("/torrent/remove", get(crate::handlers::admin::torrent_remove_handler))
+        .route("/torrent/update", get(crate::handlers::admin::torrent_update_handler))
+        .route("/torrent/list", get(crate::handlers::admin::torrent_list_handler))
+        .route("/peer/drop", post(crate::handlers::admin::peer_drop_handler))
+        .route("/peer/connectable", post(crate::handlers::admin::peer_connectable_handler))
         .route("/user/add", get(crate::handlers::admin::user_add_handler))
         .route("/user/remove", get(crate::handlers::admin::user_remove_handler))
-        
-        // Blacklist endpoints (require API key)
+        .route("/anticheat/user", get(crate::handlers::admin::anticheat_user_handler))
+        .route_layer(from_extractor_with_state::<ApiKeyAuth<AdminError>, _>(Arc::clone(&state)));
+
+    let blacklist = Router::new()
         .route("/ip/ban", get(crate::handlers::blacklist::ip_ban_handler))
         .route("/ip/unban", get(crate::handlers::blacklist::ip_unban_handler))
         .route("/ip/list", get(crate::handlers::blacklist::ip_list_handler))
+        .route("/ratelimit/reset", post(crate::handlers::blacklist::ratelimit_reset_handler))
         .route("/client/ban", get(crate::handlers::blacklist::client_ban_handler))
         .route("/client/unban", get(crate::handlers::blacklist::client_unban_handler))
         .route("/client/list", get(crate::handlers::blacklist::client_list_handler))
+        .route("/user/ban", get(crate::handlers::blacklist::user_ban_handler))
+        .route("/user/unban", get(crate::handlers::blacklist::user_unban_handler))
+        .route_layer(from_extractor_with_state::<ApiKeyAuth<BlacklistError>, _>(Arc::clone(&state)));
+
+    let mut router = Router::new()
+        // Public endpoints
+        .route("/", get(crate::handlers::root::root_handler))
+        .route(
+            "/announce",
+            get(crate::handlers::announce::announce_handler).layer(map_response_with_state(
+                Arc::clone(&state),
+                crate::handlers::announce::apply_announce_content_type,
+            )),
+        )
+        .route("/health", get(crate::handlers::health::health_handler))
+        
+        // Admin endpoints (require API key)
+        .merge(monitoring)
+        .merge(admin)
+        
+        // Blacklist endpoints (require API key)
+        .merge(blacklist)
 
         // 404 fallback for all unmatched routes
         .fallback(crate::handlers::fallback::fallback_handler)
 
-        .with_state(state)
+        // No endpoint takes more than a small body; larger ones are refused with 413
+        .layer(RequestBodyLimitLayer::new(state.config.server.max_body_size))
+
+        // Tag every request, including fallback hits, with an X-Request-Id
+        .layer(from_fn(crate::core::request_id::request_id_middleware));
+
+    // Server and operator-configured headers go on every response, errors included.
+    // They were checked when the config was validated.
+    for (name, value) in state.config.response_headers().unwrap_or_default() {
+        router = router.layer(SetResponseHeaderLayer::overriding(name, value));
+    }
+
+    router.with_state(state)
 }
@@ -1,35 +1,255 @@
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
 use tracing::{info, warn};
 
-use crate::api::client::ApiClient;
+use crate::api::client::{ApiClient, ApiTorrent, ApiUser};
+use crate::core::config::SecurityConfig;
 use crate::models::{torrent::Torrent, user::User};
 use crate::core::state::AppState;
+use crate::stores::{torrent_cache::TorrentCache, user_cache::UserCache};
 use crate::wal::wal::WalOperation;
 // this runs at boot time 
 pub fn apply_wal_operations(state: &AppState, operations: &[WalOperation]) -> Result<()> {
+    apply_cache_operations(&state.user_cache.load(), &state.torrent_cache.load(), operations);
+
     for op in operations {
         match op {
-            WalOperation::AddTorrent { id, info_hash, freeleech } => {
-                let torrent = Torrent::new(*id, *info_hash, *freeleech, true);
-                state.torrent_cache.add_torrent(torrent);
+            WalOperation::RemoveTorrent { info_hash } => {
+                state.peer_store.remove_torrent(*info_hash);
+            }
+            WalOperation::BanUser { id } => {
+                state.user_blacklist.ban(*id);
+            }
+            WalOperation::UnbanUser { id } => {
+                state.user_blacklist.unban(*id);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Apply the user and torrent operations among `operations` to the given caches
+///
+/// Reloads use this to carry admin changes made during the fetch over to the new caches.
+pub fn apply_cache_operations(user_cache: &UserCache, torrent_cache: &TorrentCache, operations: &[WalOperation]) {
+    for op in operations {
+        match op {
+            WalOperation::AddTorrent { id, info_hash, freeleech, announce_interval, peer_timeout } => {
+                let torrent = Torrent::new(*id, *info_hash, *freeleech, true)
+                    .with_announce_interval(*announce_interval)
+                    .with_peer_timeout(*peer_timeout);
+                if let Err(e) = torrent_cache.add_torrent(torrent) {
+                    warn!(torrent_id = id, error = %e, "Skipping WAL torrent add");
+                }
             }
             WalOperation::RemoveTorrent { info_hash } => {
-                state.torrent_cache.remove_torrent(*info_hash);
+                torrent_cache.remove_torrent(*info_hash);
+            }
+            WalOperation::SetTorrentActive { info_hash, active } => {
+                torrent_cache.set_active(*info_hash, *active);
             }
             WalOperation::AddUser { id, passkey, class } => {
                 let user = User::new(*id, *passkey, *class, true);
-                state.user_cache.add_user(user);
+                if let Err(e) = user_cache.add_user(user) {
+                    warn!(user_id = id, error = %e, "Skipping WAL user add");
+                }
             }
             WalOperation::RemoveUser { passkey } => {
-                state.user_cache.remove_user(*passkey);
+                user_cache.remove_user(*passkey);
             }
+            WalOperation::BanUser { .. } | WalOperation::UnbanUser { .. } => {}
+        }
+    }
+}
+
+/// Re-apply the torrent pauses and resumes among `operations` to `torrent_cache`
+///
+/// The API and the torrents file list every torrent as active, and a pause replayed
+/// before its torrent was loaded had nothing to apply to, so startup re-applies them
+/// once every source is loaded.
+pub fn reapply_torrent_pauses(torrent_cache: &TorrentCache, operations: &[WalOperation]) {
+    for op in operations {
+        if let WalOperation::SetTorrentActive { info_hash, active } = op {
+            torrent_cache.set_active(*info_hash, *active);
         }
     }
+}
+
+/// Load allowed torrents from a local file into the given cache.
+///
+/// Each line is `id,info_hash_hex,freeleech` where freeleech is `0` or `1`. Blank lines and
+/// lines starting with `#` are ignored; malformed lines are logged and skipped.
+/// Returns the number of torrents loaded.
+pub fn load_torrents_file(torrent_cache: &TorrentCache, path: &Path) -> Result<usize> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read torrents file {}", path.display()))?;
+
+    let mut loaded = 0;
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse_torrent_line(line) {
+            Ok(torrent) => {
+                if let Err(e) = torrent_cache.add_torrent(torrent) {
+                    warn!(line_num = line_num + 1, error = %e, "Skipping torrents file line");
+                    continue;
+                }
+                loaded += 1;
+            }
+            Err(e) => {
+                warn!(
+                    line_num = line_num + 1,
+                    error = %e,
+                    "Failed to parse torrents file line, skipping"
+                );
+            }
+        }
+    }
+
+    info!(path = %path.display(), torrents = loaded, "Torrents loaded from file");
+
+    Ok(loaded)
+}
+
+/// Merge the entries of `banned_ips_file` and `banned_clients_file` into the inline ban lists.
+///
+/// Invalid IPs are logged and skipped when the blacklist is built, as for inline entries.
+pub fn load_ban_list_files(security: &mut SecurityConfig) -> Result<()> {
+    if let Some(path) = &security.banned_ips_file {
+        let ips = read_list_file(path)?;
+        info!(path = %path.display(), entries = ips.len(), "Banned IPs loaded from file");
+        security.banned_ips.extend(ips);
+    }
+
+    if let Some(path) = &security.banned_clients_file {
+        let clients = read_list_file(path)?;
+        info!(path = %path.display(), entries = clients.len(), "Banned clients loaded from file");
+        security.banned_clients.extend(clients);
+    }
+
     Ok(())
 }
 
-pub async fn populate_from_api(state: &AppState, api_client: &ApiClient) -> Result<()> {
+/// Read one entry per line, ignoring blank lines and lines starting with `#`
+fn read_list_file(path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read list file {}", path.display()))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn parse_torrent_line(line: &str) -> Result<Torrent> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [id, info_hash_hex, freeleech] = fields[..] else {
+        bail!("Expected 3 fields, got {}", fields.len());
+    };
+
+    let id: u32 = id.parse().context("Invalid torrent id")?;
+
+    let hash_bytes = hex::decode(info_hash_hex).context("Invalid info_hash hex")?;
+    if hash_bytes.len() != 20 {
+        bail!("Invalid info_hash length: expected 20, got {}", hash_bytes.len());
+    }
+    let mut info_hash = [0u8; 20];
+    info_hash.copy_from_slice(&hash_bytes);
+
+    let freeleech = match freeleech {
+        "0" => false,
+        "1" => true,
+        other => bail!("Invalid freeleech flag '{}', expected 0 or 1", other),
+    };
+
+    Ok(Torrent::new(id, info_hash, freeleech, true))
+}
+
+/// Convert a torrent from the external API, or None (logged) if its info_hash is malformed
+pub fn torrent_from_api(api_torrent: &ApiTorrent) -> Option<Torrent> {
+    let info_hash: [u8; 20] = match hex::decode(&api_torrent.info_hash) {
+        Ok(hash_bytes) => match hash_bytes.try_into() {
+            Ok(info_hash) => info_hash,
+            Err(_) => {
+                warn!(
+                    torrent_id = api_torrent.id,
+                    info_hash = %api_torrent.info_hash,
+                    "Invalid info_hash length, skipping torrent"
+                );
+                return None;
+            }
+        },
+        Err(e) => {
+            warn!(
+                torrent_id = api_torrent.id,
+                info_hash = %api_torrent.info_hash,
+                error = %e,
+                "Failed to decode info_hash, skipping torrent"
+            );
+            return None;
+        }
+    };
+
+    let torrent = Torrent::new(
+        api_torrent.id,
+        info_hash,
+        api_torrent.is_freeleech,
+        true, // Assume active from API
+    )
+    .with_size(api_torrent.size)
+    .with_announce_interval(api_torrent.announce_interval.filter(|&interval| interval > 0))
+    .with_peer_timeout(api_torrent.peer_timeout.filter(|&timeout| timeout > 0));
+
+    Some(torrent)
+}
+
+/// Convert a user from the external API, or None (logged) if its passkey is malformed
+pub fn user_from_api(api_user: &ApiUser) -> Option<User> {
+    // Passkeys are 32-character alphanumeric strings, store as bytes directly
+    if api_user.passkey.len() != 32 {
+        warn!(
+            user_id = api_user.id,
+            passkey = %api_user.passkey,
+            passkey_len = api_user.passkey.len(),
+            "Invalid passkey length (expected 32), skipping user"
+        );
+        return None;
+    }
+
+    let mut passkey = [0u8; 32];
+    passkey.copy_from_slice(api_user.passkey.as_bytes());
+
+    // User is active if they can download and are not security locked
+    let is_active = api_user.can_download && !api_user.security_locked;
+
+    Some(User::new(api_user.id, passkey, api_user.user_class_id, is_active))
+}
+
+/// What `populate_from_api` found besides the cache entries
+pub struct PopulateOutcome {
+    /// IDs of `security_locked` users, for the caller to apply as API bans
+    pub locked_users: Vec<u32>,
+    /// API pages that failed to load; their entries are missing from the caches
+    pub skipped_pages: Vec<u32>,
+}
+
+/// Fetch users and torrents from the external API into the given caches.
+///
+/// Reloads pass freshly created caches so the live ones stay untouched until the swap.
+/// Pages the API failed to serve are reported rather than treated as an error, so the
+/// caller decides whether incomplete data is good enough.
+pub async fn populate_from_api(
+    user_cache: &UserCache,
+    torrent_cache: &TorrentCache,
+    api_client: &ApiClient,
+) -> Result<PopulateOutcome> {
     let api_data = api_client.fetch_data().await
         .context("Failed to fetch data from external API")?;
     
@@ -39,72 +259,170 @@ pub async fn populate_from_api(state: &AppState, api_client: &ApiClient) -> Resu
         "Data fetched from external API"
     );
     
+    // Entries beyond the configured cache sizes are counted and logged once
+    let mut rejected_torrents = 0usize;
+    let mut rejected_users = 0usize;
+
     for api_torrent in api_data.torrents {
-        match hex::decode(&api_torrent.info_hash) {
-            Ok(hash_bytes) if hash_bytes.len() == 20 => {
-                let mut info_hash = [0u8; 20];
-                info_hash.copy_from_slice(&hash_bytes);
-                
-                let torrent = Torrent::new(
-                    api_torrent.id,
-                    info_hash,
-                    api_torrent.is_freeleech,
-                    true, // Assume active from API
-                );
-                
-                state.torrent_cache.add_torrent(torrent);
-            }
-            Ok(_) => {
-                warn!(
-                    torrent_id = api_torrent.id,
-                    info_hash = %api_torrent.info_hash,
-                    "Invalid info_hash length, skipping torrent"
-                );
-            }
-            Err(e) => {
-                warn!(
-                    torrent_id = api_torrent.id,
-                    info_hash = %api_torrent.info_hash,
-                    error = %e,
-                    "Failed to decode info_hash, skipping torrent"
-                );
+        if let Some(torrent) = torrent_from_api(&api_torrent) {
+            if torrent_cache.add_torrent(torrent).is_err() {
+                rejected_torrents += 1;
             }
         }
     }
     
+    let mut locked_users = Vec::new();
     for api_user in api_data.users {
-        // Passkeys are 32-character alphanumeric strings, store as bytes directly
-        if api_user.passkey.len() == 32 {
-            let passkey_bytes = api_user.passkey.as_bytes();
-            let mut passkey = [0u8; 32];
-            passkey.copy_from_slice(passkey_bytes);
-            
-            // User is active if they can download and are not security locked
-            let is_active = api_user.can_download && !api_user.security_locked;
-            
-            let user = User::new(
-                api_user.id,
-                passkey,
-                api_user.user_class_id,
-                is_active,
-            );
-            
-            state.user_cache.add_user(user);
-        } else {
-            warn!(
-                user_id = api_user.id,
-                passkey = %api_user.passkey,
-                passkey_len = api_user.passkey.len(),
-                "Invalid passkey length (expected 32), skipping user"
-            );
+        if api_user.security_locked {
+            locked_users.push(api_user.id);
+        }
+
+        if let Some(user) = user_from_api(&api_user) {
+            if user_cache.add_user(user).is_err() {
+                rejected_users += 1;
+            }
         }
     }
     
+    if rejected_torrents > 0 || rejected_users > 0 {
+        warn!(
+            rejected_torrents = rejected_torrents,
+            rejected_users = rejected_users,
+            "Cache size limit reached, skipped API entries"
+        );
+    }
+
     info!(
-        users_cached = state.user_cache.len(),
-        torrents_cached = state.torrent_cache.len(),
+        users_cached = user_cache.len(),
+        torrents_cached = torrent_cache.len(),
+        locked_users = locked_users.len(),
         "Caches populated from external API"
     );
     
-    Ok(())
+    Ok(PopulateOutcome {
+        locked_users,
+        skipped_pages: api_data.skipped_pages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reapply_torrent_pauses_after_api_load() {
+        let torrent_cache = TorrentCache::new();
+        let operations = vec![
+            WalOperation::SetTorrentActive { info_hash: [1u8; 20], active: false },
+            WalOperation::SetTorrentActive { info_hash: [2u8; 20], active: false },
+            WalOperation::SetTorrentActive { info_hash: [2u8; 20], active: true },
+        ];
+
+        // As on restart: the WAL is replayed before the API torrents are loaded
+        apply_cache_operations(&UserCache::new(), &torrent_cache, &operations);
+        for id in 1..=2u8 {
+            let api_torrent = ApiTorrent {
+                id: id as u32,
+                info_hash: hex::encode([id; 20]),
+                is_freeleech: false,
+                size: 0,
+                announce_interval: None,
+                peer_timeout: None,
+                seeders: 0,
+                leechers: 0,
+            };
+            torrent_cache.add_torrent(torrent_from_api(&api_torrent).unwrap()).unwrap();
+        }
+
+        reapply_torrent_pauses(&torrent_cache, &operations);
+
+        assert!(!torrent_cache.get_torrent([1u8; 20]).unwrap().is_active);
+        assert!(torrent_cache.get_torrent([2u8; 20]).unwrap().is_active);
+    }
+
+    #[test]
+    fn test_load_torrents_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("torrents.csv");
+        std::fs::write(
+            &path,
+            format!(
+                "# id,info_hash,freeleech\n1,{},0\n\n 2 , {} , 1 \n",
+                "01".repeat(20),
+                "AB".repeat(20),
+            ),
+        )
+        .unwrap();
+
+        let cache = TorrentCache::new();
+        assert_eq!(load_torrents_file(&cache, &path).unwrap(), 2);
+
+        let torrent = cache.get_torrent([0x01; 20]).unwrap();
+        assert_eq!(torrent.id, 1);
+        assert!(!torrent.is_freeleech);
+        assert!(torrent.is_active);
+        assert!(cache.get_torrent([0xab; 20]).unwrap().is_freeleech);
+    }
+
+    #[test]
+    fn test_load_torrents_file_skips_malformed_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("torrents.csv");
+        std::fs::write(
+            &path,
+            format!(
+                "1,{hash},0\nnot-a-line\nx,{hash},0\n3,abcd,0\n4,{hash},yes\n5,{other},1,extra\n",
+                hash = "01".repeat(20),
+                other = "02".repeat(20),
+            ),
+        )
+        .unwrap();
+
+        let cache = TorrentCache::new();
+        assert_eq!(load_torrents_file(&cache, &path).unwrap(), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_load_torrents_file_missing() {
+        let cache = TorrentCache::new();
+        assert!(load_torrents_file(&cache, Path::new("/nonexistent/torrents.csv")).is_err());
+    }
+
+    #[test]
+    fn test_load_ban_list_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let ips_path = temp_dir.path().join("banned_ips.txt");
+        let clients_path = temp_dir.path().join("banned_clients.txt");
+        std::fs::write(&ips_path, "# abusive peers
+10.0.0.1
+
+  2001:db8::1  
+not-an-ip
+").unwrap();
+        std::fs::write(&clients_path, "BadClient
+# SpamBot
+").unwrap();
+
+        let mut security = SecurityConfig {
+            banned_ips: vec!["192.168.1.1".to_string()],
+            banned_ips_file: Some(ips_path),
+            banned_clients_file: Some(clients_path),
+            ..SecurityConfig::default()
+        };
+        load_ban_list_files(&mut security).unwrap();
+
+        assert_eq!(security.banned_ips, ["192.168.1.1", "10.0.0.1", "2001:db8::1", "not-an-ip"]);
+        assert_eq!(security.banned_clients, ["BadClient"]);
+    }
+
+    #[test]
+    fn test_load_ban_list_files_missing() {
+        let mut security = SecurityConfig {
+            banned_ips_file: Some("/nonexistent/banned_ips.txt".into()),
+            ..SecurityConfig::default()
+        };
+        assert!(load_ban_list_files(&mut security).is_err());
+    }
 }
@@ -1,11 +1,24 @@
 // Application state (AppState)
 
+use crate::anti_cheat::violations::ViolationCounts;
+use crate::api::client::ApiClient;
 use crate::core::config::Config;
 use crate::metrics::collector::Metrics;
-use crate::security::{client_blacklist::ClientBlacklist, ip_blacklist::IpBlacklist, rate_limiter::RateLimiter};
-use crate::stores::{peer_store::PeerStore, torrent_cache::TorrentCache, user_cache::UserCache};
+use crate::security::{
+    client_blacklist::ClientBlacklist, global_limiter::GlobalAnnounceLimiter, ip_blacklist::IpBlacklist,
+    rate_limiter::{RateLimitOverflow, RateLimiter}, user_blacklist::UserBlacklist,
+};
+use crate::stores::{
+    announce_counts::AnnounceCounts, peer_store::PeerStore, rejected_passkeys::RejectedPasskeyCache, torrent_cache::TorrentCache,
+    unknown_torrents::UnknownTorrentCache, user_cache::UserCache,
+};
 use crate::wal::wal::Wal;
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64};
 use std::sync::Arc;
+use tracing::warn;
 
 /// Shared application state
 /// 
@@ -16,11 +29,14 @@ pub struct AppState {
     /// Peer store for tracking active peers
     pub peer_store: Arc<PeerStore>,
     
-    /// User cache for authentication
-    pub user_cache: Arc<UserCache>,
+    /// User cache for authentication, swapped wholesale on reload
+    pub user_cache: Arc<ArcSwap<UserCache>>,
     
-    /// Torrent cache for authorization
-    pub torrent_cache: Arc<TorrentCache>,
+    /// Torrent cache for authorization, swapped wholesale on reload
+    pub torrent_cache: Arc<ArcSwap<TorrentCache>>,
+    
+    /// Recently rejected passkeys, answered without a user lookup
+    pub rejected_passkeys: Arc<RejectedPasskeyCache>,
     
     /// IP blacklist for banning malicious IPs
     pub ip_blacklist: Arc<IpBlacklist>,
@@ -28,9 +44,18 @@ pub struct AppState {
     /// Client blacklist for banning malicious clients
     pub client_blacklist: Arc<ClientBlacklist>,
     
+    /// Banned users, rejected after authentication
+    pub user_blacklist: Arc<UserBlacklist>,
+    
     /// Rate limiter for preventing abuse
     pub rate_limiter: Arc<RateLimiter>,
     
+    /// Tracker-wide announce rate cap
+    pub global_limiter: Arc<GlobalAnnounceLimiter>,
+    
+    /// Users exempt from rate limiting and anti-cheat checks
+    pub exempt_users: Arc<HashSet<u32>>,
+    
     /// Metrics collector for tracking statistics
     pub metrics: Arc<Metrics>,
     
@@ -39,6 +64,40 @@ pub struct AppState {
     
     /// Configuration
     pub config: Arc<Config>,
+    
+    /// Set while a /reload is fetching data into fresh caches
+    pub reload_in_progress: Arc<AtomicBool>,
+    
+    /// Held shared by admin cache writes and exclusively by /reload while it carries the
+    /// writes made during its fetch over to the fresh caches
+    pub admin_writes: Arc<tokio::sync::RwLock<()>>,
+    
+    /// Unix timestamp of the last successful reload (0 if none yet)
+    pub last_reload: Arc<AtomicI64>,
+    
+    /// Unix timestamp of the last successful sync with the external API (0 if none yet)
+    pub last_successful_sync: Arc<AtomicI64>,
+    
+    /// Client for on-demand user and torrent lookups, present when either is enabled
+    pub api_client: Option<Arc<ApiClient>>,
+    
+    /// Caps on-demand user lookups, using the same token bucket as the announce cap
+    pub user_lookup_limiter: Arc<GlobalAnnounceLimiter>,
+    
+    /// Caps on-demand torrent lookups
+    pub torrent_lookup_limiter: Arc<GlobalAnnounceLimiter>,
+    
+    /// Info_hashes recently missing from the external API
+    pub unknown_torrents: Arc<UnknownTorrentCache>,
+    
+    /// Announces counted for logging.announce_log_sample_rate
+    pub announce_log_counter: Arc<AtomicU64>,
+    
+    /// Anti-cheat violations per user_id since startup
+    pub violation_counts: Arc<DashMap<u32, ViolationCounts>>,
+    
+    /// Announces per torrent and user since the previous /update
+    pub announce_counts: Arc<AnnounceCounts>,
 }
 
 impl AppState {
@@ -49,18 +108,70 @@ impl AppState {
         
         let client_blacklist = Arc::new(ClientBlacklist::with_banned_clients(&config.security.banned_clients));
         
-        let rate_limiter = Arc::new(RateLimiter::new(config.performance.max_requests_per_minute));
+        let rate_limit_overflow = match config.performance.rate_limiter_overflow.as_str() {
+            "deny" => RateLimitOverflow::Deny,
+            "allow" => RateLimitOverflow::Allow,
+            _ => RateLimitOverflow::EvictOldest,
+        };
+        let rate_limiter = Arc::new(RateLimiter::with_max_entries(
+            config.performance.max_requests_per_minute,
+            config.performance.rate_limiter_max_entries,
+            rate_limit_overflow,
+        ));
         
+        let shard_amount = config.shard_amount();
+        let peer_store = PeerStore::with_capacity_and_shard_amount(
+            config.memory.torrent_cache_size,
+            shard_amount,
+            config.memory.max_tracked_ips_per_user,
+        );
+        let user_cache = UserCache::with_capacity_and_shard_amount(config.memory.user_cache_size, shard_amount);
+        let torrent_cache = TorrentCache::with_capacity_and_shard_amount(config.memory.torrent_cache_size, shard_amount);
+
+        let user_lookup_rate = config.sync.lazy_user_lookups_per_second;
+        let torrent_lookup_rate = config.sync.lazy_torrent_lookups_per_second;
+        let api_client = if user_lookup_rate > 0 || torrent_lookup_rate > 0 {
+            match ApiClient::new(config.sync.data_endpoint.clone(), config.sync.api_key.clone()) {
+                Ok(client) => Some(Arc::new(client)),
+                Err(e) => {
+                    warn!(error = %e, "Failed to create API client, on-demand lookups disabled");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let unknown_torrents = Arc::new(UnknownTorrentCache::new(config.sync.unknown_torrent_ttl));
+
         Self {
-            peer_store: Arc::new(PeerStore::new()),
-            user_cache: Arc::new(UserCache::with_capacity(config.memory.user_cache_size)),
-            torrent_cache: Arc::new(TorrentCache::with_capacity(config.memory.torrent_cache_size)),
+            peer_store: Arc::new(peer_store),
+            user_cache: Arc::new(ArcSwap::from_pointee(user_cache)),
+            torrent_cache: Arc::new(ArcSwap::from_pointee(torrent_cache)),
+            rejected_passkeys: Arc::new(RejectedPasskeyCache::new(config.auth.rejected_passkey_ttl)),
             ip_blacklist,
             client_blacklist,
+            user_blacklist: Arc::new(UserBlacklist::new()),
             rate_limiter,
+            global_limiter: Arc::new(GlobalAnnounceLimiter::new(
+                config.performance.global_announce_rate,
+                config.performance.global_announce_burst,
+            )),
+            exempt_users: Arc::new(config.security.exempt_user_ids.iter().copied().collect()),
             metrics: Arc::new(Metrics::new()),
             wal: Arc::new(wal),
             config,
+            reload_in_progress: Arc::new(AtomicBool::new(false)),
+            admin_writes: Arc::new(tokio::sync::RwLock::new(())),
+            last_reload: Arc::new(AtomicI64::new(0)),
+            last_successful_sync: Arc::new(AtomicI64::new(0)),
+            api_client,
+            user_lookup_limiter: Arc::new(GlobalAnnounceLimiter::new(user_lookup_rate, 0)),
+            torrent_lookup_limiter: Arc::new(GlobalAnnounceLimiter::new(torrent_lookup_rate, 0)),
+            unknown_torrents,
+            announce_log_counter: Arc::new(AtomicU64::new(0)),
+            violation_counts: Arc::new(DashMap::new()),
+            announce_counts: Arc::new(AnnounceCounts::new()),
         }
     }
 }
@@ -1,35 +1,142 @@
 use crate::core::config::LoggingConfig;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry};
 
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
 
-pub fn init_tracing(config: &LoggingConfig) {
+/// Initialize the global tracing subscriber.
+///
+/// When `path` is set, logs are written to a rotating file and only mirrored to stdout
+/// if `console` is enabled. If the log file cannot be opened, logging falls back to stdout.
+///
+/// The returned guard flushes buffered file output on drop and must be held for the
+/// lifetime of the process.
+pub fn init_tracing(config: &LoggingConfig) -> Option<WorkerGuard> {
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(&config.level));
 
     let use_console = config.console || config.format == "console";
-    
-    if use_console {
-        // Pretty console output for development/debug
-        tracing_subscriber::registry()
-            .with(env_filter)
-            .with(
+
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut guard = None;
+    let mut file_error = None;
+
+    if let Some(path) = &config.path {
+        match build_file_appender(path, &config.rotation) {
+            Ok(appender) => {
+                let (writer, worker_guard) = tracing_appender::non_blocking(appender);
+                guard = Some(worker_guard);
+
+                if config.format == "console" {
+                    layers.push(
+                        tracing_subscriber::fmt::layer()
+                            .with_writer(writer)
+                            .with_target(true)
+                            .with_level(true)
+                            .with_ansi(false)
+                            .with_line_number(true)
+                            .with_thread_ids(true)
+                            .boxed(),
+                    );
+                } else {
+                    layers.push(
+                        tracing_subscriber::fmt::layer()
+                            .json()
+                            .with_writer(writer)
+                            .with_current_span(true)
+                            .with_span_list(true)
+                            .boxed(),
+                    );
+                }
+            }
+            Err(e) => file_error = Some(e),
+        }
+    }
+
+    // Without a working log file, stdout is the only output
+    if use_console || guard.is_none() {
+        if use_console {
+            // Pretty console output for development/debug
+            layers.push(
                 tracing_subscriber::fmt::layer()
                     .with_target(true)
                     .with_level(true)
                     .with_ansi(true)
                     .with_line_number(true)
                     .with_thread_ids(true)
-            )
-            .init();
-    } else {
-        tracing_subscriber::registry()
-            .with(env_filter)
-            .with(
+                    .boxed(),
+            );
+        } else {
+            layers.push(
                 tracing_subscriber::fmt::layer()
                     .json()
                     .with_current_span(true)
                     .with_span_list(true)
-            )
-            .init();
+                    .boxed(),
+            );
+        }
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(env_filter)
+        .init();
+
+    if let Some(e) = file_error {
+        tracing::error!(
+            error = format!("{:#}", e),
+            "Failed to open log file, logging to stdout instead"
+        );
+    }
+
+    guard
+}
+
+/// Create the log directory if needed and open a rolling appender for `path`
+fn build_file_appender(path: &Path, rotation: &str) -> Result<RollingFileAppender> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Log path '{}' has no file name", path.display()))?;
+
+    std::fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create log directory '{}'", directory.display()))?;
+
+    let rotation = match rotation {
+        "hourly" => Rotation::HOURLY,
+        "never" => Rotation::NEVER,
+        _ => Rotation::DAILY,
+    };
+
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name.to_string_lossy())
+        .build(directory)
+        .with_context(|| format!("Failed to open log file '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_file_appender_creates_directory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested").join("tracker.log");
+
+        assert!(build_file_appender(&path, "never").is_ok());
+        assert!(temp_dir.path().join("nested").is_dir());
+    }
+
+    #[test]
+    fn test_build_file_appender_without_file_name() {
+        assert!(build_file_appender(Path::new("/"), "daily").is_err());
     }
 }
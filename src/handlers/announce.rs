@@ -1,36 +1,113 @@
-use crate::anti_cheat::{announce_interval, duplicate_peer, ghost_seeder, ratio_check, speed_check};
-use crate::bencode::response::build_announce_response;
+use crate::anti_cheat::violations::{record_violation, ViolationKind};
+use crate::anti_cheat::{announce_interval, duplicate_peer, event_sequence, ghost_seeder, ratio_check, size_sanity, speed_check};
+use crate::bencode::response::{
+    bencode_http_response, build_announce_response, build_retired_response, sign_response, AnnounceResponseOptions,
+    BencodeBody,
+};
+use crate::core::config::AnnounceConfig;
 use crate::core::error::AnnounceError;
+use crate::core::startup::{torrent_from_api, user_from_api};
 use crate::core::state::AppState;
 use crate::models::peer::Peer;
-use crate::utils::time::current_timestamp;
-use crate::validation::params::{AnnounceEvent, AnnounceParams};
+use crate::models::torrent::Torrent;
+use crate::models::user::User;
+use crate::stores::peer_store::PeerOrder;
+use crate::stores::rejected_passkeys::Rejection;
+use crate::utils::ip::{is_globally_routable, normalize_ip};
+use crate::utils::time::{current_timestamp, current_timestamp_millis, elapsed_seconds};
+use crate::validation::params::{AnnounceEvent, AnnounceParams, Passkey, PasskeyFormat, ValidatedAnnounceParams};
 use axum::{
     extract::{ConnectInfo, State},
-    http::{HeaderMap, StatusCode},
+    http::{header, HeaderMap, HeaderValue},
     response::Response,
 };
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tracing::{debug, info, instrument, warn};
 
+/// Warning sent with announces shed by the global rate cap
+const SHED_WARNING: &str = "Tracker is under heavy load, please retry later";
+
+/// Interval advertised for a swarm of `swarm_size` peers when scaling is enabled
+///
+/// Grows linearly from `scaled_interval_min` for an empty swarm to `scaled_interval_max`
+/// at `scaled_interval_swarm_size` peers and stays there for larger swarms.
+fn swarm_interval(config: &AnnounceConfig, swarm_size: u32) -> i64 {
+    let size = swarm_size.min(config.scaled_interval_swarm_size) as i64;
+    let span = config.scaled_interval_max - config.scaled_interval_min;
+    config.scaled_interval_min + span * size / config.scaled_interval_swarm_size as i64
+}
+
+/// Interval advertised to a torrent's swarm of `swarm_size` peers
+///
+/// A torrent override wins over the swarm-scaled interval, which wins over the fixed one.
+fn advertised_interval(config: &AnnounceConfig, torrent: &Torrent, swarm_size: u32) -> i64 {
+    match torrent.announce_interval {
+        Some(interval) => interval,
+        None if config.scaled_interval_max > 0 => swarm_interval(config, swarm_size),
+        None => config.interval,
+    }
+}
+
+/// Response layout for an announce, taking the peers6 and per-family caps from config
+fn response_options(config: &AnnounceConfig, interval: i64, compact: bool, freeleech: bool) -> AnnounceResponseOptions {
+    AnnounceResponseOptions {
+        interval,
+        compact,
+        emit_peers6: config.emit_peers6,
+        omit_empty_peers: config.omit_empty_peers,
+        freeleech,
+        max_peers_ipv4: config.max_numwant_ipv4,
+        max_peers_ipv6: config.max_numwant_ipv6,
+    }
+}
+
+/// Tell a browser opening the announce URL apart from a client
+///
+/// Neither info_hash nor peer_id means a browser, whether or not the passkey is in the
+/// URL. Anything else, or everything when `detect_browsers` is off, is treated as a
+/// client and a missing required parameter is reported by name instead of failing
+/// validation generically.
+fn classify_request(params: &AnnounceParams, detect_browsers: bool) -> Result<(), AnnounceError> {
+    if detect_browsers && params.info_hash.is_empty() && params.peer_id.is_empty() {
+        return Err(AnnounceError::BrowserAccess);
+    }
+
+    for (name, value) in [
+        ("passkey", &params.passkey),
+        ("info_hash", &params.info_hash),
+        ("peer_id", &params.peer_id),
+    ] {
+        if value.is_empty() {
+            return Err(AnnounceError::MissingParameter(name.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
 /// Main announce handler
 /// 
 /// Processes BitTorrent announce requests from clients.
 ///
 /// # Flow
-/// 1. Parse and validate query parameters
+/// 1. Reject oversized query strings, parse the query and turn away browsers, then report
+///    missing parameters, validate the rest and shed load over the global announce rate
 /// 2. Extract IP address and User-Agent
-/// 3. Authenticate user (check passkey)
-/// 4. Authorize torrent (check info_hash)
-/// 5. Check IP blacklist
+/// 3. Authenticate user (check stored passkey, looking unknown ones up in the external API
+///    if enabled, or verify signed passkey)
+/// 4. Authorize torrent (check info_hash, looking unknown ones up in the external API if enabled;
+///    inactive torrents fail or, if configured, get a retirement response)
+/// 5. Check IP blacklist and, if enabled, reject non-routable IPs
 /// 6. Check client blacklist
-/// 7. Check rate limit
-/// 8. Run anti-cheat checks (log warnings, don't block)
-/// 9. Handle lifecycle events (started, stopped, completed)
+/// 7. Check rate limit (skipped for exempt users), then remove stopped peers and return early,
+///    then refuse new peers past the user class's torrent quota (skipped for exempt users)
+/// 8. Run anti-cheat checks (log warnings, don't block; skipped for exempt users and optionally for stats-only announces)
+/// 9. Validate the event sequence and handle lifecycle events (started, completed)
 /// 10. Update peer in peer store
-/// 11. Get peer list
-/// 12. Build and return bencode response
+/// 11. Get peer list and swarm stats in one peer store lookup
+/// 12. Build and return bencode response, signed if `announce.response_signing_secret` is set
 #[instrument(skip(state, headers, raw_query))]
 pub async fn announce_handler(
     State(state): State<Arc<AppState>>,
@@ -38,78 +115,46 @@ pub async fn announce_handler(
     headers: HeaderMap,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> Result<Response, AnnounceError> {
-    let query_str = raw_query.ok_or_else(|| {
-        warn!("Missing query string - browser access");
-        state.metrics.increment_failed();
-        AnnounceError::BrowserAccess
-    })?;
-    
-    let mut passkey = "";
-    let mut info_hash = "";
-    let mut peer_id = "";
-    let mut port = 0u16;
-    let mut uploaded = 0u64;
-    let mut downloaded = 0u64;
-    let mut left = 0u64;
-    let mut event = "";
-    let mut numwant = 50u32;
-    let mut compact = 1u8;
-    let mut ip: Option<&str> = None;
-    
-    for pair in query_str.split('&') {
-        if let Some((key, value)) = pair.split_once('=') {
-            match key {
-                "passkey" => passkey = value,
-                "info_hash" => info_hash = value,
-                "peer_id" => peer_id = value,
-                "port" => port = value.parse().unwrap_or(0),
-                "uploaded" => uploaded = value.parse().unwrap_or(0),
-                "downloaded" => downloaded = value.parse().unwrap_or(0),
-                "left" => left = value.parse().unwrap_or(0),
-                "event" => event = value,
-                "numwant" => numwant = value.parse().unwrap_or(50),
-                "compact" => compact = value.parse().unwrap_or(1),
-                "ip" => ip = Some(value),
-                _ => {}
-            }
-        }
+    // A missing query is handled like an empty one by the classifier below
+    let query_str = raw_query.unwrap_or_default();
+
+    if query_str.len() > state.config.performance.max_query_length {
+        warn!(
+            length = query_str.len(),
+            max_length = state.config.performance.max_query_length,
+            "Query string too long"
+        );
+        state.metrics.increment_blocked();
+        return Err(AnnounceError::InvalidParameter("Query string too long".to_string()));
     }
+    
+    let params = AnnounceParams::from_query(&query_str);
 
-    if !passkey.is_empty() && info_hash.is_empty() && peer_id.is_empty() {
-        warn!("Browser access detected: only passkey provided");
+    classify_request(&params, state.config.server.browser_responses).inspect_err(|e| {
+        match e {
+            AnnounceError::BrowserAccess => warn!("Browser access detected: no info_hash or peer_id"),
+            _ => warn!(error = %e, "Announce missing a required parameter"),
+        }
         state.metrics.increment_failed();
-        return Err(AnnounceError::BrowserAccess);
-    }
+    })?;
 
-    let params = AnnounceParams {
-        passkey: passkey.to_string(),
-        info_hash: info_hash.to_string(),
-        peer_id: peer_id.to_string(),
-        port,
-        uploaded,
-        downloaded,
-        left,
-        event: event.to_string(),
-        numwant,
-        compact,
-        ip: ip.map(|s| s.to_string()),
-    };
     debug!("Processing announce request");
 
     state.metrics.increment_announces();
 
-    let user_agent = headers
+    // Ban matching uses the raw bytes; the lossy string is only for logging and storage
+    let user_agent_bytes = headers
         .get("user-agent")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("Unknown")
-        .to_string();
+        .map(|v| v.as_bytes())
+        .unwrap_or(b"Unknown");
+    let user_agent = String::from_utf8_lossy(user_agent_bytes).into_owned();
 
     let header_list: Vec<(String, String)> = headers
         .iter()
         .map(|(name, value)| {
             (
                 name.as_str().to_string(),
-                value.to_str().unwrap_or("").to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
             )
         })
         .collect();
@@ -123,13 +168,56 @@ pub async fn announce_handler(
         return Err(AnnounceError::SuspiciousClient);
     }
 
-    let validated = params.validate().map_err(|e| {
+    // Announce is a plain GET; a body means a broken or hostile client
+    let has_body = headers.contains_key(header::TRANSFER_ENCODING)
+        || headers
+            .get(header::CONTENT_LENGTH)
+            .is_some_and(|v| v.to_str().ok().and_then(|v| v.parse::<u64>().ok()) != Some(0));
+    if has_body {
+        warn!(user_agent = %user_agent, "Suspicious client detected: announce with a request body");
+        state.metrics.increment_blocked();
+        return Err(AnnounceError::SuspiciousClient);
+    }
+
+    if state.config.security.reject_empty_user_agent
+        && headers.get("user-agent").is_none_or(|v| v.as_bytes().trim_ascii().is_empty())
+    {
+        warn!("Suspicious client detected: missing or empty User-Agent");
+        state.metrics.increment_blocked();
+        return Err(AnnounceError::SuspiciousClient);
+    }
+
+    let passkey_format = if state.config.auth.passkey_format == "hex" {
+        PasskeyFormat::Hex
+    } else {
+        PasskeyFormat::Alphanumeric
+    };
+    let validated = params.validate(passkey_format).map_err(|e| {
         warn!(error = %e, "Parameter validation failed");
         state.metrics.increment_failed();
         AnnounceError::InvalidParameter("Invalid announce parameters".to_string())
     })?;
 
-    let ip = validated.ip.unwrap_or(addr.ip());
+    // Shed load before any lookups once the tracker-wide rate is exceeded; a successful
+    // response with a long interval backs clients off where a failure would be retried
+    if !state.global_limiter.try_acquire(current_timestamp_millis()) {
+        debug!("Global announce rate exceeded, shedding announce");
+        state.metrics.increment_blocked();
+        state.metrics.increment_shed_announces();
+        return Ok(bencode_http_response(build_retired_response(
+            state.config.performance.shed_interval,
+            SHED_WARNING,
+            validated.compact,
+        )));
+    }
+
+    // Mapped addresses from dual-stack sockets are stored, banned and encoded as IPv4
+    let ip = normalize_ip(validated.ip.unwrap_or(addr.ip()));
+    // Dual-stack clients declare their address in the other family with ipv4/ipv6
+    let alt_ip = match ip {
+        IpAddr::V4(_) => validated.ipv6.map(IpAddr::V6).map(normalize_ip).filter(IpAddr::is_ipv6),
+        IpAddr::V6(_) => validated.ipv4.map(IpAddr::V4),
+    };
 
     debug!(
         ip = %ip,
@@ -141,31 +229,108 @@ pub async fn announce_handler(
         "Validated announce parameters"
     );
 
-    let user = state
-        .user_cache
-        .get_user(validated.passkey)
-        .ok_or_else(|| {
-            warn!(passkey = ?validated.passkey, "Invalid passkey");
-            state.metrics.increment_failed();
-            AnnounceError::InvalidPasskey
-        })?;
+    let user = match validated.passkey {
+        Passkey::Stored(passkey) => {
+            // Stale clients keep retrying; answer repeats without a lookup or a warn log
+            if let Some(rejection) = state.rejected_passkeys.get(&passkey, current_timestamp()) {
+                debug!(passkey = ?passkey, rejection = ?rejection, "Passkey rejected recently, answering from cache");
+                state.metrics.increment_failed();
+                return Err(match rejection {
+                    Rejection::UnknownPasskey => AnnounceError::InvalidPasskey,
+                    Rejection::UserDisabled => AnnounceError::UserDisabled,
+                });
+            }
+
+            let cached = state.user_cache.load().get_user(passkey);
+            match cached {
+                Some(user) => user,
+                None => match load_unknown_user(&state, passkey).await {
+                    Some(user) => user,
+                    None => {
+                        let repeats = state
+                            .rejected_passkeys
+                            .insert(passkey, Rejection::UnknownPasskey, current_timestamp());
+                        warn!(passkey = ?passkey, repeats = repeats, "Invalid passkey");
+                        state.metrics.increment_failed();
+                        return Err(AnnounceError::InvalidPasskey);
+                    }
+                },
+            }
+        }
+        Passkey::Signed(signed) => {
+            let secret = match state.config.auth.passkey_secret.as_deref() {
+                Some(secret) if state.config.auth.passkey_mode == "signed" => secret,
+                _ => {
+                    warn!(user_id = signed.user_id, "Signed passkey received but signed mode is disabled");
+                    state.metrics.increment_failed();
+                    return Err(AnnounceError::InvalidPasskey);
+                }
+            };
+
+            if let Err(e) = signed.verify_signature(secret) {
+                warn!(user_id = signed.user_id, error = %e, "Invalid signed passkey");
+                state.metrics.increment_failed();
+                return Err(AnnounceError::InvalidPasskey);
+            }
+
+            if signed.is_expired(current_timestamp()) {
+                warn!(user_id = signed.user_id, expiry = signed.expiry, "Signed passkey expired");
+                state.metrics.increment_failed();
+                return Err(AnnounceError::PasskeyExpired);
+            }
+
+            // Signed passkeys carry the user ID, so no cache entry is required; they carry
+            // no class either, so these users fall under the class 0 torrent quota
+            Arc::new(User::new(signed.user_id, [0u8; 32], 0, true))
+        }
+    };
+
+    if state.user_blacklist.is_banned(user.id) {
+        warn!(user_id = user.id, "User is banned");
+        state.metrics.increment_blocked();
+        return Err(AnnounceError::UserBanned);
+    }
 
     if !user.is_active {
-        warn!(user_id = user.id, "User account is disabled");
+        let repeats = match validated.passkey {
+            Passkey::Stored(passkey) => state
+                .rejected_passkeys
+                .insert(passkey, Rejection::UserDisabled, current_timestamp()),
+            Passkey::Signed(_) => 0,
+        };
+        warn!(user_id = user.id, repeats = repeats, "User account is disabled");
         state.metrics.increment_failed();
         return Err(AnnounceError::UserDisabled);
     }
 
-    info!(user_id = user.id, "User authenticated");
+    // Routine success logs are sampled; failures and blocks above and below are always logged
+    let log_success = sample_announce_log(&state);
+    if log_success {
+        info!(user_id = user.id, "User authenticated");
+    }
 
-    let torrent = state
-        .torrent_cache
-        .get_torrent(validated.info_hash)
-        .ok_or_else(|| {
+    let cached = state.torrent_cache.load().get_torrent(validated.info_hash);
+    let torrent = match cached {
+        Some(torrent) => torrent,
+        None => load_unknown_torrent(&state, validated.info_hash).await.ok_or_else(|| {
             warn!(info_hash = ?validated.info_hash, "Torrent not registered");
             state.metrics.increment_failed();
             AnnounceError::TorrentNotFound
-        })?;
+        })?,
+    };
+    state.announce_counts.record(user.id, torrent.id);
+
+    if !torrent.is_active && state.config.announce.retire_inactive_torrents {
+        info!(torrent_id = torrent.id, "Announce for retired torrent, asking client to back off");
+        let response = build_retired_response(
+            state.config.announce.retired_interval,
+            &state.config.announce.retired_message,
+            validated.compact,
+        );
+
+        state.metrics.increment_successful();
+        return Ok(bencode_http_response(response));
+    }
 
     if !torrent.is_active {
         warn!(torrent_id = torrent.id, "Torrent is not active");
@@ -175,160 +340,201 @@ pub async fn announce_handler(
 
     debug!(torrent_id = torrent.id, "Torrent authorized");
 
+    // Anti-cheat measures against the shortest interval a client may have been given as
+    // the swarm grew; the advertised one is sized from the counts the response carries
+    let announce_config = &state.config.announce;
+    let shortest_interval = match torrent.announce_interval {
+        Some(interval) => interval,
+        None if announce_config.scaled_interval_max > 0 => announce_config.scaled_interval_min,
+        None => announce_config.interval,
+    };
+    let freeleech = state.config.announce.emit_freeleech && torrent.is_freeleech;
+
     if state.ip_blacklist.is_banned(ip) {
         warn!(ip = %ip, "IP address is banned");
         state.metrics.increment_blocked();
         return Err(AnnounceError::IpBanned);
     }
 
-    if state.client_blacklist.is_banned(&user_agent) {
+    if state.config.security.reject_non_routable && !is_globally_routable(ip) {
+        warn!(ip = %ip, "Non-routable IP address rejected");
+        state.metrics.increment_blocked();
+        return Err(AnnounceError::NonRoutableIp);
+    }
+
+    if let Some(alt_ip) = alt_ip.filter(|alt_ip| state.ip_blacklist.is_banned(*alt_ip)) {
+        warn!(ip = %ip, alt_ip = %alt_ip, "Declared secondary IP address is banned");
+        state.metrics.increment_blocked();
+        return Err(AnnounceError::IpBanned);
+    }
+
+    // A non-routable secondary address is useless to other peers, so it is dropped
+    // rather than failing the announce
+    let alt_ip = alt_ip.filter(|alt_ip| {
+        let keep = !state.config.security.reject_non_routable || is_globally_routable(*alt_ip);
+        if !keep {
+            debug!(alt_ip = %alt_ip, "Ignoring non-routable secondary IP address");
+        }
+        keep
+    });
+
+    if state.client_blacklist.is_banned(user_agent_bytes) {
         warn!(user_agent = %user_agent, "Client is banned");
         state.metrics.increment_blocked();
         return Err(AnnounceError::ClientBanned);
     }
 
+    // Trusted users (internal seedboxes, monitoring bots) skip rate limiting and anti-cheat
+    let exempt = state.exempt_users.contains(&user.id);
+
     let current_time = current_timestamp();
-    if !state.rate_limiter.check_and_increment(ip, current_time) {
+    if !exempt && !state.rate_limiter.check_and_increment(ip, current_time) {
         warn!(ip = %ip, "Rate limit exceeded");
         state.metrics.increment_blocked();
         return Err(AnnounceError::RateLimitExceeded);
     }
 
-    let existing_peer = state
-        .peer_store
-        .get_peers(validated.info_hash, 1, validated.peer_id)
-        .into_iter()
-        .find(|p| p.user_id == user.id);
-
-    let last_announce = existing_peer.as_ref().map(|p| p.last_announce);
-    if let Err(e) = announce_interval::check_announce_interval(
-        user.id,
-        torrent.id,
-        last_announce,
-        current_time,
-        state.config.performance.min_announce_interval,
-    ) {
-        warn!(
-            user_id = user.id,
-            torrent_id = torrent.id,
-            error = %e,
-            "Announce interval check failed"
-        );
+    // Departing peers are handled as soon as the request is authorized, skipping the
+    // anti-cheat pipeline entirely
+    if validated.event == Some(AnnounceEvent::Stopped) {
+        return Ok(handle_stopped(&state, user.id, &torrent, freeleech, &validated, log_success));
     }
 
-    if let Err(e) = duplicate_peer::check_duplicate_peer(
-        &state.peer_store,
-        user.id,
-        torrent.id,
-        state.config.anti_cheat.max_ips_per_user,
-    ) {
-        warn!(
-            user_id = user.id,
-            torrent_id = torrent.id,
-            error = %e,
-            "Duplicate peer check failed"
-        );
-    }
+    let existing_peer = state
+        .peer_store
+        .get_peer(validated.info_hash, validated.peer_id)
+        .filter(|p| p.user_id == user.id);
 
-    if let Some(ref old_peer) = existing_peer {
-        let elapsed = current_time - old_peer.last_announce;
-        if let Err(e) = speed_check::check_speed(
-            user.id,
-            torrent.id,
-            old_peer.uploaded,
-            validated.uploaded,
-            old_peer.downloaded,
-            validated.downloaded,
-            elapsed,
-            state.config.anti_cheat.max_upload_speed,
-        ) {
+    // A new peer may not take the user into one torrent more than their class allows;
+    // peers in torrents they are already active in are always accepted
+    let quota = state
+        .config
+        .security
+        .torrent_quotas
+        .iter()
+        .find(|quota| quota.class == user.class);
+    if let Some(quota) = quota.filter(|_| !exempt && existing_peer.is_none()) {
+        let active = state.peer_store.user_torrent_count(user.id);
+        if active >= quota.max_torrents as usize && !state.peer_store.user_in_torrent(user.id, torrent.id) {
             warn!(
                 user_id = user.id,
                 torrent_id = torrent.id,
-                error = %e,
-                "Speed check failed"
+                active_torrents = active,
+                max_torrents = quota.max_torrents,
+                "User is at their torrent quota"
             );
+            state.metrics.increment_failed();
+            state.metrics.increment_quota_rejections();
+            let message = state.config.security.quota_message.clone().unwrap_or_else(|| {
+                format!(
+                    "You are active in {} torrents, the most your class allows; stop one before starting another",
+                    quota.max_torrents
+                )
+            });
+            return Err(AnnounceError::QuotaExceeded(message));
         }
     }
 
-    if let Err(e) = ratio_check::check_ratio(
-        user.id,
-        torrent.id,
-        validated.uploaded,
-        validated.downloaded,
-        state.config.anti_cheat.max_ratio,
-    ) {
-        warn!(
+    // numwant=0 without an event is a stats refresh rather than a transfer update
+    let stats_only = validated.numwant == 0 && validated.event.is_none();
+    if exempt {
+        debug!(
             user_id = user.id,
             torrent_id = torrent.id,
-            error = %e,
-            "Ratio check failed"
+            "User is exempt, skipping anti-cheat checks"
         );
-    }
-
-    let is_seeder = validated.left == 0;
-    let is_completed_event = validated.event == Some(AnnounceEvent::Completed);
-    if let Err(e) = ghost_seeder::check_ghost_seeder(
-        user.id,
-        torrent.id,
-        is_seeder,
-        validated.uploaded,
-        state.config.anti_cheat.min_seeder_upload,
-        is_completed_event,
-    ) {
-        warn!(
+    } else if stats_only && state.config.announce.skip_anti_cheat_on_stats_only {
+        debug!(
             user_id = user.id,
             torrent_id = torrent.id,
-            error = %e,
-            "Ghost seeder check failed"
+            "Stats-only announce, skipping anti-cheat checks"
         );
+    } else {
+        run_anti_cheat_checks(&state, user.id, &torrent, shortest_interval, &validated, existing_peer.as_ref(), current_time);
     }
 
-    match validated.event {
-        Some(AnnounceEvent::Stopped) => {
-            if let Err(e) = state.peer_store.remove_peer(validated.info_hash, validated.peer_id) {
+    if state.config.anti_cheat.event_sequence_policy != "off" {
+        let last_event = existing_peer.as_ref().map(|p| p.last_event);
+        if let Err(e) = event_sequence::check_event_sequence(user.id, torrent.id, last_event, validated.event) {
+            record_violation(&state.violation_counts, user.id, ViolationKind::EventSequence);
+            if state.config.anti_cheat.event_sequence_policy == "block" {
                 warn!(
                     user_id = user.id,
                     torrent_id = torrent.id,
                     error = %e,
-                    "Failed to remove peer"
-                );
-            } else {
-                info!(
-                    user_id = user.id,
-                    torrent_id = torrent.id,
-                    "Peer stopped and removed"
+                    "Announce rejected: event out of sequence"
                 );
+                state.metrics.increment_blocked();
+                return Err(AnnounceError::InvalidEventSequence);
             }
 
-            let (seeders, leechers) = state.peer_store.get_stats(validated.info_hash);
-            let response = build_announce_response(&[], seeders, leechers, validated.compact);
+            warn!(
+                user_id = user.id,
+                torrent_id = torrent.id,
+                error = %e,
+                "Event sequence check failed"
+            );
+        }
+    }
 
-            state.metrics.increment_successful();
-            return Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "text/plain")
-                .body(response.into())
-                .unwrap());
+    if !exempt && state.config.anti_cheat.size_sanity_policy != "off" {
+        let result = size_sanity::check_left_vs_size(
+            user.id,
+            torrent.id,
+            validated.event,
+            validated.left,
+            torrent.size,
+        );
+        if result.is_err() {
+            record_violation(&state.violation_counts, user.id, ViolationKind::SizeSanity);
         }
-        Some(AnnounceEvent::Started) => {
-            info!(
+        if result.is_err() && state.config.anti_cheat.size_sanity_policy == "block" {
+            warn!(
                 user_id = user.id,
                 torrent_id = torrent.id,
-                "Peer started"
+                left = validated.left,
+                torrent_size = torrent.size,
+                "Announce rejected: bytes left exceed torrent size"
             );
+            state.metrics.increment_blocked();
+            return Err(AnnounceError::LeftExceedsSize);
         }
-        Some(AnnounceEvent::Completed) => {
+    }
+
+    state.metrics.increment_event(validated.event);
+    match validated.event {
+        Some(AnnounceEvent::Started) if log_success => {
             info!(
                 user_id = user.id,
                 torrent_id = torrent.id,
-                "Peer completed download"
+                seeding = validated.left == 0,
+                "Peer started"
             );
         }
-        None => {}
+        Some(AnnounceEvent::Completed) => {
+            // Only a leecher finishing counts as a download; a peer that started
+            // already complete (re-seeding) must not inflate the completed count
+            if existing_peer.as_ref().is_some_and(|p| p.is_seeder) {
+                debug!(
+                    user_id = user.id,
+                    torrent_id = torrent.id,
+                    "Completed event from a peer already seeding, not counted"
+                );
+            } else {
+                state.peer_store.record_completion(validated.info_hash);
+                if log_success {
+                    info!(
+                        user_id = user.id,
+                        torrent_id = torrent.id,
+                        "Peer completed download"
+                    );
+                }
+            }
+        }
+        Some(AnnounceEvent::Started) | Some(AnnounceEvent::Stopped) | None => {}
     }
 
-    let peer = Peer::new(
+    let mut peer = Peer::new(
         user.id,
         torrent.id,
         validated.peer_id,
@@ -340,6 +546,17 @@ pub async fn announce_handler(
         current_time,
         user_agent.clone(),
     );
+    peer.last_event = validated
+        .event
+        .or_else(|| existing_peer.as_ref().and_then(|p| p.last_event));
+    peer.key = validated.key.clone();
+    peer.alt_ip = alt_ip;
+    peer.supportcrypto = validated.supportcrypto;
+    peer.requirecrypto = validated.requirecrypto;
+    // Connectability reported by the backend holds until the peer moves to another port
+    if let Some(old_peer) = existing_peer.as_ref().filter(|p| p.port == peer.port) {
+        peer.connectable = old_peer.connectable;
+    }
 
     if existing_peer.is_some() {
         state
@@ -348,28 +565,50 @@ pub async fn announce_handler(
             .map_err(|e| {
                 warn!(error = %e, "Failed to update peer");
                 state.metrics.increment_failed();
-                AnnounceError::InternalError(e)
+                AnnounceError::InternalError(e.into())
             })?;
         debug!(user_id = user.id, torrent_id = torrent.id, "Peer updated");
     } else {
+        if state.config.announce.dedupe_peer_endpoints {
+            let replaced = state
+                .peer_store
+                .remove_endpoint_duplicates(validated.info_hash, &peer)
+                .map_err(|e| {
+                    warn!(error = %e, "Failed to remove duplicate peers");
+                    state.metrics.increment_failed();
+                    AnnounceError::InternalError(e.into())
+                })?;
+            if replaced > 0 {
+                debug!(user_id = user.id, torrent_id = torrent.id, replaced, "Replaced peer on the same endpoint");
+            }
+        }
         state
             .peer_store
             .add_peer(validated.info_hash, peer)
             .map_err(|e| {
                 warn!(error = %e, "Failed to add peer");
                 state.metrics.increment_failed();
-                AnnounceError::InternalError(e)
+                AnnounceError::InternalError(e.into())
             })?;
-        info!(user_id = user.id, torrent_id = torrent.id, "Peer added");
+        if log_success {
+            info!(user_id = user.id, torrent_id = torrent.id, "Peer added");
+        }
     }
 
-    let peers = state.peer_store.get_peers(
-        validated.info_hash,
-        validated.numwant,
-        validated.peer_id,
-    );
-
-    let (seeders, leechers) = state.peer_store.get_stats(validated.info_hash);
+    let order = if state.config.announce.preferred_peer_ratio > 0.0 {
+        PeerOrder::Weighted {
+            requester_is_seeder: validated.left == 0,
+            preferred_ratio: state.config.announce.preferred_peer_ratio,
+            prefer_ipv6: state.config.announce.prefer_same_family.then(|| ip.is_ipv6()),
+        }
+    } else if state.config.announce.prefer_same_family {
+        PeerOrder::PreferFamily { prefer_ipv6: ip.is_ipv6() }
+    } else {
+        PeerOrder::Random
+    };
+    let (peers, (seeders, leechers)) = state
+        .peer_store
+        .get_peers_and_stats(validated.info_hash, validated.numwant, validated.peer_id, order);
 
     debug!(
         seeders = seeders,
@@ -378,13 +617,1132 @@ pub async fn announce_handler(
         "Building announce response"
     );
 
-    let response = build_announce_response(&peers, seeders, leechers, validated.compact);
+    let interval = advertised_interval(&state.config.announce, &torrent, seeders + leechers);
+    let options = response_options(&state.config.announce, interval, validated.compact, freeleech);
+    let mut response = build_announce_response(&peers, seeders, leechers, &options);
+    if let Some(secret) = &state.config.announce.response_signing_secret {
+        sign_response(&mut response, secret);
+    }
+
+    state.metrics.increment_successful();
+
+    Ok(bencode_http_response(response))
+}
+
+/// Apply the configured `announce.content_type` to bencoded announce and scrape responses
+///
+/// Runs as a layer on the announce and scrape routes so failures rendered by
+/// `AnnounceError`, which has no access to the config, get the same content type as
+/// successes.
+pub async fn apply_announce_content_type(State(state): State<Arc<AppState>>, mut response: Response) -> Response {
+    if response.extensions().get::<BencodeBody>().is_some() {
+        if let Ok(value) = HeaderValue::from_str(&state.config.announce.content_type) {
+            response.headers_mut().insert(header::CONTENT_TYPE, value);
+        }
+    }
+    response
+}
+
+/// Fetch a passkey missing from the user cache from the external API
+///
+/// Covers users created in the backend since the last sync. Lookups are rate limited
+/// so a flood of bogus passkeys cannot hammer the backend; a passkey the API does not
+/// know is then cached as rejected like any other unknown passkey.
+async fn load_unknown_user(state: &AppState, passkey: [u8; 32]) -> Option<Arc<User>> {
+    // The client also exists when only torrent lookups are enabled
+    if state.config.sync.lazy_user_lookups_per_second == 0 {
+        return None;
+    }
+    let api_client = state.api_client.as_ref()?;
+    if !state.user_lookup_limiter.try_acquire(current_timestamp_millis()) {
+        debug!(passkey = ?passkey, "On-demand user lookup rate exceeded");
+        return None;
+    }
+
+    // Stored passkeys are validated as ASCII
+    let passkey_str = std::str::from_utf8(&passkey).ok()?;
+    let api_user = match api_client.fetch_user(passkey_str).await {
+        Ok(api_user) => api_user?,
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "On-demand user lookup failed");
+            return None;
+        }
+    };
+
+    let user = user_from_api(&api_user)?;
+    info!(user_id = user.id, "Loaded unknown passkey from external API");
+    if let Err(e) = state.user_cache.load().add_user(user.clone()) {
+        warn!(user_id = user.id, error = %e, "User cache full, on-demand user not cached");
+    }
+    Some(Arc::new(user))
+}
+
+/// Fetch an info_hash missing from the torrent cache from the external API
+///
+/// Covers torrents published since the last sync. Lookups are rate limited, and an
+/// info_hash the API does not know is remembered for `sync.unknown_torrent_ttl`
+/// seconds so repeated announces for it do not reach the backend.
+async fn load_unknown_torrent(state: &AppState, info_hash: [u8; 20]) -> Option<Arc<Torrent>> {
+    if state.config.sync.lazy_torrent_lookups_per_second == 0 {
+        return None;
+    }
+    let api_client = state.api_client.as_ref()?;
+    let now = current_timestamp();
+    if state.unknown_torrents.contains(&info_hash, now) {
+        return None;
+    }
+    if !state.torrent_lookup_limiter.try_acquire(current_timestamp_millis()) {
+        debug!(info_hash = ?info_hash, "On-demand torrent lookup rate exceeded");
+        return None;
+    }
+
+    let api_torrent = match api_client.fetch_torrent(&hex::encode(info_hash)).await {
+        Ok(Some(api_torrent)) => api_torrent,
+        Ok(None) => {
+            state.unknown_torrents.insert(info_hash, now);
+            return None;
+        }
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "On-demand torrent lookup failed");
+            return None;
+        }
+    };
+
+    let torrent = torrent_from_api(&api_torrent)?;
+    info!(torrent_id = torrent.id, "Loaded unknown info_hash from external API");
+    if let Err(e) = state.torrent_cache.load().add_torrent(torrent.clone()) {
+        warn!(torrent_id = torrent.id, error = %e, "Torrent cache full, on-demand torrent not cached");
+    }
+    Some(Arc::new(torrent))
+}
+
+/// Whether this announce's routine success logs are emitted
+///
+/// Counts announces and picks one in every `logging.announce_log_sample_rate`.
+fn sample_announce_log(state: &AppState) -> bool {
+    let rate = state.config.logging.announce_log_sample_rate;
+    rate <= 1 || state.announce_log_counter.fetch_add(1, Ordering::Relaxed).is_multiple_of(rate)
+}
+
+/// Remove a stopped peer and build its response
+///
+/// The client is leaving and usually ignores the body, so the peer is removed directly
+/// by peer_id without the existing-peer lookup, and no anti-cheat checks run.
+fn handle_stopped(
+    state: &AppState,
+    user_id: u32,
+    torrent: &Torrent,
+    freeleech: bool,
+    validated: &ValidatedAnnounceParams,
+    log_success: bool,
+) -> Response {
+    let torrent_id = torrent.id;
+    match state.peer_store.remove_peer(validated.info_hash, validated.peer_id) {
+        Ok(()) if log_success => {
+            info!(
+                user_id = user_id,
+                torrent_id = torrent_id,
+                "Peer stopped and removed"
+            );
+        }
+        Ok(()) => {}
+        Err(e) if e.is_not_found() => {
+            debug!(
+                user_id = user_id,
+                torrent_id = torrent_id,
+                "Peer stopped but was not in the peer store"
+            );
+        }
+        Err(e) => {
+            warn!(
+                user_id = user_id,
+                torrent_id = torrent_id,
+                error = %e,
+                "Failed to remove peer"
+            );
+        }
+    }
+
+    let response = if state.config.announce.empty_stopped_response {
+        Vec::new()
+    } else {
+        let (seeders, leechers) = state.peer_store.get_stats(validated.info_hash);
+        let interval = advertised_interval(&state.config.announce, torrent, seeders + leechers);
+        let options = response_options(&state.config.announce, interval, validated.compact, freeleech);
+        let mut response = build_announce_response(&[], seeders, leechers, &options);
+        if let Some(secret) = &state.config.announce.response_signing_secret {
+            sign_response(&mut response, secret);
+        }
+        response
+    };
 
     state.metrics.increment_successful();
+    state.metrics.increment_event(Some(AnnounceEvent::Stopped));
+    bencode_http_response(response)
+}
 
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "text/plain")
-        .body(response.into())
-        .unwrap())
+/// Run the anti-cheat checks for an announce. Violations are logged, never rejected.
+fn run_anti_cheat_checks(
+    state: &AppState,
+    user_id: u32,
+    torrent: &Torrent,
+    interval: i64,
+    validated: &ValidatedAnnounceParams,
+    existing_peer: Option<&Peer>,
+    current_time: i64,
+) {
+    let torrent_id = torrent.id;
+    let last_announce = existing_peer.map(|p| p.last_announce);
+    if let Err(e) = announce_interval::check_announce_interval(
+        user_id,
+        torrent_id,
+        last_announce,
+        current_time,
+        // A torrent with a short interval override must not flag clients that follow it
+        state.config.performance.min_announce_interval.min(interval / 2),
+    ) {
+        warn!(
+            user_id,
+            torrent_id,
+            error = %e,
+            "Announce interval check failed"
+        );
+        record_violation(&state.violation_counts, user_id, ViolationKind::AnnounceInterval);
+    }
+
+    if let Err(e) = duplicate_peer::check_duplicate_peer(
+        &state.peer_store,
+        user_id,
+        torrent_id,
+        state.config.anti_cheat.max_ips_per_user,
+        state.config.anti_cheat.dedupe_ips_by_key,
+    ) {
+        warn!(
+            user_id,
+            torrent_id,
+            error = %e,
+            "Duplicate peer check failed"
+        );
+        record_violation(&state.violation_counts, user_id, ViolationKind::DuplicatePeer);
+    }
+
+    let max_peer_ids = state.config.anti_cheat.max_peer_ids_per_user;
+    if max_peer_ids > 0 {
+        if let Err(e) = duplicate_peer::check_peer_id_count(
+            &state.peer_store,
+            validated.info_hash,
+            user_id,
+            torrent_id,
+            max_peer_ids,
+        ) {
+            warn!(
+                user_id,
+                torrent_id,
+                error = %e,
+                "Peer ID count check failed"
+            );
+            record_violation(&state.violation_counts, user_id, ViolationKind::PeerIdCount);
+        }
+    }
+
+    if let Some(old_peer) = existing_peer {
+        let elapsed = elapsed_seconds(old_peer.last_announce, current_time);
+        if let Err(e) = speed_check::check_speed(
+            user_id,
+            torrent_id,
+            old_peer.uploaded,
+            validated.uploaded,
+            old_peer.downloaded,
+            validated.downloaded,
+            elapsed,
+            state.config.anti_cheat.max_upload_speed,
+        ) {
+            warn!(
+                user_id,
+                torrent_id,
+                error = %e,
+                "Speed check failed"
+            );
+            record_violation(&state.violation_counts, user_id, ViolationKind::Speed);
+        }
+    }
+
+    if let Err(e) = ratio_check::check_ratio(
+        user_id,
+        torrent_id,
+        validated.uploaded,
+        validated.downloaded,
+        state.config.anti_cheat.max_ratio,
+    ) {
+        warn!(
+            user_id,
+            torrent_id,
+            error = %e,
+            "Ratio check failed"
+        );
+        record_violation(&state.violation_counts, user_id, ViolationKind::Ratio);
+    }
+
+    let is_seeder = validated.left == 0;
+    let is_completed_event = validated.event == Some(AnnounceEvent::Completed);
+    if let Err(e) = ghost_seeder::check_ghost_seeder(
+        user_id,
+        torrent_id,
+        is_seeder,
+        validated.uploaded,
+        state.config.anti_cheat.min_seeder_upload,
+        is_completed_event,
+        state.config.anti_cheat.skip_ghost_seeder_on_freeleech && torrent.is_freeleech,
+    ) {
+        warn!(
+            user_id,
+            torrent_id,
+            error = %e,
+            "Ghost seeder check failed"
+        );
+        record_violation(&state.violation_counts, user_id, ViolationKind::GhostSeeder);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Config;
+    use crate::utils::auth::sign_passkey;
+    use crate::wal::wal::Wal;
+    use axum::extract::RawQuery;
+    use axum::http::StatusCode;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::atomic::Ordering;
+    use tempfile::TempDir;
+
+    fn create_test_state() -> Arc<AppState> {
+        create_test_state_with(Config::for_tests())
+    }
+
+    fn create_test_state_with(config: Config) -> Arc<AppState> {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let wal = Wal::new(wal_path).unwrap();
+
+        Arc::new(AppState::new(config, wal))
+    }
+
+    /// State with user 1 (passkey "a" x 32) and torrent 1 (info_hash "A" x 20) registered
+    fn create_swarm_state() -> Arc<AppState> {
+        create_swarm_state_with(Config::for_tests())
+    }
+
+    fn create_swarm_state_with(config: Config) -> Arc<AppState> {
+        let state = create_test_state_with(config);
+        state.user_cache.load().add_user(User::new(1, [b'a'; 32], 0, true)).unwrap();
+        state.torrent_cache.load().add_torrent(Torrent::new(1, [0x41; 20], false, true)).unwrap();
+        state
+    }
+
+    fn client_addr() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 6881)
+    }
+
+    /// Announce query for the swarm state: user 1 on torrent 1 as peer "B" x 20
+    ///
+    /// Each id is a single character repeated to the field's length.
+    #[derive(Clone)]
+    struct TestQuery {
+        passkey: String,
+        info_hash: u8,
+        peer_id: u8,
+        left: u64,
+        event: Option<&'static str>,
+        params: Vec<&'static str>,
+    }
+
+    impl TestQuery {
+        fn new() -> Self {
+            Self {
+                passkey: "a".repeat(32),
+                info_hash: b'A',
+                peer_id: b'B',
+                left: 0,
+                event: None,
+                params: Vec::new(),
+            }
+        }
+
+        fn passkey(mut self, passkey: u8) -> Self {
+            self.passkey = (passkey as char).to_string().repeat(32);
+            self
+        }
+
+        /// Send a signed passkey token instead of a stored passkey
+        fn signed_passkey(mut self, token: String) -> Self {
+            self.passkey = token;
+            self
+        }
+
+        fn info_hash(mut self, info_hash: u8) -> Self {
+            self.info_hash = info_hash;
+            self
+        }
+
+        fn peer_id(mut self, peer_id: u8) -> Self {
+            self.peer_id = peer_id;
+            self
+        }
+
+        fn left(mut self, left: u64) -> Self {
+            self.left = left;
+            self
+        }
+
+        fn event(mut self, event: &'static str) -> Self {
+            self.event = Some(event);
+            self
+        }
+
+        /// Extra `key=value` pairs appended as given
+        fn param(mut self, param: &'static str) -> Self {
+            self.params.push(param);
+            self
+        }
+
+        fn build(&self) -> String {
+            let repeat = |c: u8, len: usize| (c as char).to_string().repeat(len);
+            let mut query = format!(
+                "passkey={}&info_hash={}&peer_id={}&port=6881&uploaded=0&downloaded=0&left={}",
+                self.passkey,
+                repeat(self.info_hash, 20),
+                repeat(self.peer_id, 20),
+                self.left,
+            );
+            if let Some(event) = self.event {
+                query.push_str("&event=");
+                query.push_str(event);
+            }
+            for param in &self.params {
+                query.push('&');
+                query.push_str(param);
+            }
+            query
+        }
+    }
+
+    async fn send_raw(state: &Arc<AppState>, query: Option<String>) -> Result<Response, AnnounceError> {
+        announce_handler(State(Arc::clone(state)), RawQuery(query), HeaderMap::new(), ConnectInfo(client_addr())).await
+    }
+
+    async fn send(state: &Arc<AppState>, query: TestQuery) -> Result<Response, AnnounceError> {
+        send_raw(state, Some(query.build())).await
+    }
+
+    async fn send_from(
+        state: &Arc<AppState>,
+        query: TestQuery,
+        headers: HeaderMap,
+        addr: SocketAddr,
+    ) -> Result<Response, AnnounceError> {
+        announce_handler(State(Arc::clone(state)), RawQuery(Some(query.build())), headers, ConnectInfo(addr)).await
+    }
+
+    async fn body_of(response: Response) -> String {
+        let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    async fn announce(state: &Arc<AppState>, left: u64, event: &'static str) {
+        let result = send(state, TestQuery::new().left(left).event(event)).await;
+        assert!(result.is_ok());
+    }
+
+    async fn announce_stopped(state: &Arc<AppState>) -> String {
+        let response = send(state, TestQuery::new().event("stopped").param("compact=1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        body_of(response).await
+    }
+
+    #[tokio::test]
+    async fn test_announce_with_signed_passkey() {
+        let mut config = Config::for_tests();
+        config.auth.passkey_mode = "signed".to_string();
+        config.auth.passkey_secret = Some("secret".to_string());
+        let state = create_test_state_with(config);
+        state.torrent_cache.load().add_torrent(Torrent::new(1, [0x41; 20], false, true)).unwrap();
+
+        let token = sign_passkey(42, current_timestamp() + 3600, "secret");
+        let response = send(&state, TestQuery::new().signed_passkey(token.clone()).left(100)).await.unwrap();
+        assert!(!body_of(response).await.contains("failure reason"));
+        assert_eq!(state.peer_store.get_peer([0x41; 20], [b'B'; 20]).unwrap().user_id, 42);
+
+        // Any change to the token breaks its signature
+        let mut tampered = token.clone();
+        let last = if tampered.pop() == Some('0') { '1' } else { '0' };
+        tampered.push(last);
+        let result = send(&state, TestQuery::new().signed_passkey(tampered).peer_id(b'C')).await;
+        assert!(matches!(result, Err(AnnounceError::InvalidPasskey)));
+
+        // So does signing for another user
+        let forged = token.replacen("42.", "43.", 1);
+        let result = send(&state, TestQuery::new().signed_passkey(forged).peer_id(b'C')).await;
+        assert!(matches!(result, Err(AnnounceError::InvalidPasskey)));
+        assert!(state.peer_store.get_peer([0x41; 20], [b'C'; 20]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_announce_rejects_oversized_query() {
+        let state = create_test_state();
+        let query = format!("passkey={}&info_hash=", "a".repeat(4096));
+
+        let result = send_raw(&state, Some(query)).await;
+
+        assert!(matches!(result, Err(AnnounceError::InvalidParameter(_))));
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(state.metrics.total_announces.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_announce_without_browser_responses() {
+        let mut config = Config::for_tests();
+        config.server.browser_responses = false;
+        let state = create_test_state_with(config);
+
+        let result = send_raw(&state, None).await;
+        assert!(matches!(result, Err(AnnounceError::MissingParameter(name)) if name == "passkey"));
+
+        let result = send_raw(&state, Some(format!("passkey={}", "a".repeat(32)))).await;
+        assert!(matches!(result, Err(AnnounceError::MissingParameter(name)) if name == "info_hash"));
+    }
+
+    #[tokio::test]
+    async fn test_announce_browser_and_missing_parameter_classification() {
+        let state = create_swarm_state();
+
+        let passkey = format!("passkey={}", "a".repeat(32));
+        let info_hash = format!("info_hash={}", "A".repeat(20));
+        let peer_id = format!("peer_id={}", "B".repeat(20));
+        let rest = "port=6881&uploaded=0&downloaded=0&left=0";
+
+        // Totally empty query, with or without the question mark
+        assert!(matches!(send_raw(&state, None).await, Err(AnnounceError::BrowserAccess)));
+        assert!(matches!(send_raw(&state, Some(String::new())).await, Err(AnnounceError::BrowserAccess)));
+
+        // Only the passkey: the announce URL pasted into a browser
+        assert!(matches!(send_raw(&state, Some(passkey.clone())).await, Err(AnnounceError::BrowserAccess)));
+
+        // A client that lost the passkey
+        let result = send_raw(&state, Some(format!("{}&{}&{}", info_hash, peer_id, rest))).await;
+        assert!(matches!(result, Err(AnnounceError::MissingParameter(name)) if name == "passkey"));
+
+        // Only info_hash
+        let result = send_raw(&state, Some(info_hash.clone())).await;
+        assert!(matches!(result, Err(AnnounceError::MissingParameter(name)) if name == "passkey"));
+
+        // Missing peer_id
+        let result = send_raw(&state, Some(format!("{}&{}&{}", passkey, info_hash, rest))).await;
+        assert!(matches!(result, Err(AnnounceError::MissingParameter(name)) if name == "peer_id"));
+
+        // Missing info_hash
+        let result = send_raw(&state, Some(format!("{}&{}&{}", passkey, peer_id, rest))).await;
+        assert!(matches!(result, Err(AnnounceError::MissingParameter(name)) if name == "info_hash"));
+
+        // None of the above count as announces
+        assert_eq!(state.metrics.total_announces.load(Ordering::Relaxed), 0);
+        assert_eq!(state.metrics.failed_announces.load(Ordering::Relaxed), 7);
+
+        // A full valid query goes through
+        assert!(send(&state, TestQuery::new()).await.is_ok());
+        assert_eq!(state.peer_store.total_peers(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_announce_rejects_non_routable_ip() {
+        let mut config = Config::for_tests();
+        config.security.reject_non_routable = true;
+        let state = create_swarm_state_with(config);
+        let addr = SocketAddr::new("fe80::1".parse().unwrap(), 6881);
+
+        let result = send_from(&state, TestQuery::new(), HeaderMap::new(), addr).await;
+
+        assert!(matches!(result, Err(AnnounceError::NonRoutableIp)));
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(state.peer_store.total_peers(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_announce_content_type_applies_to_success_and_failure() {
+        use axum::{body::Body, http::Request};
+        use tower::Service;
+
+        let mut config = Config::for_tests();
+        config.announce.content_type = "application/x-bittorrent".to_string();
+        let state = create_swarm_state_with(config);
+
+        let mut router = crate::core::routes::build_router(state);
+        let mut call = |uri: String| {
+            let mut request = Request::get(uri).body(Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(client_addr()));
+            router.call(request)
+        };
+
+        let response = call(format!("/announce?{}", TestQuery::new().build())).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-bittorrent");
+
+        // Bencoded failures carry the same content type
+        let response = call(format!("/announce?{}", TestQuery::new().info_hash(b'Z').build())).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-bittorrent");
+
+        // The plain text browser message is left alone
+        let response = call("/announce".to_string()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+    }
+
+    #[tokio::test]
+    async fn test_announce_caches_rejected_passkey_until_user_added() {
+        use crate::handlers::admin::user_add_handler;
+        use crate::models::admin::UserAddQuery;
+        use axum::extract::Query;
+
+        let state = create_swarm_state();
+        let query = TestQuery::new().passkey(b'c');
+
+        let result = send(&state, query.clone()).await;
+        assert!(matches!(result, Err(AnnounceError::InvalidPasskey)));
+        assert_eq!(state.rejected_passkeys.len(), 1);
+
+        // A user appearing behind the cache's back is not seen until the rejection expires
+        state.user_cache.load().add_user(User::new(2, [b'c'; 32], 0, true)).unwrap();
+        let result = send(&state, query.clone()).await;
+        assert!(matches!(result, Err(AnnounceError::InvalidPasskey)));
+
+        // Adding the user through the admin API invalidates the cached rejection
+        let params = UserAddQuery {
+            id: 2,
+            passkey: hex::encode([b'c'; 32]),
+            class: 0,
+        };
+        user_add_handler(State(Arc::clone(&state)), Query(params)).await.unwrap();
+        assert!(send(&state, query).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_announce_loads_unknown_passkey_from_api() {
+        use axum::{routing::get, Router};
+        use std::sync::atomic::AtomicUsize;
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&lookups);
+        let app = Router::new().route(
+            "/api",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+                r#"{"torrents":[],"users":[{"id":2,"passkey":"cccccccccccccccccccccccccccccccc","user_class_id":0,"can_download":true}]}"#
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut config = Config::for_tests();
+        config.sync.data_endpoint = endpoint;
+        config.sync.lazy_user_lookups_per_second = 1;
+        let state = create_swarm_state_with(config);
+
+        assert!(send(&state, TestQuery::new().passkey(b'c')).await.is_ok());
+        assert_eq!(state.user_cache.load().get_user([b'c'; 32]).unwrap().id, 2);
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+
+        // The single lookup per second is spent, so this passkey fails without a request
+        let result = send(&state, TestQuery::new().passkey(b'd')).await;
+        assert!(matches!(result, Err(AnnounceError::InvalidPasskey)));
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_announce_skips_user_lookup_when_only_torrent_lookups_enabled() {
+        use axum::{routing::get, Router};
+        use std::sync::atomic::AtomicUsize;
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&lookups);
+        let app = Router::new().route(
+            "/api",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+                r#"{"torrents":[],"users":[{"id":2,"passkey":"cccccccccccccccccccccccccccccccc","user_class_id":0,"can_download":true}]}"#
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut config = Config::for_tests();
+        config.sync.data_endpoint = endpoint;
+        config.sync.lazy_user_lookups_per_second = 0;
+        config.sync.lazy_torrent_lookups_per_second = 100;
+        let state = create_swarm_state_with(config);
+
+        let result = send(&state, TestQuery::new().passkey(b'c')).await;
+        assert!(matches!(result, Err(AnnounceError::InvalidPasskey)));
+        assert_eq!(lookups.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_announce_loads_unknown_torrent_from_api() {
+        use axum::{routing::get, Router};
+        use std::sync::atomic::AtomicUsize;
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&lookups);
+        let app = Router::new().route(
+            "/api",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+                r#"{"torrents":[{"id":7,"info_hash":"4141414141414141414141414141414141414141","is_freeleech":false}],"users":[]}"#
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut config = Config::for_tests();
+        config.sync.data_endpoint = endpoint;
+        config.sync.lazy_torrent_lookups_per_second = 100;
+        let state = create_test_state_with(config);
+        state.user_cache.load().add_user(User::new(1, [b'a'; 32], 0, true)).unwrap();
+
+        assert!(send(&state, TestQuery::new()).await.is_ok());
+        assert_eq!(state.torrent_cache.load().get_torrent([0x41; 20]).unwrap().id, 7);
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+
+        // An info_hash the API does not know is only looked up once within the TTL
+        let unknown = TestQuery::new().info_hash(b'Z');
+        assert!(matches!(send(&state, unknown.clone()).await, Err(AnnounceError::TorrentNotFound)));
+        assert!(matches!(send(&state, unknown).await, Err(AnnounceError::TorrentNotFound)));
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_announce_rejects_request_body() {
+        let state = create_swarm_state();
+        let with_header = |name, value| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            send_from(&state, TestQuery::new(), headers, client_addr())
+        };
+
+        let result = with_header(header::CONTENT_LENGTH, "5").await;
+        assert!(matches!(result, Err(AnnounceError::SuspiciousClient)));
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 1);
+
+        let result = with_header(header::TRANSFER_ENCODING, "chunked").await;
+        assert!(matches!(result, Err(AnnounceError::SuspiciousClient)));
+
+        // An explicit empty body is harmless
+        let result = with_header(header::CONTENT_LENGTH, "0").await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_announce_stores_declared_ipv6() {
+        let state = create_swarm_state();
+
+        let result = send(&state, TestQuery::new().param("ipv6=2001%3Adb8%3A%3A1").param("ipv4=9.9.9.9")).await;
+        assert!(result.is_ok());
+
+        // The connection address stays primary; only the other family is taken from the params
+        let peer = state.peer_store.get_peer([0x41; 20], [0x42; 20]).unwrap();
+        assert_eq!(peer.ip, client_addr().ip());
+        assert_eq!(peer.alt_ip, Some("2001:db8::1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_announce_normalizes_ipv4_mapped_address() {
+        let state = create_swarm_state();
+        let query = TestQuery::new().param("compact=1");
+        let mapped: IpAddr = "::ffff:10.0.0.1".parse().unwrap();
+        let addr = SocketAddr::new(mapped, 6881);
+
+        let result = send_from(&state, query.clone(), HeaderMap::new(), addr).await;
+        assert!(result.is_ok());
+
+        let peer = state.peer_store.get_peer([0x41; 20], [0x42; 20]).unwrap();
+        assert_eq!(peer.ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+
+        // A ban on the IPv4 form applies to the mapped connection
+        state.ip_blacklist.ban(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), None);
+        let result = send_from(&state, query, HeaderMap::new(), addr).await;
+        assert!(matches!(result, Err(AnnounceError::IpBanned)));
+    }
+
+    #[tokio::test]
+    async fn test_announce_rejects_banned_user() {
+        let state = create_swarm_state();
+        state.user_blacklist.ban(1);
+
+        let result = send(&state, TestQuery::new()).await;
+        assert!(matches!(result, Err(AnnounceError::UserBanned)));
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 1);
+
+        state.user_blacklist.unban(1);
+        assert!(send(&state, TestQuery::new()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_announce_rejects_empty_user_agent() {
+        let mut config = Config::for_tests();
+        config.security.reject_empty_user_agent = true;
+        let state = create_swarm_state_with(config);
+
+        let mut blank = HeaderMap::new();
+        blank.insert("user-agent", "  ".parse().unwrap());
+        for headers in [HeaderMap::new(), blank] {
+            let result = send_from(&state, TestQuery::new(), headers, client_addr()).await;
+            assert!(matches!(result, Err(AnnounceError::SuspiciousClient)));
+        }
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 2);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", "qBittorrent/4.6.0".parse().unwrap());
+        let result = send_from(&state, TestQuery::new(), headers, client_addr()).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stats_only_announce_keeps_peer_alive() {
+        let mut config = Config::for_tests();
+        config.announce.skip_anti_cheat_on_stats_only = true;
+        let state = create_swarm_state_with(config);
+
+        let stale_peer = Peer::new(
+            1,
+            1,
+            [0x42; 20],
+            client_addr().ip(),
+            6881,
+            0,
+            0,
+            0,
+            current_timestamp() - 3000,
+            String::new(),
+        );
+        state.peer_store.add_peer([0x41; 20], stale_peer).unwrap();
+
+        let result = send(&state, TestQuery::new().param("numwant=0")).await;
+
+        assert!(result.is_ok());
+        assert_eq!(state.metrics.successful_announces.load(Ordering::Relaxed), 1);
+
+        // The refreshed peer must survive a cleanup that would have reaped the stale entry
+        assert_eq!(state.peer_store.cleanup_stale_peers(60, |_| None), 0);
+        assert_eq!(state.peer_store.total_peers(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_announce_rejects_banned_non_utf8_user_agent() {
+        let state = create_swarm_state();
+        state.client_blacklist.ban("BadClient".to_string(), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_bytes(b"BadClient/1.0 \xff").unwrap());
+
+        let result = send_from(&state, TestQuery::new(), headers, client_addr()).await;
+
+        assert!(matches!(result, Err(AnnounceError::ClientBanned)));
+        assert_eq!(state.peer_store.total_peers(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_started_while_complete_counts_as_seeder() {
+        let state = create_swarm_state();
+
+        announce(&state, 0, "started").await;
+
+        assert_eq!(state.peer_store.get_stats([0x41; 20]), (1, 0));
+        assert_eq!(state.peer_store.take_completed([0x41; 20]), 0);
+    }
+
+    #[tokio::test]
+    async fn test_completed_after_leeching_is_counted() {
+        let state = create_swarm_state();
+
+        announce(&state, 1000, "started").await;
+        announce(&state, 0, "completed").await;
+
+        assert_eq!(state.peer_store.get_stats([0x41; 20]), (1, 0));
+        assert_eq!(state.peer_store.take_completed([0x41; 20]), 1);
+    }
+
+    #[tokio::test]
+    async fn test_completed_after_starting_complete_is_not_counted() {
+        let state = create_swarm_state();
+
+        announce(&state, 0, "started").await;
+        announce(&state, 0, "completed").await;
+
+        assert_eq!(state.peer_store.get_stats([0x41; 20]), (1, 0));
+        assert_eq!(state.peer_store.take_completed([0x41; 20]), 0);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_sequence_event_blocked() {
+        let mut config = Config::for_tests();
+        config.anti_cheat.event_sequence_policy = "block".to_string();
+        let state = create_swarm_state_with(config);
+
+        // completed from a peer the tracker never saw
+        let result = send(&state, TestQuery::new().event("completed")).await;
+
+        assert!(matches!(result, Err(AnnounceError::InvalidEventSequence)));
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(state.peer_store.total_peers(), 0);
+        assert_eq!(state.peer_store.take_completed([0x41; 20]), 0);
+    }
+
+    #[tokio::test]
+    async fn test_left_exceeding_size_blocked() {
+        let mut config = Config::for_tests();
+        config.anti_cheat.size_sanity_policy = "block".to_string();
+        let state = create_test_state_with(config);
+        state.user_cache.load().add_user(User::new(1, [b'a'; 32], 0, true)).unwrap();
+        state.torrent_cache.load().add_torrent(Torrent::new(1, [0x41; 20], false, true).with_size(1000)).unwrap();
+
+        let result = send(&state, TestQuery::new().left(5000).event("started")).await;
+
+        assert!(matches!(result, Err(AnnounceError::LeftExceedsSize)));
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(state.peer_store.total_peers(), 0);
+
+        // Within the torrent size the announce goes through
+        announce(&state, 1000, "started").await;
+        assert_eq!(state.peer_store.total_peers(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_sequence_event_warn_only_by_default() {
+        let state = create_swarm_state();
+
+        announce(&state, 1000, "started").await;
+        announce(&state, 1000, "started").await;
+
+        assert_eq!(state.peer_store.total_peers(), 1);
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_torrent_announce_interval_override() {
+        let state = create_swarm_state();
+        state.torrent_cache.load().add_torrent(
+            Torrent::new(2, [0x43; 20], false, true).with_announce_interval(Some(600)),
+        ).unwrap();
+
+        // Falls back to announce.interval
+        let body = body_of(send(&state, TestQuery::new().left(100)).await.unwrap()).await;
+        assert!(body.contains("8:intervali1800e12:min intervali900e"));
+
+        let body = body_of(send(&state, TestQuery::new().info_hash(b'C').left(100)).await.unwrap()).await;
+        assert!(body.contains("8:intervali600e12:min intervali300e"));
+    }
+
+    #[test]
+    fn test_swarm_interval_scales_within_band() {
+        let config = AnnounceConfig {
+            scaled_interval_min: 900,
+            scaled_interval_max: 3600,
+            scaled_interval_swarm_size: 1000,
+            ..AnnounceConfig::default()
+        };
+
+        assert_eq!(swarm_interval(&config, 0), 900);
+        assert_eq!(swarm_interval(&config, 500), 2250);
+        assert_eq!(swarm_interval(&config, 1000), 3600);
+        assert_eq!(swarm_interval(&config, 50_000), 3600);
+    }
+
+    #[tokio::test]
+    async fn test_scaled_interval_used_without_override() {
+        let mut config = Config::for_tests();
+        config.announce.scaled_interval_min = 600;
+        config.announce.scaled_interval_max = 3600;
+        config.announce.scaled_interval_swarm_size = 3;
+        let state = create_swarm_state_with(config);
+
+        let state = &state;
+        let body_for = |peer_id: u8| async move {
+            body_of(send(state, TestQuery::new().peer_id(peer_id).left(100)).await.unwrap()).await
+        };
+
+        // Sized by the swarm the response reports, the announcing peer included
+        assert!(body_for(b'B').await.contains("8:completei0e10:incompletei1e8:intervali1600e"));
+        assert!(body_for(b'C').await.contains("8:completei0e10:incompletei2e8:intervali2600e"));
+        assert!(body_for(b'D').await.contains("8:completei0e10:incompletei3e8:intervali3600e"));
+    }
+
+    #[tokio::test]
+    async fn test_inactive_torrent_retirement_response() {
+        let mut config = Config::for_tests();
+        config.announce.retire_inactive_torrents = true;
+        let state = create_test_state_with(config);
+        state.user_cache.load().add_user(User::new(1, [b'a'; 32], 0, true)).unwrap();
+        state.torrent_cache.load().add_torrent(Torrent::new(1, [0x41; 20], false, false)).unwrap();
+
+        let body = body_of(send(&state, TestQuery::new().left(100)).await.unwrap()).await;
+
+        assert!(body.contains("8:intervali604800e"));
+        assert!(body.contains("15:warning message15:Torrent retired"));
+        assert!(!body.contains("failure reason"));
+        assert_eq!(state.peer_store.total_peers(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_exempt_user_bypasses_rate_limit() {
+        let mut config = Config::for_tests();
+        config.performance.max_requests_per_minute = 1;
+        config.security.exempt_user_ids = vec![1];
+        let state = create_swarm_state_with(config);
+        state.user_cache.load().add_user(User::new(2, [b'b'; 32], 0, true)).unwrap();
+
+        for _ in 0..3 {
+            announce(&state, 1000, "started").await;
+        }
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 0);
+
+        // A regular user from another IP is still limited
+        let query = TestQuery::new().passkey(b'b').peer_id(b'C').left(1000);
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 4, 4)), 6881);
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            results.push(send_from(&state, query.clone(), HeaderMap::new(), addr).await);
+        }
+
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(AnnounceError::RateLimitExceeded)));
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_torrent_quota_per_class() {
+        let mut config = Config::for_tests();
+        config.security.torrent_quotas = vec![crate::core::config::TorrentQuota { class: 0, max_torrents: 1 }];
+        let state = create_swarm_state_with(config);
+        // Class 1 has no quota
+        state.user_cache.load().add_user(User::new(2, [b'b'; 32], 1, true)).unwrap();
+        state.torrent_cache.load().add_torrent(Torrent::new(2, [0x43; 20], false, true)).unwrap();
+
+        let join = |passkey: u8, info_hash: u8, peer_id: u8, event: &'static str| {
+            let query = TestQuery::new().passkey(passkey).info_hash(info_hash).peer_id(peer_id).left(100).event(event);
+            send(&state, query)
+        };
+
+        assert!(join(b'a', b'A', b'B', "started").await.is_ok());
+        // A second client in the same torrent is fine
+        assert!(join(b'a', b'A', b'C', "started").await.is_ok());
+
+        let result = join(b'a', b'C', b'D', "started").await;
+        assert!(matches!(&result, Err(AnnounceError::QuotaExceeded(message)) if message.contains("1 torrents")));
+        assert_eq!(state.metrics.quota_rejections.load(Ordering::Relaxed), 1);
+        let body = body_of(axum::response::IntoResponse::into_response(result.unwrap_err())).await;
+        assert!(body.starts_with("d14:failure reason"));
+
+        assert!(join(b'b', b'A', b'E', "started").await.is_ok());
+        assert!(join(b'b', b'C', b'F', "started").await.is_ok());
+
+        // Leaving the first torrent frees the slot
+        assert!(join(b'a', b'A', b'B', "stopped").await.is_ok());
+        assert!(join(b'a', b'A', b'C', "stopped").await.is_ok());
+        assert!(join(b'a', b'C', b'D', "started").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_global_rate_cap_sheds_announces() {
+        let mut config = Config::for_tests();
+        config.performance.global_announce_rate = 1;
+        let state = create_swarm_state_with(config);
+
+        announce(&state, 1000, "started").await;
+
+        let query = TestQuery::new().peer_id(b'C').left(1000).param("compact=1");
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 4, 4)), 6881);
+        let response = send_from(&state, query, HeaderMap::new(), addr).await.unwrap();
+        let body = body_of(response).await;
+
+        assert!(body.contains(SHED_WARNING));
+        assert!(body.contains("intervali3600e"));
+        // The shed announce never reached the peer store
+        assert_eq!(state.peer_store.total_peers(), 1);
+        assert!(state.global_limiter.is_shedding(current_timestamp_millis()));
+        assert_eq!(state.metrics.shed_announces.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stopped_removes_peer() {
+        let state = create_swarm_state();
+
+        announce(&state, 1000, "started").await;
+        let body = announce_stopped(&state).await;
+
+        assert!(body.contains("5:peers0:"));
+        assert_eq!(state.peer_store.total_peers(), 0);
+        assert_eq!(state.peer_store.get_stats([0x41; 20]), (0, 0));
+        assert_eq!(state.metrics.successful_announces.load(Ordering::Relaxed), 2);
+        assert_eq!(state.metrics.started_count.load(Ordering::Relaxed), 1);
+        assert_eq!(state.metrics.stopped_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stopped_empty_response() {
+        let mut config = Config::for_tests();
+        config.announce.empty_stopped_response = true;
+        let state = create_swarm_state_with(config);
+
+        announce(&state, 1000, "started").await;
+        let body = announce_stopped(&state).await;
+
+        assert!(body.is_empty());
+        assert_eq!(state.peer_store.total_peers(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stopped_skips_anti_cheat() {
+        let mut config = Config::for_tests();
+        config.anti_cheat.event_sequence_policy = "block".to_string();
+        let state = create_swarm_state_with(config);
+
+        // Stopping immediately after starting would trip the interval check if it ran
+        announce(&state, 1000, "started").await;
+        announce_stopped(&state).await;
+        // A stopped from a peer that is already gone is still a plain success
+        announce_stopped(&state).await;
+
+        assert_eq!(state.peer_store.total_peers(), 0);
+        assert_eq!(state.metrics.total_announces.load(Ordering::Relaxed), 3);
+        assert_eq!(state.metrics.successful_announces.load(Ordering::Relaxed), 3);
+        assert_eq!(state.metrics.failed_announces.load(Ordering::Relaxed), 0);
+        assert_eq!(state.metrics.blocked_requests.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_inactive_torrent_fails_by_default() {
+        let state = create_test_state();
+        state.user_cache.load().add_user(User::new(1, [b'a'; 32], 0, true)).unwrap();
+        state.torrent_cache.load().add_torrent(Torrent::new(1, [0x41; 20], false, false)).unwrap();
+
+        let result = send(&state, TestQuery::new().left(100)).await;
+
+        assert!(matches!(result, Err(AnnounceError::TorrentInactive)));
+    }
+
+    #[test]
+    fn test_announce_log_sampling() {
+        let mut config = Config::for_tests();
+        config.logging.announce_log_sample_rate = 3;
+        let state = create_test_state_with(config);
+
+        let sampled: Vec<bool> = (0..6).map(|_| sample_announce_log(&state)).collect();
+        assert_eq!(sampled, [true, false, false, true, false, false]);
+
+        // The default rate of 1 logs every announce without touching the counter
+        let state = create_test_state();
+        assert!((0..3).all(|_| sample_announce_log(&state)));
+        assert_eq!(state.announce_log_counter.load(Ordering::Relaxed), 0);
+    }
 }
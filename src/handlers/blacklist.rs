@@ -1,10 +1,11 @@
 use crate::core::error::BlacklistError;
 use crate::models::admin::{
     ClientBanQuery, ClientListResponse, IpBanQuery, IpListResponse,
-    SuccessResponse,
+    RateLimitResetQuery, SuccessResponse, UserBanQuery,
 };
 use crate::core::state::AppState;
-use crate::utils::auth::verify_api_key;
+use crate::utils::time::current_timestamp;
+use crate::wal::wal::WalOperation;
 use axum::{
     extract::{Query, State},
     http::StatusCode,
@@ -13,24 +14,33 @@ use axum::{
 use std::sync::Arc;
 use tracing::{info, warn};
 
+/// Convert an optional `ttl` query parameter into a ban expiry timestamp
+fn ban_expiry(ttl: Option<i64>) -> Result<Option<i64>, BlacklistError> {
+    match ttl {
+        Some(ttl) if ttl <= 0 => Err(BlacklistError::InvalidParameter(format!(
+            "ttl must be a positive number of seconds, got {}",
+            ttl
+        ))),
+        Some(ttl) => Ok(Some(current_timestamp().saturating_add(ttl))),
+        None => Ok(None),
+    }
+}
+
 /// Ban an IP address
+///
+/// With `ttl`, the ban expires after that many seconds.
 pub async fn ip_ban_handler(
     State(state): State<Arc<AppState>>,
     Query(params): Query<IpBanQuery>,
 ) -> Result<Response, BlacklistError> {
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized IP ban attempt");
-        return Err(BlacklistError::InvalidApiKey);
-    }
-
-
     let ip = params.ip.parse()
         .map_err(|e| BlacklistError::InvalidIpAddress(format!("{}: {}", params.ip, e)))?;
 
+    let expires_at = ban_expiry(params.ttl)?;
 
-    state.ip_blacklist.ban(ip);
+    state.ip_blacklist.ban(ip, expires_at);
 
-    info!(ip = %params.ip, "IP address banned");
+    info!(ip = %params.ip, ttl = ?params.ttl, "IP address banned");
 
     Ok((
         StatusCode::OK,
@@ -47,13 +57,6 @@ pub async fn ip_unban_handler(
     State(state): State<Arc<AppState>>,
     Query(params): Query<IpBanQuery>,
 ) -> Result<Response, BlacklistError> {
-    // Verify API key
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized IP unban attempt");
-        return Err(BlacklistError::InvalidApiKey);
-    }
-
-
     let ip = params.ip.parse()
         .map_err(|e| BlacklistError::InvalidIpAddress(format!("{}: {}", params.ip, e)))?;
 
@@ -75,14 +78,7 @@ pub async fn ip_unban_handler(
 /// List all banned IP addresses
 pub async fn ip_list_handler(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<crate::models::admin::ApiKeyQuery>,
 ) -> Result<Response, BlacklistError> {
-    // Verify API key
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized IP list attempt");
-        return Err(BlacklistError::InvalidApiKey);
-    }
-
     // Get all banned IPs
     let ipv4 = state
         .ip_blacklist
@@ -108,19 +104,75 @@ pub async fn ip_list_handler(
         .into_response())
 }
 
+/// Reset the rate limiter for an IP address, or for all IPs if none is given
+///
+/// Returns 404 if the IP had no active rate limit window.
+pub async fn ratelimit_reset_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<RateLimitResetQuery>,
+) -> Result<Response, BlacklistError> {
+    let Some(ip_str) = params.ip else {
+        let cleared = state.rate_limiter.reset_all();
+
+        info!(cleared = cleared, "Rate limiter reset for all IPs");
+
+        return Ok((
+            StatusCode::OK,
+            Json(SuccessResponse {
+                success: true,
+                message: format!("Rate limit reset for {} IP addresses", cleared),
+            }),
+        )
+            .into_response());
+    };
+
+    let ip = ip_str.parse()
+        .map_err(|e| BlacklistError::InvalidIpAddress(format!("{}: {}", ip_str, e)))?;
+
+    if !state.rate_limiter.reset(ip) {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(SuccessResponse {
+                success: false,
+                message: "IP address had no active rate limit window".to_string(),
+            }),
+        )
+            .into_response());
+    }
+
+    info!(ip = %ip_str, "Rate limiter reset for IP address");
+
+    Ok((
+        StatusCode::OK,
+        Json(SuccessResponse {
+            success: true,
+            message: "Rate limit reset successfully".to_string(),
+        }),
+    )
+        .into_response())
+}
+
 /// Ban a BitTorrent client
+///
+/// With `version`, only User-Agents of that client within the version constraint are banned.
+/// With `ttl`, the ban expires after that many seconds.
 pub async fn client_ban_handler(
     State(state): State<Arc<AppState>>,
     Query(params): Query<ClientBanQuery>,
 ) -> Result<Response, BlacklistError> {
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized client ban attempt");
-        return Err(BlacklistError::InvalidApiKey);
+    let expires_at = ban_expiry(params.ttl)?;
+
+    match &params.version {
+        Some(version) => {
+            state
+                .client_blacklist
+                .ban_version(params.client.clone(), version, expires_at)
+                .map_err(|e| BlacklistError::InvalidParameter(e.to_string()))?;
+        }
+        None => state.client_blacklist.ban(params.client.clone(), expires_at),
     }
 
-    state.client_blacklist.ban(params.client.clone());
-
-    info!(client = %params.client, "Client banned");
+    info!(client = %params.client, version = ?params.version, ttl = ?params.ttl, "Client banned");
 
     Ok((
         StatusCode::OK,
@@ -137,15 +189,13 @@ pub async fn client_unban_handler(
     State(state): State<Arc<AppState>>,
     Query(params): Query<ClientBanQuery>,
 ) -> Result<Response, BlacklistError> {
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized client unban attempt");
-        return Err(BlacklistError::InvalidApiKey);
-    }
-
     // Unban the client
-    state.client_blacklist.unban(&params.client);
+    match &params.version {
+        Some(version) => state.client_blacklist.unban_version(&params.client, version),
+        None => state.client_blacklist.unban(&params.client),
+    }
 
-    info!(client = %params.client, "Client unbanned");
+    info!(client = %params.client, version = ?params.version, "Client unbanned");
 
     Ok((
         StatusCode::OK,
@@ -160,13 +210,7 @@ pub async fn client_unban_handler(
 /// List all banned clients
 pub async fn client_list_handler(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<crate::models::admin::ApiKeyQuery>,
 ) -> Result<Response, BlacklistError> {
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized client list attempt");
-        return Err(BlacklistError::InvalidApiKey);
-    }
-
     // Get all banned clients
     let clients = state.client_blacklist.list();
 
@@ -180,64 +224,100 @@ pub async fn client_list_handler(
         .into_response())
 }
 
+/// Resolve a user ban query to a user ID, looking passkeys up in the user cache
+fn resolve_user_id(state: &AppState, params: &UserBanQuery) -> Result<u32, BlacklistError> {
+    match (params.id, params.passkey.as_deref()) {
+        (Some(id), None) => Ok(id),
+        (None, Some(passkey)) => {
+            let passkey: [u8; 32] = passkey.as_bytes().try_into().map_err(|_| {
+                BlacklistError::InvalidParameter("passkey must be 32 characters".to_string())
+            })?;
+            state
+                .user_cache
+                .load()
+                .get_user(passkey)
+                .map(|user| user.id)
+                .ok_or_else(|| BlacklistError::InvalidParameter("No user with that passkey".to_string()))
+        }
+        _ => Err(BlacklistError::InvalidParameter(
+            "Exactly one of id or passkey must be given".to_string(),
+        )),
+    }
+}
+
+/// Ban a user by ID or passkey
+///
+/// GET /user/ban?id=<id>
+/// GET /user/ban?passkey=<passkey>
+///
+/// The user stays in the cache but their announces fail with "User is banned". The
+/// ban is persisted in the WAL and survives reloads.
+pub async fn user_ban_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<UserBanQuery>,
+) -> Result<Response, BlacklistError> {
+    let user_id = resolve_user_id(&state, &params)?;
+
+    state.user_blacklist.ban(user_id);
+
+    if let Err(e) = state.wal.log_operation(WalOperation::BanUser { id: user_id }) {
+        warn!(error = %e, "Failed to log user ban to WAL");
+        // Continue anyway - the ban is in effect
+    }
+
+    info!(user_id = user_id, "User banned");
+
+    Ok((
+        StatusCode::OK,
+        Json(SuccessResponse {
+            success: true,
+            message: "User banned successfully".to_string(),
+        }),
+    )
+        .into_response())
+}
+
+/// Lift a user ban by ID or passkey
+///
+/// GET /user/unban?id=<id>
+/// GET /user/unban?passkey=<passkey>
+pub async fn user_unban_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<UserBanQuery>,
+) -> Result<Response, BlacklistError> {
+    let user_id = resolve_user_id(&state, &params)?;
+
+    state.user_blacklist.unban(user_id);
+
+    if let Err(e) = state.wal.log_operation(WalOperation::UnbanUser { id: user_id }) {
+        warn!(error = %e, "Failed to log user unban to WAL");
+    }
+
+    info!(user_id = user_id, "User unbanned");
+
+    Ok((
+        StatusCode::OK,
+        Json(SuccessResponse {
+            success: true,
+            message: "User unbanned successfully".to_string(),
+        }),
+    )
+        .into_response())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::config::{
-        AntiCheatConfig, Config, LoggingConfig, MemoryConfig, PerformanceConfig, SecurityConfig,
-        ServerConfig, SyncConfig,
-    };
+    use crate::core::config::Config;
     use crate::wal::wal::Wal;
     use tempfile::TempDir;
 
-    fn create_test_config() -> Config {
-        Config {
-            server: ServerConfig {
-                port: Some(8080),
-                unix_socket: None,
-                num_threads: 4,
-                max_connections: 1000,
-            },
-            memory: MemoryConfig {
-                peer_capacity: 10000,
-                torrent_cache_size: 1000,
-                user_cache_size: 1000,
-            },
-            performance: PerformanceConfig {
-                min_announce_interval: 1800,
-                max_requests_per_minute: 60,
-                cleanup_interval: 300,
-                peer_timeout: 3600,
-            },
-            sync: SyncConfig {
-                data_endpoint: "http://localhost:8000/api".to_string(),
-                api_key: "test-api-key".to_string(),
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                format: "json".to_string(),
-                path: None,
-                console: true,
-            },
-            anti_cheat: AntiCheatConfig {
-                max_ips_per_user: 3,
-                max_ratio: 10.0,
-                max_upload_speed: 100.0,
-                max_download_speed: 100.0,
-                min_seeder_upload: 1024,
-            },
-            security: SecurityConfig {
-                banned_ips: vec![],
-                banned_clients: vec![],
-            },
-        }
-    }
 
     fn create_test_state() -> Arc<AppState> {
         let temp_dir = TempDir::new().unwrap();
         let wal_path = temp_dir.path().join("test.wal");
         let wal = Wal::new(wal_path).unwrap();
-        let config = create_test_config();
+        let config = Config::for_tests();
 
         Arc::new(AppState::new(config, wal))
     }
@@ -247,8 +327,8 @@ mod tests {
         let state = create_test_state();
 
         let params = IpBanQuery {
-            api_key: "test-api-key".to_string(),
             ip: "192.168.1.1".to_string(),
+            ttl: None,
         };
 
         let response = ip_ban_handler(State(state.clone()), Query(params)).await.unwrap();
@@ -260,17 +340,125 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_ip_ban_invalid_api_key() {
+    async fn test_user_ban_by_id_and_passkey() {
+        let state = create_test_state();
+        state.user_cache.load().add_user(crate::models::user::User::new(7, [b'k'; 32], 0, true)).unwrap();
+
+        let params = UserBanQuery { id: Some(3), passkey: None };
+        user_ban_handler(State(state.clone()), Query(params)).await.unwrap();
+        assert!(state.user_blacklist.is_banned(3));
+
+        let params = UserBanQuery { id: None, passkey: Some("k".repeat(32)) };
+        user_ban_handler(State(state.clone()), Query(params)).await.unwrap();
+        assert!(state.user_blacklist.is_banned(7));
+
+        // Banned users stay in the cache
+        assert!(state.user_cache.load().get_user([b'k'; 32]).is_some());
+
+        let params = UserBanQuery { id: Some(7), passkey: None };
+        user_unban_handler(State(state.clone()), Query(params)).await.unwrap();
+        assert!(!state.user_blacklist.is_banned(7));
+    }
+
+    #[tokio::test]
+    async fn test_user_ban_invalid_query() {
+        let state = create_test_state();
+
+        for params in [
+            UserBanQuery { id: None, passkey: None },
+            UserBanQuery { id: Some(1), passkey: Some("k".repeat(32)) },
+            UserBanQuery { id: None, passkey: Some("unknown".to_string()) },
+            UserBanQuery { id: None, passkey: Some("u".repeat(32)) },
+        ] {
+            let result = user_ban_handler(State(state.clone()), Query(params)).await;
+            assert!(matches!(result, Err(BlacklistError::InvalidParameter(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ip_ban_with_ttl() {
         let state = create_test_state();
 
         let params = IpBanQuery {
-            api_key: "wrong-key".to_string(),
             ip: "192.168.1.1".to_string(),
+            ttl: Some(3600),
         };
 
-        let result = ip_ban_handler(State(state), Query(params)).await;
-        assert!(result.is_err());
-        let response = result.unwrap_err().into_response();
+        let response = ip_ban_handler(State(state.clone()), Query(params)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.ip_blacklist.is_banned("192.168.1.1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_ban_invalid_ttl() {
+        let state = create_test_state();
+
+        let params = IpBanQuery {
+            ip: "192.168.1.1".to_string(),
+            ttl: Some(0),
+        };
+
+        let response = ip_ban_handler(State(state.clone()), Query(params)).await.unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let params = ClientBanQuery {
+            client: "BadClient".to_string(),
+            version: None,
+            ttl: Some(-5),
+        };
+
+        let response = client_ban_handler(State(state.clone()), Query(params)).await.unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(state.ip_blacklist.is_empty());
+        assert!(state.client_blacklist.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ratelimit_reset_ip() {
+        let state = create_test_state();
+        let ip = "192.168.1.1".parse().unwrap();
+        state.rate_limiter.check_and_increment(ip, 1000);
+
+        let params = RateLimitResetQuery {
+            ip: Some("192.168.1.1".to_string()),
+        };
+
+        let response = ratelimit_reset_handler(State(state.clone()), Query(params)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Second reset finds no active window
+        let params = RateLimitResetQuery {
+            ip: Some("192.168.1.1".to_string()),
+        };
+
+        let response = ratelimit_reset_handler(State(state), Query(params)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ratelimit_reset_all() {
+        let state = create_test_state();
+        state.rate_limiter.check_and_increment("192.168.1.1".parse().unwrap(), 1000);
+        state.rate_limiter.check_and_increment("10.0.0.1".parse().unwrap(), 1000);
+
+        let params = RateLimitResetQuery {
+            ip: None,
+        };
+
+        let response = ratelimit_reset_handler(State(state.clone()), Query(params)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(state.rate_limiter.tracked_ips(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ip_ban_invalid_api_key() {
+        use axum::{body::Body, http::Request};
+        use tower::Service;
+
+        let mut router = crate::core::routes::build_router(create_test_state());
+
+        let request = Request::get("/ip/ban?api_key=wrong-key&ip=192.168.1.1").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
@@ -279,8 +467,8 @@ mod tests {
         let state = create_test_state();
 
         let params = IpBanQuery {
-            api_key: "test-api-key".to_string(),
             ip: "invalid-ip".to_string(),
+            ttl: None,
         };
 
         let result = ip_ban_handler(State(state), Query(params)).await;
@@ -295,13 +483,13 @@ mod tests {
 
         // First ban an IP
         let ip = "192.168.1.1".parse().unwrap();
-        state.ip_blacklist.ban(ip);
+        state.ip_blacklist.ban(ip, None);
         assert!(state.ip_blacklist.is_banned(ip));
 
 
         let params = IpBanQuery {
-            api_key: "test-api-key".to_string(),
             ip: "192.168.1.1".to_string(),
+            ttl: None,
         };
 
         let response = ip_unban_handler(State(state.clone()), Query(params)).await.unwrap();
@@ -316,29 +504,23 @@ mod tests {
         let state = create_test_state();
 
 
-        state.ip_blacklist.ban("192.168.1.1".parse().unwrap());
-        state.ip_blacklist.ban("10.0.0.1".parse().unwrap());
-        state.ip_blacklist.ban("2001:db8::1".parse().unwrap());
-
-        let params = crate::models::admin::ApiKeyQuery {
-            api_key: "test-api-key".to_string(),
-        };
+        state.ip_blacklist.ban("192.168.1.1".parse().unwrap(), None);
+        state.ip_blacklist.ban("10.0.0.1".parse().unwrap(), None);
+        state.ip_blacklist.ban("2001:db8::1".parse().unwrap(), None);
 
-        let response = ip_list_handler(State(state), Query(params)).await.unwrap();
+        let response = ip_list_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
 
     #[tokio::test]
     async fn test_ip_list_invalid_api_key() {
-        let state = create_test_state();
+        use axum::{body::Body, http::Request};
+        use tower::Service;
 
-        let params = crate::models::admin::ApiKeyQuery {
-            api_key: "wrong-key".to_string(),
-        };
+        let mut router = crate::core::routes::build_router(create_test_state());
 
-        let result = ip_list_handler(State(state), Query(params)).await;
-        assert!(result.is_err());
-        let response = result.unwrap_err().into_response();
+        let request = Request::get("/ip/list?api_key=wrong-key").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
@@ -347,8 +529,9 @@ mod tests {
         let state = create_test_state();
 
         let params = ClientBanQuery {
-            api_key: "test-api-key".to_string(),
             client: "BadClient".to_string(),
+            version: None,
+            ttl: None,
         };
 
         let response = client_ban_handler(State(state.clone()), Query(params)).await.unwrap();
@@ -359,17 +542,46 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_client_ban_invalid_api_key() {
+    async fn test_client_ban_with_version() {
         let state = create_test_state();
 
         let params = ClientBanQuery {
-            api_key: "wrong-key".to_string(),
-            client: "BadClient".to_string(),
+            client: "Deluge".to_string(),
+            version: Some("<2.0".to_string()),
+            ttl: None,
+        };
+
+        let response = client_ban_handler(State(state.clone()), Query(params)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert!(state.client_blacklist.is_banned("Deluge/1.3.15"));
+        assert!(!state.client_blacklist.is_banned("Deluge/2.1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_client_ban_invalid_version() {
+        let state = create_test_state();
+
+        let params = ClientBanQuery {
+            client: "Deluge".to_string(),
+            version: Some("2.0".to_string()),
+            ttl: None,
         };
 
         let result = client_ban_handler(State(state), Query(params)).await;
-        assert!(result.is_err());
         let response = result.unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_client_ban_invalid_api_key() {
+        use axum::{body::Body, http::Request};
+        use tower::Service;
+
+        let mut router = crate::core::routes::build_router(create_test_state());
+
+        let request = Request::get("/client/ban?api_key=wrong-key&client=BadClient").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
@@ -378,13 +590,14 @@ mod tests {
         let state = create_test_state();
 
         // First ban a client
-        state.client_blacklist.ban("BadClient".to_string());
+        state.client_blacklist.ban("BadClient".to_string(), None);
         assert!(state.client_blacklist.is_banned("BadClient/1.0"));
 
         // Now unban it
         let params = ClientBanQuery {
-            api_key: "test-api-key".to_string(),
             client: "BadClient".to_string(),
+            version: None,
+            ttl: None,
         };
 
         let response = client_unban_handler(State(state.clone()), Query(params)).await.unwrap();
@@ -399,28 +612,22 @@ mod tests {
         let state = create_test_state();
 
         // Ban some clients
-        state.client_blacklist.ban("BadClient1".to_string());
-        state.client_blacklist.ban("BadClient2".to_string());
-
-        let params = crate::models::admin::ApiKeyQuery {
-            api_key: "test-api-key".to_string(),
-        };
+        state.client_blacklist.ban("BadClient1".to_string(), None);
+        state.client_blacklist.ban("BadClient2".to_string(), None);
 
-        let response = client_list_handler(State(state), Query(params)).await.unwrap();
+        let response = client_list_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
 
     #[tokio::test]
     async fn test_client_list_invalid_api_key() {
-        let state = create_test_state();
+        use axum::{body::Body, http::Request};
+        use tower::Service;
 
-        let params = crate::models::admin::ApiKeyQuery {
-            api_key: "wrong-key".to_string(),
-        };
+        let mut router = crate::core::routes::build_router(create_test_state());
 
-        let result = client_list_handler(State(state), Query(params)).await;
-        assert!(result.is_err());
-        let response = result.unwrap_err().into_response();
+        let request = Request::get("/client/list?api_key=wrong-key").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 }
@@ -0,0 +1,206 @@
+// Cache export endpoint
+
+use crate::core::error::MonitoringError;
+use crate::core::state::AppState;
+use crate::models::torrent::Torrent;
+use crate::models::user::User;
+use crate::utils::time::current_timestamp;
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::iter;
+use std::sync::Arc;
+use tracing::info;
+
+/// Number of entries serialized per streamed body chunk
+const EXPORT_CHUNK_SIZE: usize = 1000;
+
+/// User entry in the export, with the passkey as hex
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportUser {
+    pub id: u32,
+    pub passkey: String,
+    pub class: u8,
+    pub is_active: bool,
+}
+
+/// Torrent entry in the export, with the info_hash as hex
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportTorrent {
+    pub id: u32,
+    pub info_hash: String,
+    pub is_freeleech: bool,
+    pub is_active: bool,
+}
+
+impl From<&User> for ExportUser {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id,
+            passkey: hex::encode(user.passkey),
+            class: user.class,
+            is_active: user.is_active,
+        }
+    }
+}
+
+impl From<&Torrent> for ExportTorrent {
+    fn from(torrent: &Torrent) -> Self {
+        Self {
+            id: torrent.id,
+            info_hash: hex::encode(torrent.info_hash),
+            is_freeleech: torrent.is_freeleech,
+            is_active: torrent.is_active,
+        }
+    }
+}
+
+/// Export handler
+///
+/// GET /export
+///
+/// Streams a point-in-time JSON dump of the user and torrent caches as
+/// `{"users": [...], "torrents": [...], "timestamp": ...}`. The caches are
+/// snapshotted as reference-counted entries and serialized in chunks, so the
+/// full document is never buffered.
+///
+/// Requires valid API key for authentication.
+pub async fn export_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, MonitoringError> {
+    let timestamp = current_timestamp();
+
+    let users = state.user_cache.load().snapshot();
+    let torrents = state.torrent_cache.load().snapshot();
+
+    info!(users = users.len(), torrents = torrents.len(), "Exporting cache state");
+
+    let parts = iter::once(Bytes::from_static(b"{\"users\":["))
+        .chain(encode_chunks::<_, ExportUser>(users))
+        .chain(iter::once(Bytes::from_static(b"],\"torrents\":[")))
+        .chain(encode_chunks::<_, ExportTorrent>(torrents))
+        .chain(iter::once(Bytes::from(format!("],\"timestamp\":{}}}", timestamp))));
+
+    let body = Body::from_stream(stream::iter(parts.map(Ok::<_, Infallible>)));
+
+    Ok((StatusCode::OK, [(header::CONTENT_TYPE, "application/json")], body).into_response())
+}
+
+/// Lazily serialize entries into comma-separated JSON chunks
+fn encode_chunks<T, E>(entries: Vec<Arc<T>>) -> impl Iterator<Item = Bytes>
+where
+    E: for<'a> From<&'a T> + Serialize,
+{
+    let chunk_count = entries.len().div_ceil(EXPORT_CHUNK_SIZE);
+
+    (0..chunk_count).map(move |chunk_index| {
+        let start = chunk_index * EXPORT_CHUNK_SIZE;
+        let end = (start + EXPORT_CHUNK_SIZE).min(entries.len());
+        let mut buf = Vec::with_capacity((end - start) * 128);
+
+        for (i, entry) in entries[start..end].iter().enumerate() {
+            if start + i > 0 {
+                buf.push(b',');
+            }
+            // Serializing plain structs into a Vec cannot fail
+            serde_json::to_writer(&mut buf, &E::from(entry.as_ref())).unwrap();
+        }
+
+        Bytes::from(buf)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Config;
+    use crate::wal::wal::Wal;
+    use http_body_util::BodyExt;
+    use tempfile::TempDir;
+
+    /// Shape of the exported document, which the handler streams piecewise
+    #[derive(Debug, Deserialize)]
+    struct ExportResponse {
+        users: Vec<ExportUser>,
+        torrents: Vec<ExportTorrent>,
+        timestamp: i64,
+    }
+
+    fn create_test_config() -> Config {
+        let mut config = Config::for_tests();
+        // Room for more than one export chunk of users
+        config.memory.user_cache_size = 10_000;
+        config
+    }
+
+    fn create_test_state() -> Arc<AppState> {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let wal = Wal::new(wal_path).unwrap();
+        let config = create_test_config();
+
+        Arc::new(AppState::new(config, wal))
+    }
+
+    async fn read_export(response: Response) -> ExportResponse {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_export_empty() {
+        let state = create_test_state();
+
+        let response = export_handler(State(state)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let export = read_export(response).await;
+        assert!(export.users.is_empty());
+        assert!(export.torrents.is_empty());
+        assert!(export.timestamp > 0);
+    }
+
+    #[tokio::test]
+    async fn test_export_spans_multiple_chunks() {
+        let state = create_test_state();
+
+        for i in 0..(EXPORT_CHUNK_SIZE as u32 + 5) {
+            let mut passkey = [0u8; 32];
+            passkey[..4].copy_from_slice(&i.to_be_bytes());
+            state.user_cache.load().add_user(User::new(i, passkey, 1, true)).unwrap();
+        }
+
+        let mut info_hash = [0u8; 20];
+        info_hash[0] = 0xab;
+        state.torrent_cache.load().add_torrent(Torrent::new(7, info_hash, true, true)).unwrap();
+
+        let response = export_handler(State(state)).await.unwrap();
+        let export = read_export(response).await;
+
+        assert_eq!(export.users.len(), EXPORT_CHUNK_SIZE + 5);
+        assert!(export.users.iter().all(|u| u.passkey.len() == 64));
+
+        assert_eq!(export.torrents.len(), 1);
+        assert_eq!(export.torrents[0].id, 7);
+        assert_eq!(export.torrents[0].info_hash, hex::encode(info_hash));
+        assert!(export.torrents[0].is_freeleech);
+    }
+
+    #[tokio::test]
+    async fn test_export_invalid_api_key() {
+        use axum::{body::Body, http::Request};
+        use tower::Service;
+
+        let mut router = crate::core::routes::build_router(create_test_state());
+
+        let request = Request::get("/export?api_key=wrong-key").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,10 +1,13 @@
 use axum::{
+    extract::State,
     http::HeaderMap,
     response::{Html, IntoResponse, Response},
 };
 use crate::core::error::AnnounceError;
+use crate::core::state::AppState;
+use std::sync::Arc;
 
-pub async fn fallback_handler(headers: HeaderMap) -> Response {
+pub async fn fallback_handler(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
     // Check if this is a browser request
     let user_agent = headers
         .get("user-agent")
@@ -17,7 +20,7 @@ pub async fn fallback_handler(headers: HeaderMap) -> Response {
         || user_agent.contains("Firefox")
         || user_agent.contains("Edge");
     
-    if is_browser {
+    if is_browser && state.config.server.browser_responses {
         let html = "Nothing to see here. Lost in the void!";
         
         return Html(html).into_response();
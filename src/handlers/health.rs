@@ -1,57 +1,159 @@
-use axum::{http::StatusCode, response::IntoResponse, Json};
+use crate::core::state::AppState;
+use crate::utils::time::current_timestamp;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tracing::warn;
 
 #[derive(Debug, Serialize, serde::Deserialize)]
 pub struct HealthResponse {
     pub status: String,
     pub timestamp: i64,
+    /// Unix timestamp of the last successful sync with the external API, if any
+    pub last_successful_sync: Option<i64>,
+    /// Seconds since the last successful sync, if any
+    pub sync_age: Option<i64>,
+    /// Bytes logged to the WAL since it was last truncated by a reload
+    pub wal_size: Option<u64>,
+    /// Whether the last WAL write succeeded
+    #[serde(default)]
+    pub wal_healthy: bool,
+    /// Failed WAL writes since the last successful one
+    #[serde(default)]
+    pub wal_write_failures: u64,
 }
 
 /// Health check handler
 /// 
 /// GET /health
-pub async fn health_handler() -> impl IntoResponse {
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
+///
+/// Reports 503 with status "stale" when `sync.max_sync_age` is set and the last
+/// successful sync (startup fetch, reload or /update poll) is older than that, or
+/// has not happened at all. A failing WAL is reported in `wal_healthy` without changing
+/// the status, since announces are still served.
+pub async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let timestamp = current_timestamp();
+
+    let last_sync = state.last_successful_sync.load(Ordering::Relaxed);
+    let last_successful_sync = (last_sync > 0).then_some(last_sync);
+    let sync_age = last_successful_sync.map(|last| timestamp.saturating_sub(last));
+
+    let wal_size = state
+        .wal
+        .size()
+        .inspect_err(|e| warn!(error = %e, "Failed to read WAL size for health check"))
+        .ok();
+
+    let (status_code, status) = if is_sync_stale(sync_age, state.config.sync.max_sync_age) {
+        (StatusCode::SERVICE_UNAVAILABLE, "stale")
+    } else {
+        (StatusCode::OK, "ok")
+    };
 
     (
-        StatusCode::OK,
+        status_code,
         Json(HealthResponse {
-            status: "ok".to_string(),
+            status: status.to_string(),
             timestamp,
+            last_successful_sync,
+            sync_age,
+            wal_size,
+            wal_healthy: state.wal.is_healthy(),
+            wal_write_failures: state.wal.consecutive_failures(),
         }),
     )
 }
 
+fn is_sync_stale(sync_age: Option<i64>, max_sync_age: u64) -> bool {
+    if max_sync_age == 0 {
+        return false;
+    }
+    sync_age.is_none_or(|age| age > max_sync_age as i64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::config::Config;
+    use crate::wal::wal::Wal;
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use tempfile::TempDir;
+
+    fn create_test_config(max_sync_age: u64) -> Config {
+        let mut config = Config::for_tests();
+        config.sync.max_sync_age = max_sync_age;
+        config
+    }
+
+    fn create_test_state(max_sync_age: u64) -> Arc<AppState> {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let wal = Wal::new(wal_path).unwrap();
+        let config = create_test_config(max_sync_age);
+
+        Arc::new(AppState::new(config, wal))
+    }
+
+    async fn call_health(state: Arc<AppState>) -> (StatusCode, HealthResponse) {
+        let response = health_handler(State(state)).await.into_response();
+        let (parts, body) = response.into_parts();
+        let bytes = Body::new(body).collect().await.unwrap().to_bytes();
+        (parts.status, serde_json::from_slice(&bytes).unwrap())
+    }
 
     #[tokio::test]
     async fn test_health_handler() {
-        let response = health_handler().await.into_response();
+        let (status, _) = call_health(create_test_state(0)).await;
         
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(status, StatusCode::OK);
     }
 
     #[tokio::test]
     async fn test_health_response_has_timestamp() {
-        use axum::body::Body;
-        use http_body_util::BodyExt;
-        
-        let response = health_handler().await.into_response();
-        
-
-        let (parts, body) = response.into_parts();
-        assert_eq!(parts.status, StatusCode::OK);
-        
-        let body = Body::new(body);
-        let bytes = body.collect().await.unwrap().to_bytes();
-        let health: HealthResponse = serde_json::from_slice(&bytes).unwrap();
+        let (status, health) = call_health(create_test_state(0)).await;
+        assert_eq!(status, StatusCode::OK);
         
         assert_eq!(health.status, "ok");
         assert!(health.timestamp > 0);
+        assert_eq!(health.last_successful_sync, None);
+        assert_eq!(health.sync_age, None);
+        assert_eq!(health.wal_size, Some(0));
+        assert!(health.wal_healthy);
+        assert_eq!(health.wal_write_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_stale_sync() {
+        let state = create_test_state(600);
+
+        // Never synced
+        let (status, health) = call_health(Arc::clone(&state)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health.status, "stale");
+
+        // Synced recently
+        let synced_at = current_timestamp() - 60;
+        state.last_successful_sync.store(synced_at, Ordering::Relaxed);
+        let (status, health) = call_health(Arc::clone(&state)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(health.last_successful_sync, Some(synced_at));
+        assert!(health.sync_age.unwrap() >= 60);
+
+        // Synced too long ago
+        state.last_successful_sync.store(current_timestamp() - 3600, Ordering::Relaxed);
+        let (status, health) = call_health(state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health.status, "stale");
+    }
+
+    #[test]
+    fn test_is_sync_stale() {
+        assert!(!is_sync_stale(None, 0));
+        assert!(!is_sync_stale(Some(10_000), 0));
+        assert!(is_sync_stale(None, 600));
+        assert!(!is_sync_stale(Some(600), 600));
+        assert!(is_sync_stale(Some(601), 600));
     }
 }
@@ -2,20 +2,13 @@
 
 use crate::core::error::MonitoringError;
 use crate::core::state::AppState;
-use crate::utils::auth::verify_api_key;
+use crate::metrics::collector::SnapshotSources;
 use axum::{
-    extract::{Query, State},
+    extract::State,
     http::StatusCode,
     response::{IntoResponse, Json, Response},
 };
-use serde::Deserialize;
 use std::sync::Arc;
-use tracing::warn;
-
-#[derive(Debug, Deserialize)]
-pub struct MetricsQuery {
-    pub api_key: String,
-}
 
 /// Returns JSON with all tracker statistics including:
 /// - Total announces, successful/failed counts, success rate
@@ -26,21 +19,16 @@ pub struct MetricsQuery {
 /// Requires valid API key for authentication.
 pub async fn metrics_handler(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<MetricsQuery>,
 ) -> Result<Response, MonitoringError> {
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized metrics access attempt");
-        return Err(MonitoringError::InvalidApiKey);
-    }
-
-
-    let snapshot = state.metrics.get_snapshot(
-        &state.peer_store,
-        &state.user_cache,
-        &state.torrent_cache,
-        &state.ip_blacklist,
-        &state.client_blacklist,
-    );
+    let snapshot = state.metrics.get_snapshot(&SnapshotSources {
+        peer_store: &state.peer_store,
+        user_cache: &state.user_cache.load(),
+        torrent_cache: &state.torrent_cache.load(),
+        ip_blacklist: &state.ip_blacklist,
+        client_blacklist: &state.client_blacklist,
+        global_limiter: &state.global_limiter,
+        wal_healthy: state.wal.is_healthy(),
+    });
 
     Ok((StatusCode::OK, Json(snapshot)).into_response())
 }
@@ -48,62 +36,17 @@ pub async fn metrics_handler(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::config::{
-        AntiCheatConfig, Config, LoggingConfig, MemoryConfig, PerformanceConfig, SecurityConfig,
-        ServerConfig, SyncConfig,
-    };
+    use crate::core::config::Config;
     use crate::metrics::collector::MetricsSnapshot;
     use crate::wal::wal::Wal;
     use tempfile::TempDir;
 
-    fn create_test_config() -> Config {
-        Config {
-            server: ServerConfig {
-                port: Some(8080),
-                unix_socket: None,
-                num_threads: 4,
-                max_connections: 1000,
-            },
-            memory: MemoryConfig {
-                peer_capacity: 10000,
-                torrent_cache_size: 1000,
-                user_cache_size: 1000,
-            },
-            performance: PerformanceConfig {
-                min_announce_interval: 1800,
-                max_requests_per_minute: 60,
-                cleanup_interval: 300,
-                peer_timeout: 3600,
-            },
-            sync: SyncConfig {
-                data_endpoint: "http://localhost:8000/api".to_string(),
-                api_key: "test-api-key".to_string(),
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                format: "json".to_string(),
-                path: None,
-                console: true,
-            },
-            anti_cheat: AntiCheatConfig {
-                max_ips_per_user: 3,
-                max_ratio: 10.0,
-                max_upload_speed: 100.0,
-                max_download_speed: 100.0,
-                min_seeder_upload: 1024,
-            },
-            security: SecurityConfig {
-                banned_ips: vec![],
-                banned_clients: vec![],
-            },
-        }
-    }
 
     fn create_test_state() -> Arc<AppState> {
         let temp_dir = TempDir::new().unwrap();
         let wal_path = temp_dir.path().join("test.wal");
         let wal = Wal::new(wal_path).unwrap();
-        let config = create_test_config();
+        let config = Config::for_tests();
 
         Arc::new(AppState::new(config, wal))
     }
@@ -115,11 +58,7 @@ mod tests {
         
         let state = create_test_state();
 
-        let params = MetricsQuery {
-            api_key: "test-api-key".to_string(),
-        };
-
-        let response = metrics_handler(State(state), Query(params)).await.unwrap();
+        let response = metrics_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
         // Verify response contains metrics
@@ -137,15 +76,35 @@ mod tests {
 
     #[tokio::test]
     async fn test_metrics_handler_invalid_api_key() {
-        let state = create_test_state();
+        use axum::{body::Body, http::Request};
+        use tower::Service;
 
-        let params = MetricsQuery {
-            api_key: "wrong-key".to_string(),
-        };
+        let mut router = crate::core::routes::build_router(create_test_state());
 
-        let result = metrics_handler(State(state), Query(params)).await;
-        assert!(result.is_err());
-        let response = result.unwrap_err().into_response();
+        let request = Request::get("/metrics?api_key=wrong-key").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_handler_bearer_token() {
+        use axum::{body::Body, http::Request};
+        use tower::Service;
+
+        let mut router = crate::core::routes::build_router(create_test_state());
+
+        let request = Request::get("/metrics")
+            .header("Authorization", "Bearer test-api-key")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::get("/metrics")
+            .header("Authorization", "Bearer wrong-key")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
@@ -160,11 +119,7 @@ mod tests {
         state.metrics.increment_announces();
         state.metrics.increment_successful();
 
-        let params = MetricsQuery {
-            api_key: "test-api-key".to_string(),
-        };
-
-        let response = metrics_handler(State(state), Query(params)).await.unwrap();
+        let response = metrics_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
         let (_, body) = response.into_parts();
@@ -4,4 +4,7 @@ pub mod blacklist;
 pub mod health;
 pub mod metrics;
 pub mod update;
+pub mod export;
+pub mod scrape;
 pub mod fallback;
+pub mod root;
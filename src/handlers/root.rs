@@ -0,0 +1,18 @@
+use crate::core::state::AppState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use std::sync::Arc;
+
+/// Root handler
+///
+/// GET /
+///
+/// Gives casual browser visits to the tracker base URL a short plain-text answer,
+/// separate from the fallback used for unknown paths.
+pub async fn root_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let message = match &state.config.server.root_message {
+        Some(message) => message.clone(),
+        None => format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+    };
+
+    (StatusCode::OK, message)
+}
@@ -0,0 +1,139 @@
+// Full scrape endpoint
+
+use crate::bencode::encoder::BencodeDictBuilder;
+use crate::bencode::response::bencode_http_response;
+use crate::core::state::AppState;
+use axum::{
+    extract::{RawQuery, State},
+    response::Response,
+};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Returns swarm stats for every registered torrent as a bencoded scrape response
+///
+/// The torrent list is private on most trackers, so this sits behind the monitoring
+/// API key and `security.allow_full_scrape`. Per-torrent scrape is not supported, and
+/// `downloaded` is left out because completions are reported to the external API
+/// rather than kept here.
+pub async fn full_scrape_handler(
+    State(state): State<Arc<AppState>>,
+    RawQuery(raw_query): RawQuery,
+) -> Response {
+    if raw_query.as_deref().is_some_and(|query| query.split('&').any(|pair| pair.starts_with("info_hash="))) {
+        return scrape_failure("Per-torrent scrape is not supported");
+    }
+
+    if !state.config.security.allow_full_scrape {
+        warn!("Full scrape requested but disabled");
+        return scrape_failure("Full scrape is disabled");
+    }
+
+    let mut torrents = state.torrent_cache.load().snapshot();
+    // Bencode dictionary keys must be sorted
+    torrents.sort_unstable_by_key(|torrent| torrent.info_hash);
+
+    let mut body = Vec::with_capacity(16 + torrents.len() * 56);
+    body.extend_from_slice(b"d5:filesd");
+    for torrent in &torrents {
+        let (seeders, leechers) = state.peer_store.get_stats(torrent.info_hash);
+        body.extend_from_slice(b"20:");
+        body.extend_from_slice(&torrent.info_hash);
+
+        let mut stats = BencodeDictBuilder::with_capacity(32);
+        stats.insert("complete", seeders as i64).insert("incomplete", leechers as i64);
+        stats.encode_into(&mut body);
+    }
+    body.extend_from_slice(b"ee");
+
+    info!(torrents = torrents.len(), "Full scrape served");
+
+    bencode_http_response(body)
+}
+
+fn scrape_failure(reason: &str) -> Response {
+    let mut dict = BencodeDictBuilder::with_capacity(64);
+    dict.insert("failure reason", reason);
+    bencode_http_response(dict.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Config;
+    use crate::models::peer::Peer;
+    use crate::models::torrent::Torrent;
+    use crate::wal::wal::Wal;
+    use http_body_util::BodyExt;
+    use std::net::{IpAddr, Ipv4Addr};
+    use tempfile::TempDir;
+
+
+    fn create_test_state(allow_full_scrape: bool) -> Arc<AppState> {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let wal = Wal::new(wal_path).unwrap();
+        let mut config = Config::for_tests();
+        config.security.allow_full_scrape = allow_full_scrape;
+
+        Arc::new(AppState::new(config, wal))
+    }
+
+    async fn scrape(state: Arc<AppState>, query: Option<&str>) -> Vec<u8> {
+        let response = full_scrape_handler(State(state), RawQuery(query.map(str::to_string))).await;
+        response.into_body().collect().await.unwrap().to_bytes().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_full_scrape_disabled_by_default() {
+        let state = create_test_state(false);
+
+        let body = scrape(state, None).await;
+        assert_eq!(body, b"d14:failure reason23:Full scrape is disablede".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_full_scrape_lists_all_torrents() {
+        let state = create_test_state(true);
+        state.torrent_cache.load().add_torrent(Torrent::new(2, [0x42; 20], false, true)).unwrap();
+        state.torrent_cache.load().add_torrent(Torrent::new(1, [0x41; 20], false, true)).unwrap();
+        let peer = Peer::new(1, 1, [1u8; 20], IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 6881, 0, 0, 0, 1000, String::new());
+        state.peer_store.add_peer([0x41; 20], peer).unwrap();
+
+        let body = scrape(state, None).await;
+        let expected = format!(
+            "d5:filesd20:{}d8:completei1e10:incompletei0ee20:{}d8:completei0e10:incompletei0eeee",
+            "A".repeat(20),
+            "B".repeat(20),
+        );
+        assert_eq!(body, expected.into_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_full_scrape_rejects_info_hash() {
+        let state = create_test_state(true);
+
+        let body = scrape(state, Some("info_hash=AAAAAAAAAAAAAAAAAAAA")).await;
+        assert_eq!(body, b"d14:failure reason35:Per-torrent scrape is not supportede".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_full_scrape_uses_announce_content_type() {
+        use axum::{body::Body, http::{header, Request}};
+        use tower::Service;
+
+        let temp_dir = TempDir::new().unwrap();
+        let wal = Wal::new(temp_dir.path().join("test.wal")).unwrap();
+        let mut config = Config::for_tests();
+        config.security.allow_full_scrape = true;
+        config.announce.content_type = "application/x-bittorrent".to_string();
+        let state = Arc::new(AppState::new(config, wal));
+
+        let request = Request::get("/scrape")
+            .header(header::AUTHORIZATION, "Bearer test-api-key")
+            .body(Body::empty())
+            .unwrap();
+        let response = crate::core::routes::build_router(state).call(request).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-bittorrent");
+    }
+}
@@ -1,20 +1,16 @@
 use crate::core::error::MonitoringError;
 use crate::core::state::AppState;
-use crate::utils::auth::verify_api_key;
+use crate::utils::time::current_timestamp;
 use axum::{
-    extract::{Query, State},
+    extract::State,
     http::StatusCode,
     response::{IntoResponse, Json, Response},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::IpAddr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tracing::warn;
-
-#[derive(Debug, Deserialize)]
-pub struct UpdateQuery {
-    pub api_key: String,
-}
 
 /// Peer data for external API
 #[derive(Debug, Serialize, Deserialize)]
@@ -31,6 +27,20 @@ pub struct PeerUpdate {
     pub last_announce: i64,
     pub user_agent: String,
     pub user_class: u8,
+    /// Most recent event the peer sent this session; omitted if it has only sent regular announces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_event: Option<String>,
+    /// Whether the peer accepts incoming connections; omitted while unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connectable: Option<bool>,
+    /// Encryption flags from the last announce; false when the client did not send them
+    #[serde(default)]
+    pub supportcrypto: bool,
+    #[serde(default)]
+    pub requirecrypto: bool,
+    /// Share of the torrent downloaded, 0 to 1; omitted while the torrent size is unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,12 +48,52 @@ pub struct TorrentUpdate {
     pub torrent_id: u32,
     pub seeders: u32,
     pub leechers: u32,
+    /// Downloads completed since the previous update
+    pub completed: u32,
+    /// Announces for the torrent since the previous update
+    #[serde(default)]
+    pub announces: u64,
+}
+
+/// Announces made by a user since the previous update
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserAnnounceUpdate {
+    pub user_id: u32,
+    pub announces: u64,
+}
+
+/// Seeding seconds accumulated by a user on a torrent since the previous update
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeedTimeUpdate {
+    pub user_id: u32,
+    pub torrent_id: u32,
+    pub seed_time_delta: u64,
+}
+
+/// Totals across all of a user's peers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserTotalsUpdate {
+    pub user_id: u32,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub left: u64,
+    /// Torrents the user has at least one peer in
+    pub active_torrents: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserTotalsResponse {
+    pub users: Vec<UserTotalsUpdate>,
+    pub timestamp: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateResponse {
     pub peers: Vec<PeerUpdate>,
     pub torrents: Vec<TorrentUpdate>,
+    pub seed_times: Vec<SeedTimeUpdate>,
+    #[serde(default)]
+    pub user_announces: Vec<UserAnnounceUpdate>,
     pub timestamp: i64,
 }
 
@@ -53,27 +103,28 @@ pub struct UpdateResponse {
 /// Used by the external API to synchronize tracker state.
 /// 
 /// Response includes:
-/// - peers: Array of peer data with torrent_id, user_id, peer_id, IP, port, stats, user_agent, user_class
-/// - torrents: Array of torrent stats with torrent_id, seeders, leechers
+/// - peers: Array of peer data with torrent_id, user_id, peer_id, IP, port, stats, user_agent, user_class,
+///   last_event (the most recent started/completed event, omitted if none), connectable
+///   (omitted while unknown), the supportcrypto/requirecrypto announce flags and progress
+///   (1 - left/size, omitted while the torrent size is unknown)
+/// - torrents: Array of torrent stats with torrent_id, seeders, leechers, completed
+///   (downloads finished since the previous update) and announces (announces since the
+///   previous update). Torrents announced to since then but without peers now are
+///   included with zero seeders and leechers.
+/// - user_announces: Array of user_id, announces (announces since the previous update)
+/// - seed_times: Array of user_id, torrent_id, seed_time_delta (seconds seeded since the
+///   previous update; the counters are reset on every call)
 /// - timestamp: Current Unix timestamp
 /// 
 /// Requires valid API key for authentication.
 pub async fn update_handler(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<UpdateQuery>,
 ) -> Result<Response, MonitoringError> {
-    if !verify_api_key(&params.api_key, &state.config.sync.api_key) {
-        warn!("Unauthorized update access attempt");
-        return Err(MonitoringError::InvalidApiKey);
-    }
-
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
+    let timestamp = current_timestamp();
 
     let mut peers = Vec::new();
     let mut torrents = Vec::new();
+    let mut torrent_announces: HashMap<u32, u64> = state.announce_counts.take_torrents().into_iter().collect();
 
 
     for torrent_entry in state.peer_store.peers.iter() {
@@ -84,11 +135,13 @@ pub async fn update_handler(
         let (seeders, leechers) = state.peer_store.get_stats(info_hash);
 
 
-        if let Some(torrent) = state.torrent_cache.get_torrent(info_hash) {
+        if let Some(torrent) = state.torrent_cache.load().get_torrent(info_hash) {
             torrents.push(TorrentUpdate {
                 torrent_id: torrent.id,
                 seeders,
                 leechers,
+                completed: state.peer_store.take_completed(info_hash),
+                announces: torrent_announces.remove(&torrent.id).unwrap_or(0),
             });
 
 
@@ -96,7 +149,7 @@ pub async fn update_handler(
                 let peer = peer_entry.value();
 
                 // Get user class from user cache
-                let user_class = if let Some(user) = state.user_cache.get_user_by_id(peer.user_id) {
+                let user_class = if let Some(user) = state.user_cache.load().get_user_by_id(peer.user_id) {
                     user.class
                 } else {
                     0 // Default class if user not found
@@ -124,14 +177,51 @@ pub async fn update_handler(
                     last_announce: peer.last_announce,
                     user_agent: peer.user_agent.clone(),
                     user_class,
+                    last_event: peer.last_event.map(|event| event.as_str().to_string()),
+                    connectable: peer.connectable,
+                    supportcrypto: peer.supportcrypto,
+                    requirecrypto: peer.requirecrypto,
+                    progress: peer.progress(torrent.size),
                 });
             }
         }
     }
 
+    // Swarms that emptied since the last update still report their announces
+    torrents.extend(torrent_announces.into_iter().map(|(torrent_id, announces)| TorrentUpdate {
+        torrent_id,
+        seeders: 0,
+        leechers: 0,
+        completed: 0,
+        announces,
+    }));
+
+    let user_announces = state
+        .announce_counts
+        .take_users()
+        .into_iter()
+        .map(|(user_id, announces)| UserAnnounceUpdate { user_id, announces })
+        .collect();
+
+    let seed_times = state
+        .peer_store
+        .take_seed_time()
+        .into_iter()
+        .map(|(user_id, torrent_id, seed_time_delta)| SeedTimeUpdate {
+            user_id,
+            torrent_id,
+            seed_time_delta,
+        })
+        .collect();
+
+    // The external API pulling our stats counts as a sync for /health
+    state.last_successful_sync.store(timestamp, Ordering::Relaxed);
+
     let response = UpdateResponse {
         peers,
         torrents,
+        seed_times,
+        user_announces,
         timestamp,
     };
 
@@ -139,68 +229,76 @@ pub async fn update_handler(
 }
 
 
+/// Per-user rollup handler
+///
+/// GET /update/users
+///
+/// Sums uploaded, downloaded and left over every peer of each user and counts the
+/// torrents they are active in, for backends that only need user-level accounting.
+/// Unlike /update, nothing is reset by the call. Users are sorted by user_id.
+///
+/// Requires valid API key for authentication.
+pub async fn user_totals_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, MonitoringError> {
+    let timestamp = current_timestamp();
+
+    // Peers are grouped by torrent, so a user's torrent is new whenever the swarm
+    // index differs from the last one counted for them
+    let mut totals: HashMap<u32, (UserTotalsUpdate, usize)> = HashMap::new();
+    for (swarm_index, torrent_entry) in state.peer_store.peers.iter().enumerate() {
+        for peer_entry in torrent_entry.value().iter() {
+            let peer = peer_entry.value();
+            let (total, last_swarm) = totals.entry(peer.user_id).or_insert_with(|| {
+                (
+                    UserTotalsUpdate {
+                        user_id: peer.user_id,
+                        uploaded: 0,
+                        downloaded: 0,
+                        left: 0,
+                        active_torrents: 0,
+                    },
+                    usize::MAX,
+                )
+            });
+
+            total.uploaded = total.uploaded.saturating_add(peer.uploaded);
+            total.downloaded = total.downloaded.saturating_add(peer.downloaded);
+            total.left = total.left.saturating_add(peer.left);
+            if *last_swarm != swarm_index {
+                *last_swarm = swarm_index;
+                total.active_torrents += 1;
+            }
+        }
+    }
+
+    let mut users: Vec<UserTotalsUpdate> = totals.into_values().map(|(total, _)| total).collect();
+    users.sort_unstable_by_key(|total| total.user_id);
+
+    // The external API pulling our stats counts as a sync for /health
+    state.last_successful_sync.store(timestamp, Ordering::Relaxed);
+
+    Ok((StatusCode::OK, Json(UserTotalsResponse { users, timestamp })).into_response())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::config::{
-        AntiCheatConfig, Config, LoggingConfig, MemoryConfig, PerformanceConfig, SecurityConfig,
-        ServerConfig, SyncConfig,
-    };
+    use crate::core::config::Config;
     use crate::models::peer::Peer;
     use crate::models::torrent::Torrent;
     use crate::models::user::User;
+    use crate::validation::params::AnnounceEvent;
     use crate::wal::wal::Wal;
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
     use tempfile::TempDir;
 
-    fn create_test_config() -> Config {
-        Config {
-            server: ServerConfig {
-                port: Some(8080),
-                unix_socket: None,
-                num_threads: 4,
-                max_connections: 1000,
-            },
-            memory: MemoryConfig {
-                peer_capacity: 10000,
-                torrent_cache_size: 1000,
-                user_cache_size: 1000,
-            },
-            performance: PerformanceConfig {
-                min_announce_interval: 1800,
-                max_requests_per_minute: 60,
-                cleanup_interval: 300,
-                peer_timeout: 3600,
-            },
-            sync: SyncConfig {
-                data_endpoint: "http://localhost:8000/api".to_string(),
-                api_key: "test-api-key".to_string(),
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                format: "json".to_string(),
-                path: None,
-                console: true,
-            },
-            anti_cheat: AntiCheatConfig {
-                max_ips_per_user: 3,
-                max_ratio: 10.0,
-                max_upload_speed: 100.0,
-                max_download_speed: 100.0,
-                min_seeder_upload: 1024,
-            },
-            security: SecurityConfig {
-                banned_ips: vec![],
-                banned_clients: vec![],
-            },
-        }
-    }
 
     fn create_test_state() -> Arc<AppState> {
         let temp_dir = TempDir::new().unwrap();
         let wal_path = temp_dir.path().join("test.wal");
         let wal = Wal::new(wal_path).unwrap();
-        let config = create_test_config();
+        let config = Config::for_tests();
 
         Arc::new(AppState::new(config, wal))
     }
@@ -212,11 +310,7 @@ mod tests {
         
         let state = create_test_state();
 
-        let params = UpdateQuery {
-            api_key: "test-api-key".to_string(),
-        };
-
-        let response = update_handler(State(state), Query(params)).await.unwrap();
+        let response = update_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
         // Verify response structure
@@ -227,20 +321,55 @@ mod tests {
 
         assert_eq!(update.peers.len(), 0);
         assert_eq!(update.torrents.len(), 0);
+        assert!(update.seed_times.is_empty());
         assert!(update.timestamp > 0);
     }
 
     #[tokio::test]
-    async fn test_update_handler_invalid_api_key() {
+    async fn test_update_reports_announce_counts() {
+        use http_body_util::BodyExt;
+
         let state = create_test_state();
+        let info_hash = [2u8; 20];
+        state.torrent_cache.load().add_torrent(Torrent::new(456, info_hash, false, true)).unwrap();
+        let peer = Peer::new(123, 456, [3u8; 20], IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 6881, 0, 0, 0, 1000, "qBittorrent/4.5.0".to_string());
+        state.peer_store.add_peer(info_hash, peer).unwrap();
 
-        let params = UpdateQuery {
-            api_key: "wrong-key".to_string(),
-        };
+        state.announce_counts.record(123, 456);
+        state.announce_counts.record(123, 456);
+        // Torrent 789 has no peers left but was announced to
+        state.announce_counts.record(124, 789);
+
+        let response = update_handler(State(state.clone())).await.unwrap();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let update: UpdateResponse = serde_json::from_slice(&bytes).unwrap();
+
+        let announces = |torrent_id| update.torrents.iter().find(|t| t.torrent_id == torrent_id).unwrap().announces;
+        assert_eq!(announces(456), 2);
+        assert_eq!(announces(789), 1);
+
+        let mut users: Vec<(u32, u64)> = update.user_announces.iter().map(|u| (u.user_id, u.announces)).collect();
+        users.sort_unstable();
+        assert_eq!(users, vec![(123, 2), (124, 1)]);
+
+        // Counts are deltas, reset by every update
+        let response = update_handler(State(state)).await.unwrap();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let update: UpdateResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(update.torrents.len(), 1);
+        assert_eq!(update.torrents[0].announces, 0);
+        assert!(update.user_announces.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_handler_invalid_api_key() {
+        use axum::{body::Body, http::Request};
+        use tower::Service;
+
+        let mut router = crate::core::routes::build_router(create_test_state());
 
-        let result = update_handler(State(state), Query(params)).await;
-        assert!(result.is_err());
-        let response = result.unwrap_err().into_response();
+        let request = Request::get("/update?api_key=wrong-key").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
@@ -253,12 +382,12 @@ mod tests {
 
         // Add a user
         let user = User::new(123, [1u8; 32], 2, true);
-        state.user_cache.add_user(user);
+        state.user_cache.load().add_user(user).unwrap();
 
         // Add a torrent
         let info_hash = [2u8; 20];
         let torrent = Torrent::new(456, info_hash, false, true);
-        state.torrent_cache.add_torrent(torrent);
+        state.torrent_cache.load().add_torrent(torrent).unwrap();
 
         // Add a peer
         let peer = Peer::new(
@@ -273,13 +402,14 @@ mod tests {
             1000,
             "TestClient/1.0".to_string(),
         );
-        state.peer_store.add_peer(info_hash, peer).unwrap();
+        state.peer_store.add_peer(info_hash, peer.clone()).unwrap();
 
-        let params = UpdateQuery {
-            api_key: "test-api-key".to_string(),
-        };
+        // Re-announce as a seeder 300 seconds later
+        let mut reannounce = peer;
+        reannounce.last_announce = 1300;
+        state.peer_store.update_peer(info_hash, [3u8; 20], reannounce).unwrap();
 
-        let response = update_handler(State(state), Query(params)).await.unwrap();
+        let response = update_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
         let (_, body) = response.into_parts();
@@ -289,6 +419,10 @@ mod tests {
 
         assert_eq!(update.peers.len(), 1);
         assert_eq!(update.torrents.len(), 1);
+        assert_eq!(update.seed_times.len(), 1);
+        assert_eq!(update.seed_times[0].user_id, 123);
+        assert_eq!(update.seed_times[0].torrent_id, 456);
+        assert_eq!(update.seed_times[0].seed_time_delta, 300);
 
         let peer_update = &update.peers[0];
         assert_eq!(peer_update.torrent_id, 456);
@@ -308,6 +442,43 @@ mod tests {
         assert_eq!(torrent_update.leechers, 0);
     }
 
+    #[tokio::test]
+    async fn test_user_totals_handler() {
+        use axum::body::Body;
+        use http_body_util::BodyExt;
+
+        let state = create_test_state();
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let peer = |user_id, torrent_id, peer_id, uploaded, downloaded, left| {
+            Peer::new(user_id, torrent_id, [peer_id; 20], ip, 6881, uploaded, downloaded, left, 1000, "TestClient/1.0".to_string())
+        };
+
+        // User 1 runs two clients on torrent 1 and one on torrent 2
+        state.peer_store.add_peer([1u8; 20], peer(1, 1, 1, 100, 10, 0)).unwrap();
+        state.peer_store.add_peer([1u8; 20], peer(1, 1, 2, 200, 20, 5)).unwrap();
+        state.peer_store.add_peer([2u8; 20], peer(1, 2, 3, 300, 30, 50)).unwrap();
+        state.peer_store.add_peer([2u8; 20], peer(2, 2, 4, 7, 8, 9)).unwrap();
+
+        let response = user_totals_handler(State(state)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = Body::new(response.into_body()).collect().await.unwrap().to_bytes();
+        let totals: UserTotalsResponse = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(totals.users.len(), 2);
+        let user = &totals.users[0];
+        assert_eq!(user.user_id, 1);
+        assert_eq!(user.uploaded, 600);
+        assert_eq!(user.downloaded, 60);
+        assert_eq!(user.left, 55);
+        assert_eq!(user.active_torrents, 2);
+
+        let user = &totals.users[1];
+        assert_eq!(user.user_id, 2);
+        assert_eq!(user.uploaded, 7);
+        assert_eq!(user.active_torrents, 1);
+    }
+
     #[tokio::test]
     async fn test_update_handler_with_ipv6_peer() {
         use axum::body::Body;
@@ -317,12 +488,12 @@ mod tests {
 
         // Add a user
         let user = User::new(789, [4u8; 32], 1, true);
-        state.user_cache.add_user(user);
+        state.user_cache.load().add_user(user).unwrap();
 
         // Add a torrent
         let info_hash = [5u8; 20];
         let torrent = Torrent::new(999, info_hash, true, true);
-        state.torrent_cache.add_torrent(torrent);
+        state.torrent_cache.load().add_torrent(torrent).unwrap();
 
         // Add an IPv6 peer
         let peer = Peer::new(
@@ -339,11 +510,7 @@ mod tests {
         );
         state.peer_store.add_peer(info_hash, peer).unwrap();
 
-        let params = UpdateQuery {
-            api_key: "test-api-key".to_string(),
-        };
-
-        let response = update_handler(State(state), Query(params)).await.unwrap();
+        let response = update_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
         let (_, body) = response.into_parts();
@@ -357,6 +524,71 @@ mod tests {
         assert_eq!(peer_update.ipv4, None);
         assert_eq!(peer_update.ipv6, Some("2001:db8::1".to_string()));
         assert_eq!(peer_update.user_class, 1);
+        assert_eq!(peer_update.last_event, None);
+    }
+
+    #[tokio::test]
+    async fn test_update_handler_includes_last_event() {
+        let state = create_test_state();
+
+        let info_hash = [5u8; 20];
+        state.torrent_cache.load().add_torrent(Torrent::new(999, info_hash, false, true)).unwrap();
+
+        let mut peer = Peer::new(
+            789,
+            999,
+            [6u8; 20],
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+            6881,
+            0,
+            0,
+            0,
+            2000,
+            "qBittorrent/4.5.0".to_string(),
+        );
+        peer.last_event = Some(AnnounceEvent::Completed);
+        peer.supportcrypto = true;
+        state.peer_store.add_peer(info_hash, peer).unwrap();
+
+        let response = update_handler(State(state)).await.unwrap();
+        let bytes = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains("\"last_event\":\"completed\""));
+
+        let update: UpdateResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(update.peers[0].last_event.as_deref(), Some("completed"));
+        assert!(update.peers[0].supportcrypto);
+        assert!(!update.peers[0].requirecrypto);
+    }
+
+    #[tokio::test]
+    async fn test_update_handler_includes_progress() {
+        let state = create_test_state();
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+
+        let sized = [5u8; 20];
+        let unknown_size = [6u8; 20];
+        state.torrent_cache.load().add_torrent(Torrent::new(1, sized, false, true).with_size(1000)).unwrap();
+        state.torrent_cache.load().add_torrent(Torrent::new(2, unknown_size, false, true)).unwrap();
+
+        let peer = Peer::new(7, 1, [1u8; 20], ip, 6881, 0, 0, 250, 2000, "qBittorrent/4.5.0".to_string());
+        state.peer_store.add_peer(sized, peer).unwrap();
+        // More left than the torrent holds clamps to 0
+        let peer = Peer::new(8, 1, [2u8; 20], ip, 6882, 0, 0, 5000, 2000, "qBittorrent/4.5.0".to_string());
+        state.peer_store.add_peer(sized, peer).unwrap();
+        let peer = Peer::new(7, 2, [3u8; 20], ip, 6881, 0, 0, 250, 2000, "qBittorrent/4.5.0".to_string());
+        state.peer_store.add_peer(unknown_size, peer).unwrap();
+
+        let response = update_handler(State(state)).await.unwrap();
+        let bytes = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+        let update: UpdateResponse = serde_json::from_slice(&bytes).unwrap();
+
+        let progress = |peer_id: u8| {
+            let peer_id = hex::encode([peer_id; 20]);
+            update.peers.iter().find(|p| p.peer_id == peer_id).unwrap().progress
+        };
+        assert_eq!(progress(1), Some(0.75));
+        assert_eq!(progress(2), Some(0.0));
+        assert_eq!(progress(3), None);
     }
 
     #[tokio::test]
@@ -369,14 +601,14 @@ mod tests {
         // Add users
         for i in 1..=3 {
             let user = User::new(i, [i as u8; 32], i as u8, true);
-            state.user_cache.add_user(user);
+            state.user_cache.load().add_user(user).unwrap();
         }
 
         // Add torrents and peers
         for i in 1..=2 {
             let info_hash = [i as u8; 20];
             let torrent = Torrent::new(i * 100, info_hash, false, true);
-            state.torrent_cache.add_torrent(torrent);
+            state.torrent_cache.load().add_torrent(torrent).unwrap();
 
             // Add 2 peers per torrent
             for j in 1..=2 {
@@ -396,11 +628,7 @@ mod tests {
             }
         }
 
-        let params = UpdateQuery {
-            api_key: "test-api-key".to_string(),
-        };
-
-        let response = update_handler(State(state), Query(params)).await.unwrap();
+        let response = update_handler(State(state)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
         let (_, body) = response.into_parts();
@@ -1,6 +1,8 @@
 mod core {
+    pub mod auth;
     pub mod config;
     pub mod error;
+    pub mod request_id;
     pub mod state;
     pub mod routes;
     pub mod startup;
@@ -20,32 +22,39 @@ mod validation;
 mod utils;
 
 use anyhow::{bail, Context, Result};
+use arc_swap::ArcSwap;
 use api::client::ApiClient;
 use axum::serve;
+use socket2::{Domain, Protocol, Socket, Type};
 use core::config::Config;
 use core::state::AppState;
-use core::startup::{apply_wal_operations, populate_from_api};
+use core::startup::{apply_wal_operations, load_ban_list_files, load_torrents_file, populate_from_api, reapply_torrent_pauses};
+use metrics::collector::{MetricsSnapshot, SnapshotSources};
 use std::env;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::{TcpListener, UnixListener};
-use std::net::SocketAddr;
-use tower::Service;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use tokio::signal;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
 use tower::ServiceBuilder;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
-use tracing::{info, debug, error, Level};
+use tracing::{info, debug, error, warn, Level};
 use wal::wal::Wal;
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
     
-    let config_path = if args.len() > 1 {
-        PathBuf::from(&args[1])
-    } else {
-        PathBuf::from("config.toml")
-    };
+    // --verify-api checks the external API schema and exits instead of serving
+    let verify_api = args.iter().any(|arg| arg == "--verify-api");
+    let config_path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("config.toml"));
     
     // Load and validate configuration
     let config = Config::from_file(&config_path)
@@ -55,8 +64,8 @@ fn main() -> Result<()> {
             config_path.display()
         ))?;
     
-    // Initialize tracing/logging
-    core::tracing_init::init_tracing(&config.logging);
+    // Initialize tracing/logging (hold the guard so buffered file logs are flushed on exit)
+    let _log_guard = core::tracing_init::init_tracing(&config.logging);
     
     // Build Tokio runtime with configured number of threads
     let runtime = tokio::runtime::Builder::new_multi_thread()
@@ -65,11 +74,36 @@ fn main() -> Result<()> {
         .build()
         .context("Failed to build Tokio runtime")?;
     
+    if verify_api {
+        return runtime.block_on(verify_api_schema(&config));
+    }
+    
     // Run the async main function
-    runtime.block_on(async_main(config, config_path))
+    let result = runtime.block_on(async_main(config, config_path));
+    
+    // A WAL replay cancelled by a shutdown signal may still be running on a blocking
+    // thread; don't hold up the exit waiting for it
+    runtime.shutdown_timeout(Duration::from_secs(1));
+    result
+}
+
+/// Fetch one page from the external API and report whether it matches the schema
+async fn verify_api_schema(config: &Config) -> Result<()> {
+    let api_client = ApiClient::new(config.sync.data_endpoint.clone(), config.sync.api_key.clone())
+        .context("Failed to create API client")?;
+    let (torrents, users) = api_client
+        .verify_schema()
+        .await
+        .with_context(|| format!("External API at {} does not match the expected schema", config.sync.data_endpoint))?;
+
+    println!(
+        "External API schema OK: page 1 has {} torrents and {} users",
+        torrents, users
+    );
+    Ok(())
 }
 
-async fn async_main(config: Config, config_path: PathBuf) -> Result<()> {
+async fn async_main(mut config: Config, config_path: PathBuf) -> Result<()> {
     info!(
         config_path = %config_path.display(),
         port = ?config.server.port,
@@ -87,49 +121,55 @@ async fn async_main(config: Config, config_path: PathBuf) -> Result<()> {
     
     info!(wal_path = %wal_path.display(), "WAL initialized");
     
+    load_ban_list_files(&mut config.security)?;
+
     // Create application state
     let state = AppState::new(config.clone(), wal);
     
-    // Replay WAL operations to restore cache state
-    info!("Replaying WAL operations");
-    let operations = state.wal.replay()
-        .context("Failed to replay WAL")?;
-    
-    apply_wal_operations(&state, &operations)?;
-    
-    info!(
-        operations_replayed = operations.len(),
-        users_loaded = state.user_cache.len(),
-        torrents_loaded = state.torrent_cache.len(),
-        "WAL replay completed"
-    );
-    
-    // Fetch data from external API
-    info!(
-        endpoint = %config.sync.data_endpoint,
-        "Fetching data from external API"
-    );
+    // Carry cumulative metrics over from the previous run if persistence is enabled
+    if let Some(path) = &config.metrics.persist_path {
+        match metrics::collector::load_counters(path) {
+            Ok(Some(counters)) => {
+                state.metrics.restore(&counters);
+                info!(
+                    path = %path.display(),
+                    total_announces = counters.total_announces,
+                    "Metrics counters restored"
+                );
+            }
+            Ok(None) => {
+                info!(path = %path.display(), "No persisted metrics counters, starting from zero");
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to load persisted metrics counters, starting from zero");
+            }
+        }
+    }
     
-    let api_client = ApiClient::new(
-        config.sync.data_endpoint.clone(),
-        config.sync.api_key.clone(),
-    ).context("Failed to create API client")?;
+    // Shutdown is signalled once and fanned out to startup and every TCP listener. The
+    // handler is installed before the slow part of startup so it can be cancelled too.
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(());
+    });
     
-    match populate_from_api(&state, &api_client).await {
-        Ok(_) => {
-            info!("Successfully populated caches from external API");
-        }
-        Err(e) => {
-            error!(
-                error = %e,
-                "Failed to fetch data from external API, continuing with WAL data only"
-            );
+    let mut startup_shutdown_rx = shutdown_rx.clone();
+    tokio::select! {
+        result = load_startup_data(&state, &config) => result?,
+        _ = startup_shutdown_rx.changed() => {
+            info!("Shutdown requested during startup, exiting before the server starts");
+            return Ok(());
         }
     }
     
     // Spawn background cleanup task
     spawn_cleanup_task(
         Arc::clone(&state.peer_store),
+        Arc::clone(&state.torrent_cache),
+        Arc::clone(&state.rate_limiter),
+        Arc::clone(&state.metrics),
+        config.metrics.persist_path.clone(),
         config.performance.cleanup_interval,
         config.performance.peer_timeout,
     );
@@ -142,17 +182,38 @@ async fn async_main(config: Config, config_path: PathBuf) -> Result<()> {
     
     // Log final startup statistics
     info!(
-        users = state.user_cache.len(),
-        torrents = state.torrent_cache.len(),
+        users = state.user_cache.load().len(),
+        torrents = state.torrent_cache.load().len(),
         peers = state.peer_store.total_peers(),
         banned_ips_ipv4 = state.ip_blacklist.list_ipv4().len(),
         banned_ips_ipv6 = state.ip_blacklist.list_ipv6().len(),
         banned_clients = state.client_blacklist.list().len(),
+        banned_users = state.user_blacklist.banned_count(),
         "BitTorrent Tracker startup complete"
     );
     
+    let state = Arc::new(state);
+    
+    // Spawn periodic metrics snapshot task if enabled
+    if config.metrics.snapshot_interval > 0 {
+        spawn_metrics_snapshot_task(
+            Arc::clone(&state),
+            config.metrics.snapshot_interval,
+            config.metrics.snapshot_webhook.clone(),
+        );
+        
+        info!(
+            snapshot_interval_seconds = config.metrics.snapshot_interval,
+            webhook_enabled = config.metrics.snapshot_webhook.is_some(),
+            "Metrics snapshot task started"
+        );
+    }
+    
+    spawn_stats_dump_task(Arc::clone(&state));
+    
     // Build the router with middleware
-    let app = core::routes::build_router(Arc::new(state))
+    let metrics = Arc::clone(&state.metrics);
+    let app = core::routes::build_router(state)
         .layer(
             ServiceBuilder::new()
                 .layer(
@@ -163,30 +224,41 @@ async fn async_main(config: Config, config_path: PathBuf) -> Result<()> {
         );
     
     // Start HTTP server(s)
-    let tcp_handle = if let Some(port) = config.server.port {
-        let addr = format!("0.0.0.0:{}", port);
+    let mut servers = JoinSet::new();
+    
+    let tcp_addrs: Vec<SocketAddr> = [
+        config.server.port.map(|port| SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))),
+        config.server.ipv4_port.map(|port| SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))),
+        config.server.ipv6_port.map(|port| SocketAddr::from((Ipv6Addr::UNSPECIFIED, port))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    
+    for addr in tcp_addrs {
         info!(address = %addr, "Starting TCP listener");
         
-        let listener = TcpListener::bind(&addr).await
+        let listener = bind_tcp_listener(addr)
             .context(format!("Failed to bind TCP listener to {}", addr))?;
         
         info!(address = %addr, "TCP listener bound successfully");
         
         let app_clone = app.clone();
-        Some(tokio::spawn(async move {
+        let mut shutdown_rx = shutdown_rx.clone();
+        servers.spawn(async move {
             serve(
                 listener,
                 app_clone.into_make_service_with_connect_info::<SocketAddr>()
             )
-                .with_graceful_shutdown(shutdown_signal())
+                .with_graceful_shutdown(async move {
+                    let _ = shutdown_rx.changed().await;
+                })
                 .await
-                .context("TCP server error")
-        }))
-    } else {
-        None
-    };
+                .context(format!("TCP server error on {}", addr))
+        });
+    }
     
-    let unix_handle = if let Some(unix_socket) = &config.server.unix_socket {
+    if let Some(unix_socket) = &config.server.unix_socket {
         info!(path = %unix_socket.display(), "Starting Unix socket listener");
         
         // Remove existing socket file if it exists
@@ -201,7 +273,7 @@ async fn async_main(config: Config, config_path: PathBuf) -> Result<()> {
         info!(path = %unix_socket.display(), "Unix socket listener bound successfully");
         
         let mut make_service = app.into_make_service();
-        Some(tokio::spawn(async move {
+        servers.spawn(async move {
             use tower::Service;
             
             loop {
@@ -233,60 +305,156 @@ async fn async_main(config: Config, config_path: PathBuf) -> Result<()> {
                     }
                 });
             }
-        }))
-    } else {
-        None
-    };
+        });
+    }
     
-    info!("HTTP server(s) started, waiting for shutdown signal");
+    if servers.is_empty() {
+        error!("No listeners configured");
+        bail!("No listeners configured");
+    }
     
-    // Wait for both servers to complete (if they exist)
-    match (tcp_handle, unix_handle) {
-        (Some(tcp), Some(unix)) => {
-            tokio::select! {
-                result = tcp => {
-                    if let Err(e) = result {
-                        error!(error = %e, "TCP server task failed");
-                    }
-                }
-                result = unix => {
-                    if let Err(e) = result {
-                        error!(error = %e, "Unix socket server task failed");
-                    }
-                }
-            }
+    info!(listeners = servers.len(), "HTTP server(s) started, waiting for shutdown signal");
+    
+    // Run until the first server exits (normally on graceful shutdown)
+    match servers.join_next().await {
+        Some(Ok(Err(e))) => {
+            error!(error = %e, "Server task failed");
         }
-        (Some(tcp), None) => {
-            if let Err(e) = tcp.await {
-                error!(error = %e, "TCP server task failed");
-            }
+        Some(Err(e)) => {
+            error!(error = %e, "Server task failed");
         }
-        (None, Some(unix)) => {
-            if let Err(e) = unix.await {
-                error!(error = %e, "Unix socket server task failed");
+        _ => {}
+    }
+    
+    info!("Shutting down gracefully");
+    
+    if let Some(path) = &config.metrics.persist_path {
+        match metrics.save_counters(path) {
+            Ok(()) => info!(path = %path.display(), "Metrics counters saved"),
+            Err(e) => error!(error = %e, "Failed to save metrics counters"),
+        }
+    }
+    
+    Ok(())
+}
+
+/// Restore the caches from the WAL and the external API
+///
+/// Runs before any listener is bound and may take a while on a large WAL or a slow API,
+/// so `async_main` races it against the shutdown signal.
+/// Bind a TCP listener, with IPv6 listeners accepting IPv6 clients only
+///
+/// Where the system allows dual-stack sockets, `[::]` would also accept IPv4
+/// connections on ipv6_port, so it would not be the separate IPv6 listener it is
+/// configured as.
+fn bind_tcp_listener(addr: SocketAddr) -> Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    // Matches tokio's TcpListener::bind, which sets SO_REUSEADDR on Unix
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+async fn load_startup_data(state: &AppState, config: &Config) -> Result<()> {
+    // Replay WAL operations to restore cache state. Replay reads the file synchronously,
+    // so it runs on a blocking thread to keep it cancellable.
+    info!("Replaying WAL operations");
+    let wal = Arc::clone(&state.wal);
+    let operations = tokio::task::spawn_blocking(move || wal.replay())
+        .await
+        .context("WAL replay task failed")?
+        .context("Failed to replay WAL")?;
+    
+    apply_wal_operations(state, &operations)?;
+    
+    info!(
+        operations_replayed = operations.len(),
+        users_loaded = state.user_cache.load().len(),
+        torrents_loaded = state.torrent_cache.load().len(),
+        "WAL replay completed"
+    );
+    
+    // Load the local torrent allowlist, for deployments that can't reach the API
+    if let Some(path) = &config.sync.torrents_file {
+        load_torrents_file(&state.torrent_cache.load(), path)?;
+    }
+    
+    // Fetch data from external API
+    info!(
+        endpoint = %config.sync.data_endpoint,
+        "Fetching data from external API"
+    );
+    
+    let api_client = ApiClient::new(
+        config.sync.data_endpoint.clone(),
+        config.sync.api_key.clone(),
+    ).context("Failed to create API client")?;
+    
+    match populate_from_api(&state.user_cache.load_full(), &state.torrent_cache.load_full(), &api_client).await {
+        Ok(outcome) => {
+            state.user_blacklist.replace_api_bans(&outcome.locked_users);
+            if outcome.skipped_pages.is_empty() {
+                state.last_successful_sync.store(utils::time::current_timestamp(), Ordering::Relaxed);
+                info!("Successfully populated caches from external API");
+            } else {
+                // The caches keep the WAL data plus everything the good pages added
+                warn!(
+                    skipped_pages = ?outcome.skipped_pages,
+                    "Populated caches from incomplete external API data, keeping existing entries"
+                );
             }
         }
-        (None, None) => {
-            error!("No listeners configured");
-            bail!("No listeners configured");
+        Err(e) => {
+            error!(
+                error = %e,
+                "Failed to fetch data from external API, continuing with WAL data only"
+            );
         }
     }
-    
-    info!("Shutting down gracefully");
+
+    // Torrents loaded above come in active, undoing pauses from the WAL
+    reapply_torrent_pauses(&state.torrent_cache.load(), &operations);
     
     Ok(())
 }
 
-/// Spawn a background task that periodically cleans up stale peers
-fn spawn_cleanup_task(peer_store: Arc<stores::peer_store::PeerStore>, cleanup_interval: u64, peer_timeout: i64) {
+/// Spawn a background task that periodically cleans up stale peers and expired
+/// rate limit windows, and saves the metrics counters if they are persisted
+///
+/// The first run is randomly offset within one interval and later runs carry ±10% jitter,
+/// so cleanup doesn't hit at a fixed phase. The average interval stays `cleanup_interval`.
+/// Saving on every run bounds what a crash loses to one interval of counts.
+fn spawn_cleanup_task(
+    peer_store: Arc<stores::peer_store::PeerStore>,
+    torrent_cache: Arc<ArcSwap<stores::torrent_cache::TorrentCache>>,
+    rate_limiter: Arc<security::rate_limiter::RateLimiter>,
+    metrics: Arc<metrics::collector::Metrics>,
+    persist_path: Option<PathBuf>,
+    cleanup_interval: u64,
+    peer_timeout: i64,
+) {
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(cleanup_interval));
+        let interval = Duration::from_secs(cleanup_interval);
+        // Deadlines advance from the previous deadline so run time doesn't skew the average
+        let mut next_run = tokio::time::Instant::now() + utils::time::random_phase(interval);
         
         loop {
-            interval.tick().await;
+            tokio::time::sleep_until(next_run).await;
+            next_run += utils::time::jittered(interval, 0.1);
             
             debug!("Running peer cleanup");
-            let removed = peer_store.cleanup_stale_peers(peer_timeout);
+            // Torrents can override the timeout, e.g. to keep rarely announcing archival seeders
+            let torrents = torrent_cache.load();
+            let removed = peer_store.cleanup_stale_peers(peer_timeout, |info_hash| {
+                torrents.get_torrent(info_hash).and_then(|torrent| torrent.peer_timeout)
+            });
             
             if removed > 0 {
                 info!(
@@ -298,10 +466,137 @@ fn spawn_cleanup_task(peer_store: Arc<stores::peer_store::PeerStore>, cleanup_in
             } else {
                 debug!("Peer cleanup completed, no stale peers found");
             }
+
+            let pruned = rate_limiter.cleanup_old_entries(utils::time::current_timestamp());
+
+            if pruned > 0 {
+                info!(
+                    pruned_entries = pruned,
+                    tracked_ips = rate_limiter.tracked_ips(),
+                    "Rate limiter cleanup completed"
+                );
+            } else {
+                debug!("Rate limiter cleanup completed, no expired entries found");
+            }
+
+            if let Some(path) = &persist_path {
+                match metrics.save_counters(path) {
+                    Ok(()) => debug!(path = %path.display(), "Metrics counters saved"),
+                    Err(e) => warn!(error = %e, "Failed to save metrics counters"),
+                }
+            }
+        }
+    });
+}
+
+/// Spawn a background task that periodically logs a metrics snapshot and optionally
+/// POSTs it to a webhook
+fn spawn_metrics_snapshot_task(state: Arc<AppState>, snapshot_interval: u64, webhook: Option<String>) {
+    tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        
+        let mut interval = tokio::time::interval(Duration::from_secs(snapshot_interval));
+        // The first tick completes immediately; skip it so snapshots start after one interval
+        interval.tick().await;
+        
+        loop {
+            interval.tick().await;
+            
+            let snapshot = state.metrics.get_snapshot(&SnapshotSources {
+                peer_store: &state.peer_store,
+                user_cache: &state.user_cache.load(),
+                torrent_cache: &state.torrent_cache.load(),
+                ip_blacklist: &state.ip_blacklist,
+                client_blacklist: &state.client_blacklist,
+                global_limiter: &state.global_limiter,
+                wal_healthy: state.wal.is_healthy(),
+            });
+            
+            log_metrics_snapshot(&snapshot, "interval");
+            
+            if let Some(url) = &webhook {
+                match client.post(url).json(&snapshot).send().await {
+                    Ok(response) if !response.status().is_success() => {
+                        warn!(status = %response.status(), "Metrics webhook returned an error status");
+                    }
+                    Ok(_) => {
+                        debug!("Metrics snapshot delivered to webhook");
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to deliver metrics snapshot to webhook");
+                    }
+                }
+            }
         }
     });
 }
 
+/// Spawn a background task that logs a metrics snapshot whenever SIGUSR1 is received
+#[cfg(unix)]
+fn spawn_stats_dump_task(state: Arc<AppState>) {
+    let mut sigusr1 = match signal::unix::signal(signal::unix::SignalKind::user_defined1()) {
+        Ok(sigusr1) => sigusr1,
+        Err(e) => {
+            warn!(error = %e, "Failed to install SIGUSR1 handler, stats dump disabled");
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        while sigusr1.recv().await.is_some() {
+            let snapshot = state.metrics.get_snapshot(&SnapshotSources {
+                peer_store: &state.peer_store,
+                user_cache: &state.user_cache.load(),
+                torrent_cache: &state.torrent_cache.load(),
+                ip_blacklist: &state.ip_blacklist,
+                client_blacklist: &state.client_blacklist,
+                global_limiter: &state.global_limiter,
+                wal_healthy: state.wal.is_healthy(),
+            });
+
+            log_metrics_snapshot(&snapshot, "SIGUSR1");
+        }
+    });
+}
+
+/// Stats dumps are triggered by SIGUSR1, which only exists on Unix
+#[cfg(not(unix))]
+fn spawn_stats_dump_task(_state: Arc<AppState>) {}
+
+fn log_metrics_snapshot(snapshot: &MetricsSnapshot, trigger: &str) {
+    info!(
+        trigger = trigger,
+        total_announces = snapshot.total_announces,
+        successful_announces = snapshot.successful_announces,
+        failed_announces = snapshot.failed_announces,
+        success_rate = snapshot.success_rate,
+        started_count = snapshot.started_count,
+        stopped_count = snapshot.stopped_count,
+        completed_count = snapshot.completed_count,
+        update_count = snapshot.update_count,
+        active_peers = snapshot.active_peers,
+        cached_torrents = snapshot.active_torrents,
+        cached_users = snapshot.active_users,
+        blocked_requests = snapshot.blocked_requests,
+        banned_ipv4 = snapshot.banned_ipv4,
+        banned_ipv6 = snapshot.banned_ipv6,
+        banned_clients = snapshot.banned_clients,
+        uptime_seconds = snapshot.uptime_seconds,
+        lifetime_seconds = snapshot.lifetime_seconds,
+        requests_per_second = snapshot.requests_per_second,
+        resident_memory_bytes = snapshot.resident_memory_bytes,
+        global_announce_rate = snapshot.global_announce_rate,
+        load_shedding = snapshot.load_shedding,
+        shed_announces = snapshot.shed_announces,
+        quota_rejections = snapshot.quota_rejections,
+        wal_healthy = snapshot.wal_healthy,
+        "Metrics snapshot"
+    );
+}
+
 /// Wait for shutdown signal (Ctrl+C or SIGTERM)
 async fn shutdown_signal() {
     let ctrl_c = async {
@@ -1,17 +1,72 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use crate::stores::peer_store::PeerStore;
 use crate::stores::user_cache::UserCache;
 use crate::stores::torrent_cache::TorrentCache;
 use crate::security::ip_blacklist::IpBlacklist;
 use crate::security::client_blacklist::ClientBlacklist;
-use serde::Serialize;
+use crate::security::global_limiter::GlobalAnnounceLimiter;
+use crate::utils::time::{current_timestamp, elapsed_seconds};
+use crate::validation::params::AnnounceEvent;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Components a metrics snapshot reads its gauges from
+#[derive(Clone, Copy)]
+pub struct SnapshotSources<'a> {
+    pub peer_store: &'a PeerStore,
+    pub user_cache: &'a UserCache,
+    pub torrent_cache: &'a TorrentCache,
+    pub ip_blacklist: &'a IpBlacklist,
+    pub client_blacklist: &'a ClientBlacklist,
+    pub global_limiter: &'a GlobalAnnounceLimiter,
+    pub wal_healthy: bool,
+}
 
 pub struct Metrics {
     pub total_announces: AtomicU64,
     pub successful_announces: AtomicU64,
     pub failed_announces: AtomicU64,
     pub blocked_requests: AtomicU64,
+    /// Announces that passed all checks, by event (`update` = no event)
+    pub started_count: AtomicU64,
+    pub stopped_count: AtomicU64,
+    pub completed_count: AtomicU64,
+    pub update_count: AtomicU64,
+    /// Announces refused because the user was at their torrent quota
+    pub quota_rejections: AtomicU64,
+    /// Announces shed by the global rate cap
+    pub shed_announces: AtomicU64,
+    /// Start of the current process
     pub start_time: i64,
+    /// Start of the first run whose counters were carried over (equals `start_time` if none)
+    pub lifetime_start: AtomicI64,
+    /// Announces restored from a previous run, excluded from `requests_per_second`
+    restored_announces: AtomicU64,
+}
+
+/// Cumulative counters persisted across restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedCounters {
+    pub total_announces: u64,
+    pub successful_announces: u64,
+    pub failed_announces: u64,
+    pub blocked_requests: u64,
+    // Absent from files written before per-event counts existed
+    #[serde(default)]
+    pub started_count: u64,
+    #[serde(default)]
+    pub stopped_count: u64,
+    #[serde(default)]
+    pub completed_count: u64,
+    #[serde(default)]
+    pub update_count: u64,
+    // Absent from files written before quota and shed counts were persisted
+    #[serde(default)]
+    pub quota_rejections: u64,
+    #[serde(default)]
+    pub shed_announces: u64,
+    pub lifetime_start: i64,
 }
 
 #[derive(Debug, Clone, Serialize, serde::Deserialize)]
@@ -26,29 +81,107 @@ pub struct MetricsSnapshot {
     #[serde(rename = "cached_users")]
     pub active_users: usize,
     pub blocked_requests: u64,
+    pub started_count: u64,
+    pub stopped_count: u64,
+    pub completed_count: u64,
+    pub update_count: u64,
     pub banned_ipv4: usize,
     pub banned_ipv6: usize,
     pub banned_clients: usize,
+    /// Uptime of the current process
     pub uptime_seconds: i64,
+    /// Seconds since counters started accumulating, spanning restarts when persisted
+    pub lifetime_seconds: i64,
     pub requests_per_second: f64,
+    /// Resident set size of the tracker process (0 where unavailable)
+    pub resident_memory_bytes: u64,
+    /// Announces received in the last full second, across all clients
+    #[serde(default)]
+    pub global_announce_rate: u64,
+    /// Whether announces over the global rate cap were shed in the last second
+    #[serde(default)]
+    pub load_shedding: bool,
+    /// Announces shed by the global rate cap
+    #[serde(default)]
+    pub shed_announces: u64,
+    /// Announces refused because the user was at their torrent quota
+    #[serde(default)]
+    pub quota_rejections: u64,
+    /// Whether the last WAL write succeeded; admin changes are not persisted while false
+    #[serde(default)]
+    pub wal_healthy: bool,
 }
 
 impl Metrics {
     pub fn new() -> Self {
-        let start_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let start_time = current_timestamp();
 
         Self {
             total_announces: AtomicU64::new(0),
             successful_announces: AtomicU64::new(0),
             failed_announces: AtomicU64::new(0),
             blocked_requests: AtomicU64::new(0),
+            started_count: AtomicU64::new(0),
+            stopped_count: AtomicU64::new(0),
+            completed_count: AtomicU64::new(0),
+            update_count: AtomicU64::new(0),
+            quota_rejections: AtomicU64::new(0),
+            shed_announces: AtomicU64::new(0),
             start_time,
+            lifetime_start: AtomicI64::new(start_time),
+            restored_announces: AtomicU64::new(0),
         }
     }
 
+    /// Current cumulative counters, for persisting on shutdown
+    pub fn persisted_counters(&self) -> PersistedCounters {
+        PersistedCounters {
+            total_announces: self.total_announces.load(Ordering::Relaxed),
+            successful_announces: self.successful_announces.load(Ordering::Relaxed),
+            failed_announces: self.failed_announces.load(Ordering::Relaxed),
+            blocked_requests: self.blocked_requests.load(Ordering::Relaxed),
+            started_count: self.started_count.load(Ordering::Relaxed),
+            stopped_count: self.stopped_count.load(Ordering::Relaxed),
+            completed_count: self.completed_count.load(Ordering::Relaxed),
+            update_count: self.update_count.load(Ordering::Relaxed),
+            quota_rejections: self.quota_rejections.load(Ordering::Relaxed),
+            shed_announces: self.shed_announces.load(Ordering::Relaxed),
+            lifetime_start: self.lifetime_start.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Add counters from a previous run so lifetime totals stay continuous
+    pub fn restore(&self, counters: &PersistedCounters) {
+        self.total_announces.fetch_add(counters.total_announces, Ordering::Relaxed);
+        self.successful_announces.fetch_add(counters.successful_announces, Ordering::Relaxed);
+        self.failed_announces.fetch_add(counters.failed_announces, Ordering::Relaxed);
+        self.blocked_requests.fetch_add(counters.blocked_requests, Ordering::Relaxed);
+        self.started_count.fetch_add(counters.started_count, Ordering::Relaxed);
+        self.stopped_count.fetch_add(counters.stopped_count, Ordering::Relaxed);
+        self.completed_count.fetch_add(counters.completed_count, Ordering::Relaxed);
+        self.update_count.fetch_add(counters.update_count, Ordering::Relaxed);
+        self.quota_rejections.fetch_add(counters.quota_rejections, Ordering::Relaxed);
+        self.shed_announces.fetch_add(counters.shed_announces, Ordering::Relaxed);
+        self.restored_announces.fetch_add(counters.total_announces, Ordering::Relaxed);
+        self.lifetime_start.fetch_min(counters.lifetime_start, Ordering::Relaxed);
+    }
+
+    /// Write the cumulative counters to `path` as JSON
+    ///
+    /// Writes to a temporary file first so a crash mid-write keeps the previous file.
+    pub fn save_counters(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec(&self.persisted_counters())
+            .context("Failed to serialize metrics counters")?;
+
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, json)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+        Ok(())
+    }
+
 
     pub fn increment_announces(&self) {
         self.total_announces.fetch_add(1, Ordering::Relaxed);
@@ -69,21 +202,42 @@ impl Metrics {
         self.blocked_requests.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn increment_quota_rejections(&self) {
+        self.quota_rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_shed_announces(&self) {
+        self.shed_announces.fetch_add(1, Ordering::Relaxed);
+    }
+
+
+    /// Count an accepted announce under its event (`None` counts as an update)
+    pub fn increment_event(&self, event: Option<AnnounceEvent>) {
+        let counter = match event {
+            Some(AnnounceEvent::Started) => &self.started_count,
+            Some(AnnounceEvent::Stopped) => &self.stopped_count,
+            Some(AnnounceEvent::Completed) => &self.completed_count,
+            None => &self.update_count,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
 
     /// Collects metrics from all components and calculates derived metrics
-    /// like success_rate, requests_per_second, and uptime_seconds.
-    pub fn get_snapshot(
-        &self,
-        peer_store: &PeerStore,
-        user_cache: &UserCache,
-        torrent_cache: &TorrentCache,
-        ip_blacklist: &IpBlacklist,
-        client_blacklist: &ClientBlacklist,
-    ) -> MetricsSnapshot {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+    /// like success_rate, requests_per_second, and uptime_seconds, plus the process
+    /// resident memory.
+    pub fn get_snapshot(&self, sources: &SnapshotSources) -> MetricsSnapshot {
+        let SnapshotSources {
+            peer_store,
+            user_cache,
+            torrent_cache,
+            ip_blacklist,
+            client_blacklist,
+            global_limiter,
+            wal_healthy,
+        } = *sources;
+        let current_time_ms = crate::utils::time::current_timestamp_millis();
+        let current_time = current_time_ms / 1000;
 
         let total_announces = self.total_announces.load(Ordering::Relaxed);
         let successful_announces = self.successful_announces.load(Ordering::Relaxed);
@@ -98,11 +252,14 @@ impl Metrics {
         };
 
         // Calculate uptime
-        let uptime_seconds = current_time - self.start_time;
+        let uptime_seconds = elapsed_seconds(self.start_time, current_time);
+        let lifetime_seconds = elapsed_seconds(self.lifetime_start.load(Ordering::Relaxed), current_time);
 
-        // Calculate requests per second
+        // Calculate requests per second for this process only
+        let process_announces =
+            total_announces.saturating_sub(self.restored_announces.load(Ordering::Relaxed));
         let requests_per_second = if uptime_seconds > 0 {
-            total_announces as f64 / uptime_seconds as f64
+            process_announces as f64 / uptime_seconds as f64
         } else {
             0.0
         };
@@ -116,15 +273,69 @@ impl Metrics {
             active_torrents: torrent_cache.len(),
             active_users: user_cache.len(),
             blocked_requests,
+            started_count: self.started_count.load(Ordering::Relaxed),
+            stopped_count: self.stopped_count.load(Ordering::Relaxed),
+            completed_count: self.completed_count.load(Ordering::Relaxed),
+            update_count: self.update_count.load(Ordering::Relaxed),
             banned_ipv4: ip_blacklist.list_ipv4().len(),
             banned_ipv6: ip_blacklist.list_ipv6().len(),
             banned_clients: client_blacklist.len(),
             uptime_seconds,
+            lifetime_seconds,
             requests_per_second,
+            resident_memory_bytes: resident_memory_bytes(),
+            global_announce_rate: global_limiter.current_rate(current_time_ms),
+            load_shedding: global_limiter.is_shedding(current_time_ms),
+            shed_announces: self.shed_announces.load(Ordering::Relaxed),
+            quota_rejections: self.quota_rejections.load(Ordering::Relaxed),
+            wal_healthy,
         }
     }
 }
 
+/// Load counters persisted by a previous run
+///
+/// Returns `None` if the file does not exist yet.
+pub fn load_counters(path: &Path) -> Result<Option<PersistedCounters>> {
+    let json = match std::fs::read(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+
+    let counters = serde_json::from_slice(&json)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    Ok(Some(counters))
+}
+
+/// Read the process resident set size from `/proc/self/statm`
+///
+/// The second field is the resident page count. Returns 0 if the file cannot be read.
+#[cfg(target_os = "linux")]
+fn resident_memory_bytes() -> u64 {
+    let Ok(statm) = std::fs::read_to_string("/proc/self/statm") else {
+        return 0;
+    };
+
+    let resident_pages = statm
+        .split_whitespace()
+        .nth(1)
+        .and_then(|pages| pages.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    // SAFETY: sysconf has no preconditions and only reads a system constant
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+
+    resident_pages * u64::try_from(page_size).unwrap_or(0)
+}
+
+/// Resident memory is only reported on Linux
+#[cfg(not(target_os = "linux"))]
+fn resident_memory_bytes() -> u64 {
+    0
+}
+
 impl Default for Metrics {
     fn default() -> Self {
         Self::new()
@@ -195,6 +406,32 @@ mod tests {
         assert_eq!(metrics.blocked_requests.load(Ordering::Relaxed), 2);
     }
 
+    #[test]
+    fn test_increment_event() {
+        let metrics = Metrics::new();
+
+        metrics.increment_event(Some(AnnounceEvent::Started));
+        metrics.increment_event(None);
+        metrics.increment_event(None);
+        metrics.increment_event(Some(AnnounceEvent::Completed));
+        metrics.increment_event(Some(AnnounceEvent::Stopped));
+
+        let snapshot = metrics.get_snapshot(&SnapshotSources {
+            peer_store: &PeerStore::new(),
+            user_cache: &UserCache::new(),
+            torrent_cache: &TorrentCache::new(),
+            ip_blacklist: &IpBlacklist::new(),
+            client_blacklist: &ClientBlacklist::new(),
+            global_limiter: &GlobalAnnounceLimiter::new(0, 0),
+            wal_healthy: true,
+        });
+
+        assert_eq!(snapshot.started_count, 1);
+        assert_eq!(snapshot.stopped_count, 1);
+        assert_eq!(snapshot.completed_count, 1);
+        assert_eq!(snapshot.update_count, 2);
+    }
+
     #[test]
     fn test_get_snapshot_empty() {
         let metrics = Metrics::new();
@@ -204,13 +441,15 @@ mod tests {
         let ip_blacklist = IpBlacklist::new();
         let client_blacklist = ClientBlacklist::new();
         
-        let snapshot = metrics.get_snapshot(
-            &peer_store,
-            &user_cache,
-            &torrent_cache,
-            &ip_blacklist,
-            &client_blacklist,
-        );
+        let snapshot = metrics.get_snapshot(&SnapshotSources {
+            peer_store: &peer_store,
+            user_cache: &user_cache,
+            torrent_cache: &torrent_cache,
+            ip_blacklist: &ip_blacklist,
+            client_blacklist: &client_blacklist,
+            global_limiter: &GlobalAnnounceLimiter::new(0, 0),
+            wal_healthy: true,
+        });
         
         assert_eq!(snapshot.total_announces, 0);
         assert_eq!(snapshot.successful_announces, 0);
@@ -252,15 +491,18 @@ mod tests {
             class: 1,
             is_active: true,
         };
-        user_cache.add_user(user);
+        user_cache.add_user(user).unwrap();
         
         let torrent = Torrent {
             id: 1,
             info_hash: [1u8; 20],
             is_freeleech: false,
             is_active: true,
+            size: 0,
+            announce_interval: None,
+            peer_timeout: None,
         };
-        torrent_cache.add_torrent(torrent);
+        torrent_cache.add_torrent(torrent).unwrap();
         
         let peer = Peer {
             user_id: 1,
@@ -274,19 +516,27 @@ mod tests {
             last_announce: 1000,
             user_agent: "TestClient/1.0".to_string(),
             is_seeder: true,
+            last_event: None,
+            key: None,
+            alt_ip: None,
+            connectable: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         peer_store.add_peer([1u8; 20], peer).unwrap();
         
-        ip_blacklist.ban(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
-        client_blacklist.ban("BadClient".to_string());
+        ip_blacklist.ban(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), None);
+        client_blacklist.ban("BadClient".to_string(), None);
         
-        let snapshot = metrics.get_snapshot(
-            &peer_store,
-            &user_cache,
-            &torrent_cache,
-            &ip_blacklist,
-            &client_blacklist,
-        );
+        let snapshot = metrics.get_snapshot(&SnapshotSources {
+            peer_store: &peer_store,
+            user_cache: &user_cache,
+            torrent_cache: &torrent_cache,
+            ip_blacklist: &ip_blacklist,
+            client_blacklist: &client_blacklist,
+            global_limiter: &GlobalAnnounceLimiter::new(0, 0),
+            wal_healthy: true,
+        });
         
         assert_eq!(snapshot.total_announces, 3);
         assert_eq!(snapshot.successful_announces, 2);
@@ -324,13 +574,15 @@ mod tests {
             metrics.increment_failed();
         }
         
-        let snapshot = metrics.get_snapshot(
-            &peer_store,
-            &user_cache,
-            &torrent_cache,
-            &ip_blacklist,
-            &client_blacklist,
-        );
+        let snapshot = metrics.get_snapshot(&SnapshotSources {
+            peer_store: &peer_store,
+            user_cache: &user_cache,
+            torrent_cache: &torrent_cache,
+            ip_blacklist: &ip_blacklist,
+            client_blacklist: &client_blacklist,
+            global_limiter: &GlobalAnnounceLimiter::new(0, 0),
+            wal_healthy: true,
+        });
         
         assert_eq!(snapshot.success_rate, 80.0);
     }
@@ -349,13 +601,15 @@ mod tests {
             metrics.increment_announces();
         }
         
-        let snapshot = metrics.get_snapshot(
-            &peer_store,
-            &user_cache,
-            &torrent_cache,
-            &ip_blacklist,
-            &client_blacklist,
-        );
+        let snapshot = metrics.get_snapshot(&SnapshotSources {
+            peer_store: &peer_store,
+            user_cache: &user_cache,
+            torrent_cache: &torrent_cache,
+            ip_blacklist: &ip_blacklist,
+            client_blacklist: &client_blacklist,
+            global_limiter: &GlobalAnnounceLimiter::new(0, 0),
+            wal_healthy: true,
+        });
         
         // Verify the calculation logic
         assert!(snapshot.uptime_seconds >= 0);
@@ -367,4 +621,84 @@ mod tests {
             assert!((snapshot.requests_per_second - expected_rps).abs() < 0.01);
         }
     }
+
+    #[test]
+    fn test_counters_persist_across_restart() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("metrics.json");
+        assert!(load_counters(&path).unwrap().is_none());
+
+        let previous = Metrics::new();
+        previous.lifetime_start.store(1000, Ordering::Relaxed);
+        for _ in 0..5 {
+            previous.increment_announces();
+        }
+        previous.increment_successful();
+        previous.increment_blocked();
+        previous.increment_event(Some(AnnounceEvent::Completed));
+        previous.increment_quota_rejections();
+        previous.increment_shed_announces();
+        previous.increment_shed_announces();
+        previous.save_counters(&path).unwrap();
+
+        let counters = load_counters(&path).unwrap().unwrap();
+        assert_eq!(counters, previous.persisted_counters());
+
+        let metrics = Metrics::new();
+        metrics.restore(&counters);
+        metrics.increment_announces();
+
+        let snapshot = metrics.get_snapshot(&SnapshotSources {
+            peer_store: &PeerStore::new(),
+            user_cache: &UserCache::new(),
+            torrent_cache: &TorrentCache::new(),
+            ip_blacklist: &IpBlacklist::new(),
+            client_blacklist: &ClientBlacklist::new(),
+            global_limiter: &GlobalAnnounceLimiter::new(0, 0),
+            wal_healthy: true,
+        });
+
+        // Totals are cumulative while uptime covers only this process
+        assert_eq!(snapshot.total_announces, 6);
+        assert_eq!(snapshot.successful_announces, 1);
+        assert_eq!(snapshot.blocked_requests, 1);
+        assert_eq!(snapshot.completed_count, 1);
+        assert_eq!(snapshot.quota_rejections, 1);
+        assert_eq!(snapshot.shed_announces, 2);
+        assert!(snapshot.uptime_seconds < 60);
+        assert!(snapshot.lifetime_seconds > snapshot.uptime_seconds);
+        assert_eq!(metrics.persisted_counters().lifetime_start, 1000);
+    }
+
+    #[test]
+    fn test_load_counters_without_event_counts() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("metrics.json");
+        std::fs::write(
+            &path,
+            br#"{"total_announces":3,"successful_announces":2,"failed_announces":1,"blocked_requests":0,"lifetime_start":1000}"#,
+        )
+        .unwrap();
+
+        let counters = load_counters(&path).unwrap().unwrap();
+        assert_eq!(counters.total_announces, 3);
+        assert_eq!(counters.update_count, 0);
+        assert_eq!(counters.quota_rejections, 0);
+        assert_eq!(counters.shed_announces, 0);
+    }
+
+    #[test]
+    fn test_load_counters_rejects_corrupt_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("metrics.json");
+        std::fs::write(&path, b"not json").unwrap();
+
+        assert!(load_counters(&path).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_resident_memory_reported_on_linux() {
+        assert!(resident_memory_bytes() > 0);
+    }
 }
@@ -1,61 +1,177 @@
+use crate::anti_cheat::violations::ViolationSnapshot;
+use crate::core::config::Config;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize)]
-pub struct ApiKeyQuery {
-    pub api_key: String,
-}
-
 #[derive(Deserialize)]
 pub struct TorrentAddQuery {
-    pub api_key: String,
     pub id: u32,
     pub info_hash: String,
     #[serde(default)]
     pub freeleech: u8,
+    /// Announce interval in seconds for this torrent, overriding `announce.interval`
+    pub announce_interval: Option<i64>,
+    /// Peer timeout in seconds for this torrent, overriding `performance.peer_timeout`
+    pub peer_timeout: Option<i64>,
 }
 
 #[derive(Deserialize)]
 pub struct TorrentRemoveQuery {
-    pub api_key: String,
+    pub info_hash: Option<String>,
+    pub id: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct TorrentUpdateQuery {
+    pub info_hash: Option<String>,
+    pub id: Option<u32>,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TorrentListQuery {
+    /// 1-based page number
+    #[serde(default = "default_page")]
+    pub page: usize,
+    #[serde(default = "default_per_page")]
+    pub per_page: usize,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    100
+}
+
+#[derive(Deserialize)]
+pub struct PeerDropQuery {
     pub info_hash: String,
+    pub peer_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct PeerConnectableQuery {
+    pub info_hash: String,
+    pub peer_id: String,
+    /// Omitted to reset the peer to unknown
+    pub connectable: Option<bool>,
 }
 
 #[derive(Deserialize)]
 pub struct UserAddQuery {
-    pub api_key: String,
     pub id: u32,
     pub passkey: String,
     pub class: u8,
 }
 
+/// Identifies a user to ban or unban by ID or passkey
+#[derive(Deserialize)]
+pub struct UserBanQuery {
+    pub id: Option<u32>,
+    pub passkey: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct UserRemoveQuery {
-    pub api_key: String,
     pub passkey: String,
 }
 
+#[derive(Deserialize)]
+pub struct AntiCheatUserQuery {
+    pub user_id: u32,
+}
+
 #[derive(Serialize)]
 pub struct SuccessResponse {
     pub success: bool,
     pub message: String,
 }
 
+#[derive(Serialize)]
+pub struct ReloadStatusResponse {
+    pub success: bool,
+    pub in_progress: bool,
+    /// Unix timestamp of the last successful reload
+    pub last_reload: Option<i64>,
+    pub users: usize,
+    pub torrents: usize,
+}
+
+#[derive(Serialize)]
+pub struct WalCompactResponse {
+    pub success: bool,
+    /// Operations in the rewritten WAL
+    pub operations: usize,
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct TorrentListEntry {
+    pub id: u32,
+    pub info_hash_hex: String,
+    pub freeleech: bool,
+    pub active: bool,
+    pub seeders: u32,
+    pub leechers: u32,
+}
+
+#[derive(Serialize)]
+pub struct TorrentListResponse {
+    pub success: bool,
+    /// Number of cached torrents across all pages
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub torrents: Vec<TorrentListEntry>,
+}
+
+#[derive(Serialize)]
+pub struct AntiCheatUserResponse {
+    pub success: bool,
+    pub user_id: u32,
+    /// Violations across all checks
+    pub total: u64,
+    pub violations: ViolationSnapshot,
+}
+
+#[derive(Serialize)]
+pub struct ConfigResponse<'a> {
+    pub success: bool,
+    /// Effective configuration with defaults filled in and secrets redacted
+    pub config: &'a Config,
+}
+
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub success: bool,
     pub error: String,
+    /// Request ID for correlating the error with server logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct IpBanQuery {
-    pub api_key: String,
     pub ip: String,
+    /// Ban duration in seconds; the ban is permanent when omitted
+    pub ttl: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct RateLimitResetQuery {
+    /// IP to reset; all windows are reset when omitted
+    pub ip: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct ClientBanQuery {
-    pub api_key: String,
     pub client: String,
+    /// Optional version constraint, e.g. `<2.0` or `>=1.3,<1.4`
+    pub version: Option<String>,
+    /// Ban duration in seconds; the ban is permanent when omitted
+    pub ttl: Option<i64>,
 }
 
 
@@ -1,4 +1,8 @@
-use std::net::IpAddr;
+use crate::validation::params::AnnounceEvent;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// First port an unprivileged process can listen on
+const FIRST_UNPRIVILEGED_PORT: u16 = 1024;
 
 /// Represents an active peer in the tracker
 #[derive(Clone, Debug)]
@@ -25,6 +29,18 @@ pub struct Peer {
     pub user_agent: String,
     /// Whether this peer is a seeder (left == 0)
     pub is_seeder: bool,
+    /// Most recent non-empty event sent in this session
+    pub last_event: Option<AnnounceEvent>,
+    /// Client `key` announce parameter, if sent
+    pub key: Option<String>,
+    /// Address in the other family than `ip`, declared by a dual-stack client
+    pub alt_ip: Option<IpAddr>,
+    /// Whether the peer accepts incoming connections, if known
+    pub connectable: Option<bool>,
+    /// Client announced support for encrypted connections
+    pub supportcrypto: bool,
+    /// Client announced it only accepts encrypted connections
+    pub requirecrypto: bool,
 }
 
 impl Peer {
@@ -52,6 +68,51 @@ impl Peer {
             last_announce,
             user_agent,
             is_seeder: left == 0,
+            last_event: None,
+            key: None,
+            alt_ip: None,
+            connectable: infer_connectable(port),
+            supportcrypto: false,
+            requirecrypto: false,
+        }
+    }
+
+    /// The peer's IPv4 address, from `ip` or `alt_ip`
+    pub fn ipv4(&self) -> Option<Ipv4Addr> {
+        [Some(self.ip), self.alt_ip].into_iter().flatten().find_map(|ip| match ip {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+    }
+
+    /// Share of the torrent downloaded, derived from `left` and the torrent size in bytes
+    ///
+    /// None while the size is unknown (0). `left` is client-reported, so the result is
+    /// clamped to [0, 1].
+    pub fn progress(&self, size: u64) -> Option<f64> {
+        if size == 0 {
+            return None;
         }
+        Some((1.0 - self.left as f64 / size as f64).clamp(0.0, 1.0))
+    }
+
+    /// The peer's IPv6 address, from `ip` or `alt_ip`
+    pub fn ipv6(&self) -> Option<Ipv6Addr> {
+        [Some(self.ip), self.alt_ip].into_iter().flatten().find_map(|ip| match ip {
+            IpAddr::V6(ip) => Some(ip),
+            IpAddr::V4(_) => None,
+        })
+    }
+}
+
+/// Guess whether a peer is connectable from its announced port
+///
+/// A torrent client does not run privileged, so a port below 1024 is almost always a
+/// made-up or misconfigured one. Any other port tells us nothing.
+pub fn infer_connectable(port: u16) -> Option<bool> {
+    if port < FIRST_UNPRIVILEGED_PORT {
+        Some(false)
+    } else {
+        None
     }
 }
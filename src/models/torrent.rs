@@ -8,6 +8,12 @@ pub struct Torrent {
     pub is_freeleech: bool,
     /// Whether this torrent is active
     pub is_active: bool,
+    /// Total size in bytes, 0 if unknown
+    pub size: u64,
+    /// Announce interval in seconds overriding `announce.interval` for this swarm
+    pub announce_interval: Option<i64>,
+    /// Seconds without an announce before a peer is reaped, overriding `performance.peer_timeout`
+    pub peer_timeout: Option<i64>,
 }
 
 impl Torrent {
@@ -17,6 +23,24 @@ impl Torrent {
             info_hash,
             is_freeleech,
             is_active,
+            size: 0,
+            announce_interval: None,
+            peer_timeout: None,
         }
     }
+
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_announce_interval(mut self, announce_interval: Option<i64>) -> Self {
+        self.announce_interval = announce_interval;
+        self
+    }
+
+    pub fn with_peer_timeout(mut self, peer_timeout: Option<i64>) -> Self {
+        self.peer_timeout = peer_timeout;
+        self
+    }
 }
@@ -1,17 +1,78 @@
-use dashmap::DashSet;
+use super::is_expired;
+use crate::utils::time::current_timestamp;
+use anyhow::{bail, Result};
+use dashmap::DashMap;
+use std::cmp::Ordering;
+
+/// Comparison operator in a version constraint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VersionOp {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+}
+
+/// A single `<op><version>` bound, e.g. `<2.0`
+#[derive(Debug, Clone)]
+struct VersionBound {
+    op: VersionOp,
+    version: Vec<u32>,
+}
+
+impl VersionBound {
+    fn matches(&self, version: &[u32]) -> bool {
+        let ordering = compare_versions(version, &self.version);
+        match self.op {
+            VersionOp::Less => ordering == Ordering::Less,
+            VersionOp::LessEqual => ordering != Ordering::Greater,
+            VersionOp::Greater => ordering == Ordering::Greater,
+            VersionOp::GreaterEqual => ordering != Ordering::Less,
+            VersionOp::Equal => ordering == Ordering::Equal,
+        }
+    }
+}
+
+/// A client name banned only for versions within a constraint
+#[derive(Debug, Clone)]
+struct VersionRule {
+    client: String,
+    bounds: Vec<VersionBound>,
+    expires_at: Option<i64>,
+}
+
+impl VersionRule {
+    fn is_banned(&self, user_agent: &[u8]) -> bool {
+        let Some(pos) = find_bytes(user_agent, self.client.as_bytes()) else {
+            return false;
+        };
+
+        // No parseable version: fall back to plain substring matching
+        match extract_version(&user_agent[pos + self.client.len()..]) {
+            Some(version) => self.bounds.iter().all(|bound| bound.matches(&version)),
+            None => true,
+        }
+    }
+}
 
 /// Client blacklist for banning malicious BitTorrent clients
 ///
 /// Client matching is performed using substring matching against User-Agent headers.
+/// Version rules additionally restrict a ban to a version range of a client
+/// (e.g. `Deluge` with `<2.0`). Bans may carry an expiry timestamp; expired
+/// entries are treated as not banned and pruned lazily.
 #[derive(Debug, Default)]
 pub struct ClientBlacklist {
-    clients: DashSet<String>,
+    clients: DashMap<String, Option<i64>>,
+    version_rules: DashMap<String, VersionRule>,
 }
 
 impl ClientBlacklist {
     pub fn new() -> Self {
         Self {
-            clients: DashSet::new(),
+            clients: DashMap::new(),
+            version_rules: DashMap::new(),
         }
     }
 
@@ -19,7 +80,7 @@ impl ClientBlacklist {
         let blacklist = Self::new();
         
         for client in clients {
-            blacklist.ban(client.clone());
+            blacklist.ban(client.clone(), None);
         }
         
         tracing::info!(count = blacklist.len(), "Initialized client blacklist with banned clients");
@@ -28,18 +89,42 @@ impl ClientBlacklist {
 
     /// Ban a BitTorrent client
     /// 
-    /// Adds the client string to the blacklist.
-    /// If the client is already banned, this is a no-op.
-    pub fn ban(&self, client: String) {
-        self.clients.insert(client);
+    /// Adds the client string to the blacklist until `expires_at` (Unix timestamp),
+    /// or permanently if `None`. Banning an already banned client replaces its expiry.
+    pub fn ban(&self, client: String, expires_at: Option<i64>) {
+        self.clients.insert(client, expires_at);
+    }
+
+    /// Ban a version range of a BitTorrent client
+    /// 
+    /// `constraint` is a comma-separated list of bounds that must all hold,
+    /// e.g. `<2.0` or `>=1.3,<1.4`. The rule is listed as `"<client> <constraint>"`.
+    /// User-Agents containing the client without a parseable version are banned.
+    /// `expires_at` works as in `ban`.
+    pub fn ban_version(&self, client: String, constraint: &str, expires_at: Option<i64>) -> Result<()> {
+        if client.is_empty() {
+            bail!("Client name must not be empty");
+        }
+
+        let bounds = parse_constraint(constraint)?;
+        let key = rule_key(&client, constraint);
+        self.version_rules.insert(key, VersionRule { client, bounds, expires_at });
+
+        Ok(())
     }
 
     /// Unban a BitTorrent client
     /// 
-    /// Removes the client string from the blacklist.
+    /// Removes the client string or version rule (as returned by `list`) from the blacklist.
     /// If the client is not banned, this is a no-op.
     pub fn unban(&self, client: &str) {
         self.clients.remove(client);
+        self.version_rules.remove(client);
+    }
+
+    /// Remove a version rule added with `ban_version`
+    pub fn unban_version(&self, client: &str, constraint: &str) {
+        self.version_rules.remove(&rule_key(client, constraint));
     }
 
     /// Check if a BitTorrent client is banned
@@ -48,11 +133,37 @@ impl ClientBlacklist {
     /// Returns true if the user_agent contains any banned client string.
     /// 
     /// # Arguments
-    /// * `user_agent` - The User-Agent header from the BitTorrent client
-    pub fn is_banned(&self, user_agent: &str) -> bool {
-        self.clients.iter().any(|entry| {
-            user_agent.contains(entry.key().as_str())
-        })
+    /// * `user_agent` - The raw User-Agent header bytes from the BitTorrent client.
+    ///   Matching works on bytes so invalid UTF-8 cannot hide a banned client.
+    pub fn is_banned(&self, user_agent: impl AsRef<[u8]>) -> bool {
+        let user_agent = user_agent.as_ref();
+        let now = current_timestamp();
+        let mut expired_clients = Vec::new();
+        let mut expired_rules = Vec::new();
+
+        let banned = self.clients.iter().any(|entry| {
+            if is_expired(*entry.value(), now) {
+                expired_clients.push(entry.key().clone());
+                return false;
+            }
+            find_bytes(user_agent, entry.key().as_bytes()).is_some()
+        }) || self.version_rules.iter().any(|entry| {
+            if is_expired(entry.value().expires_at, now) {
+                expired_rules.push(entry.key().clone());
+                return false;
+            }
+            entry.value().is_banned(user_agent)
+        });
+
+        // Removing while iterating would deadlock on the shard locks
+        for client in expired_clients {
+            self.clients.remove_if(&client, |_, expires_at| is_expired(*expires_at, now));
+        }
+        for rule in expired_rules {
+            self.version_rules.remove_if(&rule, |_, rule| is_expired(rule.expires_at, now));
+        }
+
+        banned
     }
 
     /// List all banned client strings
@@ -60,21 +171,122 @@ impl ClientBlacklist {
     /// Returns a vector of all client strings in the blacklist.
     /// The order is not guaranteed.
     pub fn list(&self) -> Vec<String> {
-        self.clients.iter().map(|entry| entry.key().clone()).collect()
+        let now = current_timestamp();
+        self.clients
+            .iter()
+            .filter(|entry| !is_expired(*entry.value(), now))
+            .map(|entry| entry.key().clone())
+            .chain(
+                self.version_rules
+                    .iter()
+                    .filter(|entry| !is_expired(entry.value().expires_at, now))
+                    .map(|entry| entry.key().clone()),
+            )
+            .collect()
     }
 
-    /// Get the total number of banned clients
+    /// Get the total number of banned clients, excluding expired bans
     pub fn len(&self) -> usize {
-        self.clients.len()
+        self.list().len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.clients.is_empty()
+        self.len() == 0
     }
 
     pub fn clear(&self) {
         self.clients.clear();
+        self.version_rules.clear();
+    }
+}
+
+fn rule_key(client: &str, constraint: &str) -> String {
+    let constraint: String = constraint.chars().filter(|c| !c.is_whitespace()).collect();
+    format!("{} {}", client, constraint)
+}
+
+/// Parse a dotted numeric version such as `2.0.1`
+fn parse_version(version: &str) -> Option<Vec<u32>> {
+    version
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Parse a constraint such as `<2.0` or `>=1.3,<1.4`
+fn parse_constraint(constraint: &str) -> Result<Vec<VersionBound>> {
+    let mut bounds = Vec::new();
+
+    for part in constraint.split(',') {
+        let part = part.trim();
+        let (op, version) = if let Some(v) = part.strip_prefix("<=") {
+            (VersionOp::LessEqual, v)
+        } else if let Some(v) = part.strip_prefix(">=") {
+            (VersionOp::GreaterEqual, v)
+        } else if let Some(v) = part.strip_prefix('<') {
+            (VersionOp::Less, v)
+        } else if let Some(v) = part.strip_prefix('>') {
+            (VersionOp::Greater, v)
+        } else if let Some(v) = part.strip_prefix('=') {
+            (VersionOp::Equal, v)
+        } else {
+            bail!("Invalid version constraint '{}': expected one of <, <=, >, >=, =", part);
+        };
+
+        let Some(version) = parse_version(version.trim()) else {
+            bail!("Invalid version in constraint '{}'", part);
+        };
+
+        bounds.push(VersionBound { op, version });
     }
+
+    Ok(bounds)
+}
+
+/// Position of the first occurrence of `needle` in `haystack`
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Extract the version that follows a client name in a User-Agent,
+/// e.g. `/2.0.3 libtorrent/...` or ` v1.3.15`
+fn extract_version(rest: &[u8]) -> Option<Vec<u32>> {
+    let start = rest
+        .iter()
+        .position(|b| !matches!(b, b'/' | b' ' | b'-' | b'_'))
+        .unwrap_or(rest.len());
+    let rest = &rest[start..];
+    let rest = rest.strip_prefix(b"v").or_else(|| rest.strip_prefix(b"V")).unwrap_or(rest);
+
+    let end = rest
+        .iter()
+        .position(|b| !b.is_ascii_digit() && *b != b'.')
+        .unwrap_or(rest.len());
+
+    // Only ASCII digits and dots remain, so this is always valid UTF-8
+    let version = std::str::from_utf8(&rest[..end]).ok()?.trim_end_matches('.');
+
+    if version.is_empty() {
+        return None;
+    }
+
+    parse_version(version)
+}
+
+/// Compare versions numerically, treating missing components as 0 (2.0 == 2.0.0)
+fn compare_versions(a: &[u32], b: &[u32]) -> Ordering {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| {
+            let x = a.get(i).copied().unwrap_or(0);
+            let y = b.get(i).copied().unwrap_or(0);
+            x.cmp(&y)
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
 }
 
 
@@ -86,7 +298,7 @@ mod tests {
     fn test_ban_client() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient".to_string());
+        blacklist.ban("BadClient".to_string(), None);
         assert!(blacklist.is_banned("BadClient/1.0"));
     }
 
@@ -94,7 +306,7 @@ mod tests {
     fn test_unban_client() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient".to_string());
+        blacklist.ban("BadClient".to_string(), None);
         assert!(blacklist.is_banned("BadClient/1.0"));
         
         blacklist.unban("BadClient");
@@ -112,7 +324,7 @@ mod tests {
     fn test_is_banned_substring_match() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient".to_string());
+        blacklist.ban("BadClient".to_string(), None);
         
         // Should match any user agent containing "BadClient"
         assert!(blacklist.is_banned("BadClient/1.0"));
@@ -129,8 +341,8 @@ mod tests {
     fn test_list_clients() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient1".to_string());
-        blacklist.ban("BadClient2".to_string());
+        blacklist.ban("BadClient1".to_string(), None);
+        blacklist.ban("BadClient2".to_string(), None);
         
         let list = blacklist.list();
         assert_eq!(list.len(), 2);
@@ -144,11 +356,11 @@ mod tests {
         assert!(blacklist.is_empty());
         assert_eq!(blacklist.len(), 0);
         
-        blacklist.ban("BadClient".to_string());
+        blacklist.ban("BadClient".to_string(), None);
         assert!(!blacklist.is_empty());
         assert_eq!(blacklist.len(), 1);
         
-        blacklist.ban("AnotherBadClient".to_string());
+        blacklist.ban("AnotherBadClient".to_string(), None);
         assert_eq!(blacklist.len(), 2);
     }
 
@@ -156,8 +368,8 @@ mod tests {
     fn test_clear() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient1".to_string());
-        blacklist.ban("BadClient2".to_string());
+        blacklist.ban("BadClient1".to_string(), None);
+        blacklist.ban("BadClient2".to_string(), None);
         assert_eq!(blacklist.len(), 2);
         
         blacklist.clear();
@@ -170,8 +382,8 @@ mod tests {
     fn test_ban_duplicate() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient".to_string());
-        blacklist.ban("BadClient".to_string());
+        blacklist.ban("BadClient".to_string(), None);
+        blacklist.ban("BadClient".to_string(), None);
         
         assert_eq!(blacklist.len(), 1);
         assert!(blacklist.is_banned("BadClient/1.0"));
@@ -214,7 +426,7 @@ mod tests {
     fn test_case_sensitive_matching() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient".to_string());
+        blacklist.ban("BadClient".to_string(), None);
         
         // Substring matching is case-sensitive
         assert!(blacklist.is_banned("BadClient/1.0"));
@@ -226,9 +438,9 @@ mod tests {
     fn test_multiple_banned_clients() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("BadClient".to_string());
-        blacklist.ban("MaliciousBot".to_string());
-        blacklist.ban("FakeSeeder".to_string());
+        blacklist.ban("BadClient".to_string(), None);
+        blacklist.ban("MaliciousBot".to_string(), None);
+        blacklist.ban("FakeSeeder".to_string(), None);
         
         assert!(blacklist.is_banned("BadClient/1.0"));
         assert!(blacklist.is_banned("MaliciousBot"));
@@ -240,7 +452,7 @@ mod tests {
     fn test_partial_match() {
         let blacklist = ClientBlacklist::new();
         
-        blacklist.ban("Thunder".to_string());
+        blacklist.ban("Thunder".to_string(), None);
         
         // Should match any user agent containing "Thunder"
         assert!(blacklist.is_banned("Thunder"));
@@ -248,4 +460,103 @@ mod tests {
         assert!(blacklist.is_banned("Thunder/5.9.0"));
         assert!(!blacklist.is_banned("qBittorrent"));
     }
+
+    #[test]
+    fn test_ban_version_below() {
+        let blacklist = ClientBlacklist::new();
+        
+        blacklist.ban_version("Deluge".to_string(), "<2.0", None).unwrap();
+        
+        assert!(blacklist.is_banned("Deluge/1.3.15 libtorrent/1.1.5.0"));
+        assert!(blacklist.is_banned("Deluge 1.9"));
+        assert!(!blacklist.is_banned("Deluge/2.0"));
+        assert!(!blacklist.is_banned("Deluge/2.1.1 libtorrent/2.0.5.0"));
+        assert!(!blacklist.is_banned("qBittorrent/1.0.0"));
+    }
+
+    #[test]
+    fn test_ban_version_range() {
+        let blacklist = ClientBlacklist::new();
+        
+        blacklist.ban_version("qBittorrent".to_string(), ">=4.1, <4.2", None).unwrap();
+        
+        assert!(blacklist.is_banned("qBittorrent/4.1.9"));
+        assert!(blacklist.is_banned("qBittorrent/v4.1"));
+        assert!(!blacklist.is_banned("qBittorrent/4.0.4"));
+        assert!(!blacklist.is_banned("qBittorrent/4.2.0"));
+    }
+
+    #[test]
+    fn test_ban_version_unparseable_falls_back_to_substring() {
+        let blacklist = ClientBlacklist::new();
+        
+        blacklist.ban_version("Deluge".to_string(), "<2.0", None).unwrap();
+        
+        assert!(blacklist.is_banned("Deluge"));
+        assert!(blacklist.is_banned("Deluge/dev"));
+    }
+
+    #[test]
+    fn test_ban_version_invalid_constraint() {
+        let blacklist = ClientBlacklist::new();
+        
+        assert!(blacklist.ban_version("Deluge".to_string(), "2.0", None).is_err());
+        assert!(blacklist.ban_version("Deluge".to_string(), "<two", None).is_err());
+        assert!(blacklist.ban_version("".to_string(), "<2.0", None).is_err());
+        assert!(blacklist.is_empty());
+    }
+
+    #[test]
+    fn test_version_rule_list_and_unban() {
+        let blacklist = ClientBlacklist::new();
+        
+        blacklist.ban("BadClient".to_string(), None);
+        blacklist.ban_version("Deluge".to_string(), ">= 1.0, < 2.0", None).unwrap();
+        
+        let list = blacklist.list();
+        assert_eq!(blacklist.len(), 2);
+        assert!(list.contains(&"Deluge >=1.0,<2.0".to_string()));
+        
+        blacklist.unban_version("Deluge", ">=1.0,<2.0");
+        assert!(!blacklist.is_banned("Deluge/1.3"));
+        
+        blacklist.ban_version("Deluge".to_string(), "<2.0", None).unwrap();
+        blacklist.unban("Deluge <2.0");
+        assert!(!blacklist.is_banned("Deluge/1.3"));
+        assert_eq!(blacklist.len(), 1);
+    }
+
+    #[test]
+    fn test_is_banned_non_utf8_user_agent() {
+        let blacklist = ClientBlacklist::new();
+        
+        blacklist.ban("BadClient".to_string(), None);
+        blacklist.ban_version("Deluge".to_string(), "<2.0", None).unwrap();
+        
+        // Invalid UTF-8 around the client name must not hide it
+        assert!(blacklist.is_banned(b"BadClient/1.0 \xff\xfe"));
+        assert!(blacklist.is_banned(b"\xc3\x28Deluge/1.3"));
+        assert!(!blacklist.is_banned(b"Deluge/2.0 \xff"));
+        assert!(!blacklist.is_banned(b"GoodClient/1.0 \xff"));
+    }
+
+    #[test]
+    fn test_ban_with_expiry() {
+        let blacklist = ClientBlacklist::new();
+        let now = current_timestamp();
+
+        blacklist.ban("BadClient".to_string(), Some(now + 3600));
+        blacklist.ban_version("Deluge".to_string(), "<2.0", Some(now + 3600)).unwrap();
+        assert!(blacklist.is_banned("BadClient/1.0"));
+        assert!(blacklist.is_banned("Deluge/1.3"));
+
+        blacklist.ban("BadClient".to_string(), Some(now - 1));
+        blacklist.ban_version("Deluge".to_string(), "<2.0", Some(now - 1)).unwrap();
+        assert!(blacklist.list().is_empty());
+        assert!(!blacklist.is_banned("BadClient/1.0"));
+        assert!(!blacklist.is_banned("Deluge/1.3"));
+
+        // Both expired entries were pruned by the lookups
+        assert!(blacklist.is_empty());
+    }
 }
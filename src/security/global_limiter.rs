@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill_ms: i64,
+    last_shed_ms: Option<i64>,
+}
+
+/// Tracker-wide token bucket for announces
+///
+/// Per-IP limiting does nothing against a distributed flood; this caps the total
+/// announce rate so the tracker stays responsive. Requests over the rate are shed
+/// with a retry-later response rather than failed. It also counts every announce so
+/// the current rate can be reported even when no limit is configured; counting is
+/// lock-free, so with no limit announces never take the bucket lock.
+#[derive(Debug)]
+pub struct GlobalAnnounceLimiter {
+    /// Announces per second admitted on average (0 disables shedding)
+    rate: u32,
+    /// Announces admitted at once after an idle period
+    burst: u32,
+    bucket: Mutex<Bucket>,
+    /// Start of the current one-second counting window
+    window_start_ms: AtomicI64,
+    window_count: AtomicU64,
+    /// Announces counted in the previous window
+    previous_count: AtomicU64,
+}
+
+impl GlobalAnnounceLimiter {
+    /// Create a limiter admitting `rate` announces per second with bursts of `burst`
+    ///
+    /// A `burst` of 0 uses `rate`; a `rate` of 0 never sheds.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        let burst = if burst == 0 { rate } else { burst };
+        Self {
+            rate,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                last_refill_ms: 0,
+                last_shed_ms: None,
+            }),
+            window_start_ms: AtomicI64::new(0),
+            window_count: AtomicU64::new(0),
+            previous_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Count an announce and take a token for it
+    ///
+    /// Returns false if the announce should be shed.
+    pub fn try_acquire(&self, now_ms: i64) -> bool {
+        self.count(now_ms);
+
+        if self.rate == 0 {
+            return true;
+        }
+
+        let mut bucket = self.bucket.lock().unwrap();
+        let refill = (now_ms - bucket.last_refill_ms).max(0) as f64 * self.rate as f64 / 1000.0;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst as f64);
+        bucket.last_refill_ms = now_ms;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            bucket.last_shed_ms = Some(now_ms);
+            false
+        }
+    }
+
+    /// Count an announce in the current window, rolling it over once a second has passed
+    ///
+    /// Only the caller that moves the window start rolls it over. Announces counted
+    /// concurrently with a rollover may land in either window, which the reported rate
+    /// tolerates.
+    fn count(&self, now_ms: i64) {
+        let window_start = self.window_start_ms.load(Ordering::Relaxed);
+        let elapsed = now_ms - window_start;
+        if elapsed >= 1000
+            && self
+                .window_start_ms
+                .compare_exchange(window_start, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            let count = self.window_count.swap(0, Ordering::Relaxed);
+            self.previous_count
+                .store(if elapsed < 2000 { count } else { 0 }, Ordering::Relaxed);
+        }
+        self.window_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Announces counted in the last full second
+    pub fn current_rate(&self, now_ms: i64) -> u64 {
+        match now_ms - self.window_start_ms.load(Ordering::Relaxed) {
+            elapsed if elapsed < 1000 => self.previous_count.load(Ordering::Relaxed),
+            elapsed if elapsed < 2000 => self.window_count.load(Ordering::Relaxed),
+            _ => 0,
+        }
+    }
+
+    /// Whether an announce was shed within the last second
+    pub fn is_shedding(&self, now_ms: i64) -> bool {
+        let bucket = self.bucket.lock().unwrap();
+        bucket.last_shed_ms.is_some_and(|last_shed| now_ms - last_shed < 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_limiter_never_sheds() {
+        let limiter = GlobalAnnounceLimiter::new(0, 0);
+        for _ in 0..1000 {
+            assert!(limiter.try_acquire(1_000_000));
+        }
+        assert!(!limiter.is_shedding(1_000_000));
+        assert_eq!(limiter.current_rate(1_000_500), 0);
+        assert_eq!(limiter.current_rate(1_001_500), 1000);
+    }
+
+    #[test]
+    fn test_sheds_over_burst_and_refills() {
+        let limiter = GlobalAnnounceLimiter::new(10, 5);
+        let now = 1_000_000;
+
+        for _ in 0..5 {
+            assert!(limiter.try_acquire(now));
+        }
+        assert!(!limiter.try_acquire(now));
+        assert!(limiter.is_shedding(now + 999));
+        assert!(!limiter.is_shedding(now + 1000));
+
+        // 10 per second refills one token every 100ms
+        assert!(limiter.try_acquire(now + 100));
+        assert!(!limiter.try_acquire(now + 100));
+    }
+
+    #[test]
+    fn test_current_rate() {
+        let limiter = GlobalAnnounceLimiter::new(0, 0);
+        let now = 1_000_000;
+
+        for _ in 0..7 {
+            limiter.try_acquire(now);
+        }
+        // The window that just closed is reported for the following second
+        assert_eq!(limiter.current_rate(now + 1500), 7);
+
+        limiter.try_acquire(now + 1500);
+        assert_eq!(limiter.current_rate(now + 1600), 7);
+        assert_eq!(limiter.current_rate(now + 2600), 1);
+        assert_eq!(limiter.current_rate(now + 5000), 0);
+    }
+}
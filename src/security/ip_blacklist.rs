@@ -1,17 +1,24 @@
-use dashmap::DashSet;
+use super::is_expired;
+use crate::utils::time::current_timestamp;
+use dashmap::DashMap;
+use std::hash::Hash;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
+/// IP blacklist
+///
+/// Each entry carries an optional expiry timestamp; permanent bans have none.
+/// Expired entries are treated as not banned and pruned lazily when looked up.
 #[derive(Debug, Default)]
 pub struct IpBlacklist {
-    ipv4: DashSet<Ipv4Addr>,
-    ipv6: DashSet<Ipv6Addr>,
+    ipv4: DashMap<Ipv4Addr, Option<i64>>,
+    ipv6: DashMap<Ipv6Addr, Option<i64>>,
 }
 
 impl IpBlacklist {
     pub fn new() -> Self {
         Self {
-            ipv4: DashSet::new(),
-            ipv6: DashSet::new(),
+            ipv4: DashMap::new(),
+            ipv6: DashMap::new(),
         }
     }
 
@@ -21,7 +28,7 @@ impl IpBlacklist {
         
         for ip_str in ips {
             match ip_str.parse::<IpAddr>() {
-                Ok(ip) => blacklist.ban(ip),
+                Ok(ip) => blacklist.ban(ip, None),
                 Err(e) => {
                     tracing::warn!(ip = %ip_str, error = %e, "Failed to parse IP address in config");
                 }
@@ -33,13 +40,16 @@ impl IpBlacklist {
     }
 
 
-    pub fn ban(&self, ip: IpAddr) {
+    /// Ban an IP address until `expires_at` (Unix timestamp), or permanently if `None`
+    ///
+    /// Banning an already banned IP replaces its expiry.
+    pub fn ban(&self, ip: IpAddr, expires_at: Option<i64>) {
         match ip {
             IpAddr::V4(ipv4) => {
-                self.ipv4.insert(ipv4);
+                self.ipv4.insert(ipv4, expires_at);
             }
             IpAddr::V6(ipv6) => {
-                self.ipv6.insert(ipv6);
+                self.ipv6.insert(ipv6, expires_at);
             }
         }
     }
@@ -58,29 +68,32 @@ impl IpBlacklist {
 
 
     pub fn is_banned(&self, ip: IpAddr) -> bool {
+        let now = current_timestamp();
         match ip {
-            IpAddr::V4(ipv4) => self.ipv4.contains(&ipv4),
-            IpAddr::V6(ipv6) => self.ipv6.contains(&ipv6),
+            IpAddr::V4(ipv4) => is_banned_in(&self.ipv4, ipv4, now),
+            IpAddr::V6(ipv6) => is_banned_in(&self.ipv6, ipv6, now),
         }
     }
 
 
     pub fn list_ipv4(&self) -> Vec<Ipv4Addr> {
-        self.ipv4.iter().map(|entry| *entry.key()).collect()
+        active_keys(&self.ipv4, current_timestamp())
     }
 
 
     pub fn list_ipv6(&self) -> Vec<Ipv6Addr> {
-        self.ipv6.iter().map(|entry| *entry.key()).collect()
+        active_keys(&self.ipv6, current_timestamp())
     }
 
+    /// Number of bans in effect, excluding expired ones not yet pruned
     pub fn len(&self) -> usize {
-        self.ipv4.len() + self.ipv6.len()
+        let now = current_timestamp();
+        active_count(&self.ipv4, now) + active_count(&self.ipv6, now)
     }
 
 
     pub fn is_empty(&self) -> bool {
-        self.ipv4.is_empty() && self.ipv6.is_empty()
+        self.len() == 0
     }
 
 
@@ -90,6 +103,32 @@ impl IpBlacklist {
     }
 }
 
+/// Look up a ban, removing it if it has expired
+fn is_banned_in<K: Eq + Hash + Copy>(bans: &DashMap<K, Option<i64>>, ip: K, now: i64) -> bool {
+    let Some(expires_at) = bans.get(&ip).map(|entry| *entry.value()) else {
+        return false;
+    };
+
+    if is_expired(expires_at, now) {
+        // Re-check under the write lock in case the ban was renewed meanwhile
+        bans.remove_if(&ip, |_, expires_at| is_expired(*expires_at, now));
+        return false;
+    }
+
+    true
+}
+
+fn active_count<K: Eq + Hash>(bans: &DashMap<K, Option<i64>>, now: i64) -> usize {
+    bans.iter().filter(|entry| !is_expired(*entry.value(), now)).count()
+}
+
+fn active_keys<K: Eq + Hash + Copy>(bans: &DashMap<K, Option<i64>>, now: i64) -> Vec<K> {
+    bans.iter()
+        .filter(|entry| !is_expired(*entry.value(), now))
+        .map(|entry| *entry.key())
+        .collect()
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -100,7 +139,7 @@ mod tests {
         let blacklist = IpBlacklist::new();
         let ip = "192.168.1.1".parse::<Ipv4Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V4(ip));
+        blacklist.ban(IpAddr::V4(ip), None);
         assert!(blacklist.is_banned(IpAddr::V4(ip)));
     }
 
@@ -109,7 +148,7 @@ mod tests {
         let blacklist = IpBlacklist::new();
         let ip = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V6(ip));
+        blacklist.ban(IpAddr::V6(ip), None);
         assert!(blacklist.is_banned(IpAddr::V6(ip)));
     }
 
@@ -118,7 +157,7 @@ mod tests {
         let blacklist = IpBlacklist::new();
         let ip = "192.168.1.1".parse::<Ipv4Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V4(ip));
+        blacklist.ban(IpAddr::V4(ip), None);
         assert!(blacklist.is_banned(IpAddr::V4(ip)));
 
         blacklist.unban(IpAddr::V4(ip));
@@ -130,7 +169,7 @@ mod tests {
         let blacklist = IpBlacklist::new();
         let ip = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V6(ip));
+        blacklist.ban(IpAddr::V6(ip), None);
         assert!(blacklist.is_banned(IpAddr::V6(ip)));
 
         blacklist.unban(IpAddr::V6(ip));
@@ -151,8 +190,8 @@ mod tests {
         let ip1 = "192.168.1.1".parse::<Ipv4Addr>().unwrap();
         let ip2 = "10.0.0.1".parse::<Ipv4Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V4(ip1));
-        blacklist.ban(IpAddr::V4(ip2));
+        blacklist.ban(IpAddr::V4(ip1), None);
+        blacklist.ban(IpAddr::V4(ip2), None);
 
         let list = blacklist.list_ipv4();
         assert_eq!(list.len(), 2);
@@ -166,8 +205,8 @@ mod tests {
         let ip1 = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
         let ip2 = "2001:db8::2".parse::<Ipv6Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V6(ip1));
-        blacklist.ban(IpAddr::V6(ip2));
+        blacklist.ban(IpAddr::V6(ip1), None);
+        blacklist.ban(IpAddr::V6(ip2), None);
 
         let list = blacklist.list_ipv6();
         assert_eq!(list.len(), 2);
@@ -184,11 +223,11 @@ mod tests {
         let ipv4 = "192.168.1.1".parse::<Ipv4Addr>().unwrap();
         let ipv6 = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V4(ipv4));
+        blacklist.ban(IpAddr::V4(ipv4), None);
         assert!(!blacklist.is_empty());
         assert_eq!(blacklist.len(), 1);
 
-        blacklist.ban(IpAddr::V6(ipv6));
+        blacklist.ban(IpAddr::V6(ipv6), None);
         assert_eq!(blacklist.len(), 2);
     }
 
@@ -198,8 +237,8 @@ mod tests {
         let ipv4 = "192.168.1.1".parse::<Ipv4Addr>().unwrap();
         let ipv6 = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V4(ipv4));
-        blacklist.ban(IpAddr::V6(ipv6));
+        blacklist.ban(IpAddr::V4(ipv4), None);
+        blacklist.ban(IpAddr::V6(ipv6), None);
         assert_eq!(blacklist.len(), 2);
 
         blacklist.clear();
@@ -213,8 +252,8 @@ mod tests {
         let blacklist = IpBlacklist::new();
         let ip = "192.168.1.1".parse::<Ipv4Addr>().unwrap();
 
-        blacklist.ban(IpAddr::V4(ip));
-        blacklist.ban(IpAddr::V4(ip));
+        blacklist.ban(IpAddr::V4(ip), None);
+        blacklist.ban(IpAddr::V4(ip), None);
 
         assert_eq!(blacklist.len(), 1);
         assert!(blacklist.is_banned(IpAddr::V4(ip)));
@@ -269,4 +308,26 @@ mod tests {
         
         assert!(blacklist.is_empty());
     }
+
+    #[test]
+    fn test_ban_with_expiry() {
+        let blacklist = IpBlacklist::new();
+        let ip = IpAddr::V4("192.168.1.1".parse::<Ipv4Addr>().unwrap());
+        let now = current_timestamp();
+
+        blacklist.ban(ip, Some(now + 3600));
+        assert!(blacklist.is_banned(ip));
+        assert_eq!(blacklist.list_ipv4().len(), 1);
+
+        // Expired bans no longer apply and are pruned on lookup
+        blacklist.ban(ip, Some(now - 1));
+        assert!(blacklist.list_ipv4().is_empty());
+        assert!(!blacklist.is_banned(ip));
+        assert!(blacklist.is_empty());
+
+        // A permanent ban replaces an expiring one
+        blacklist.ban(ip, Some(now - 1));
+        blacklist.ban(ip, None);
+        assert!(blacklist.is_banned(ip));
+    }
 }
@@ -1,3 +1,10 @@
 pub mod ip_blacklist;
 pub mod client_blacklist;
+pub mod user_blacklist;
 pub mod rate_limiter;
+pub mod global_limiter;
+
+/// Whether a ban expiring at `expires_at` (None = permanent) has lapsed at `now`
+fn is_expired(expires_at: Option<i64>, now: i64) -> bool {
+    expires_at.is_some_and(|expires_at| expires_at <= now)
+}
@@ -1,30 +1,79 @@
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use rand::Rng;
 use std::net::IpAddr;
-use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
-
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicUsize, Ordering};
+
+/// Length of a rate limit window in seconds
+const WINDOW_SECS: i64 = 60;
+
+/// Windows compared when picking one to evict from a full map
+const EVICTION_SAMPLE: usize = 8;
+
+/// What to do with a request from a new IP once the rate limit map is full
+/// and no expired windows can be dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitOverflow {
+    /// Evict the IP whose window started longest ago, among a small sample
+    EvictOldest,
+    /// Reject the request as rate limited
+    Deny,
+    /// Let the request through without tracking it
+    Allow,
+}
 
 pub struct RateLimiter {
     requests: DashMap<IpAddr, (AtomicU32, AtomicI64)>,
+    /// Number of tracked IPs, kept alongside the map since `DashMap::len` locks every shard
+    entries: AtomicUsize,
+    /// When expired windows were last dropped
+    last_prune: AtomicI64,
     max_requests_per_minute: u32,
+    /// Most IPs tracked at once (0 means unbounded)
+    max_entries: usize,
+    overflow: RateLimitOverflow,
 }
 
 impl RateLimiter {
+    #[cfg(test)]
     pub fn new(max_requests_per_minute: u32) -> Self {
+        Self::with_max_entries(max_requests_per_minute, 0, RateLimitOverflow::EvictOldest)
+    }
+
+    /// Create a rate limiter tracking at most `max_entries` IPs (0 means unbounded)
+    pub fn with_max_entries(max_requests_per_minute: u32, max_entries: usize, overflow: RateLimitOverflow) -> Self {
         Self {
             requests: DashMap::new(),
+            entries: AtomicUsize::new(0),
+            last_prune: AtomicI64::new(i64::MIN),
             max_requests_per_minute,
+            max_entries,
+            overflow,
         }
     }
 
     pub fn check_and_increment(&self, ip: IpAddr, current_time: i64) -> bool {
-        let entry = self.requests.entry(ip).or_insert_with(|| {
-            (AtomicU32::new(0), AtomicI64::new(current_time))
-        });
+        if self.max_entries > 0
+            && self.tracked_ips() >= self.max_entries
+            && !self.requests.contains_key(&ip)
+        {
+            if let Some(allowed) = self.make_room(current_time) {
+                return allowed;
+            }
+        }
+
+        let entry = match self.requests.entry(ip) {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => {
+                self.entries.fetch_add(1, Ordering::Relaxed);
+                entry.insert((AtomicU32::new(0), AtomicI64::new(current_time)))
+            }
+        };
 
         let (count, window_start) = entry.value();
         let window_start_time = window_start.load(Ordering::Relaxed);
         
-        if current_time - window_start_time >= 60 {
+        if current_time - window_start_time >= WINDOW_SECS {
             window_start.store(current_time, Ordering::Relaxed);
             count.store(1, Ordering::Relaxed);
             return true;
@@ -35,21 +84,91 @@ impl RateLimiter {
         current_count <= self.max_requests_per_minute
     }
 
-    pub fn cleanup_old_entries(&self, current_time: i64) {
+    /// Drop windows that started a minute or more ago, returning how many were removed
+    pub fn cleanup_old_entries(&self, current_time: i64) -> usize {
+        self.last_prune.store(current_time, Ordering::Relaxed);
+        self.remove_where(|window_start| current_time - window_start >= WINDOW_SECS)
+    }
+
+    /// Free a slot for a new IP in a full map
+    ///
+    /// Expired windows are dropped first, at most once per window length since every
+    /// new IP arriving at a full map ends up here. If the map is still full the overflow
+    /// policy applies: returns `None` when a slot was freed, or the verdict for an
+    /// untracked request.
+    fn make_room(&self, current_time: i64) -> Option<bool> {
+        let last_prune = self.last_prune.load(Ordering::Relaxed);
+        if current_time.saturating_sub(last_prune) >= WINDOW_SECS
+            && self
+                .last_prune
+                .compare_exchange(last_prune, current_time, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.cleanup_old_entries(current_time);
+            if self.tracked_ips() < self.max_entries {
+                return None;
+            }
+        }
+
+        match self.overflow {
+            RateLimitOverflow::EvictOldest => {
+                self.evict_sampled();
+                None
+            }
+            RateLimitOverflow::Deny => Some(false),
+            RateLimitOverflow::Allow => Some(true),
+        }
+    }
+
+    /// Evict the oldest window among a sample from a randomly chosen shard
+    fn evict_sampled(&self) {
+        let shards = self.requests.shards();
+        let start = rand::rng().random_range(0..shards.len());
+        let oldest = (0..shards.len()).find_map(|offset| {
+            let shard = shards[(start + offset) % shards.len()].read();
+            shard
+                .iter()
+                .take(EVICTION_SAMPLE)
+                .min_by_key(|(_, window)| window.get().1.load(Ordering::Relaxed))
+                .map(|(ip, _)| *ip)
+        });
+        if let Some(oldest) = oldest {
+            self.reset(oldest);
+        }
+    }
+
+    /// Remove the windows whose start time matches `expired`, returning how many were removed
+    fn remove_where(&self, expired: impl Fn(i64) -> bool) -> usize {
+        let mut removed = 0;
         self.requests.retain(|_, (_, window_start)| {
-            current_time - window_start.load(Ordering::Relaxed) < 60
+            let keep = !expired(window_start.load(Ordering::Relaxed));
+            removed += usize::from(!keep);
+            keep
         });
+        self.entries.fetch_sub(removed, Ordering::Relaxed);
+        removed
     }
 
+    /// Remove the rate limit window for an IP so its next request starts fresh
+    ///
+    /// Returns false if the IP had no active window.
+    pub fn reset(&self, ip: IpAddr) -> bool {
+        let removed = self.requests.remove(&ip).is_some();
+        if removed {
+            self.entries.fetch_sub(1, Ordering::Relaxed);
+        }
+        removed
+    }
 
-    #[allow(dead_code)]
-    pub fn len(&self) -> usize {
-        self.requests.len()
+    /// Remove all rate limit windows, returning how many were cleared
+    pub fn reset_all(&self) -> usize {
+        self.remove_where(|_| true)
     }
 
-    #[allow(dead_code)]
-    pub fn is_empty(&self) -> bool {
-        self.requests.is_empty()
+
+    /// Number of IPs with a rate limit window
+    pub fn tracked_ips(&self) -> usize {
+        self.entries.load(Ordering::Relaxed)
     }
 }
 
@@ -58,6 +177,34 @@ mod tests {
     use super::*;
     use std::net::{Ipv4Addr, Ipv6Addr};
 
+    #[test]
+    fn test_rate_limiter_reset() {
+        let limiter = RateLimiter::new(1);
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let current_time = 1000;
+
+        assert!(limiter.check_and_increment(ip, current_time));
+        assert!(!limiter.check_and_increment(ip, current_time));
+
+        assert!(limiter.reset(ip));
+        assert!(limiter.check_and_increment(ip, current_time));
+
+        // Unknown IP has no window to reset
+        assert!(!limiter.reset(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+    }
+
+    #[test]
+    fn test_rate_limiter_reset_all() {
+        let limiter = RateLimiter::new(10);
+        let current_time = 1000;
+
+        limiter.check_and_increment(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)), current_time);
+        limiter.check_and_increment(IpAddr::V6(Ipv6Addr::LOCALHOST), current_time);
+
+        assert_eq!(limiter.reset_all(), 2);
+        assert_eq!(limiter.tracked_ips(), 0);
+    }
+
     #[test]
     fn test_rate_limiter_allows_first_request() {
         let limiter = RateLimiter::new(10);
@@ -109,7 +256,7 @@ mod tests {
         assert!(!limiter.check_and_increment(ip, current_time));
 
         // After 60 seconds, window should reset
-        let new_time = current_time + 60;
+        let new_time = current_time + WINDOW_SECS;
         assert!(limiter.check_and_increment(ip, new_time));
     }
 
@@ -150,13 +297,13 @@ mod tests {
         limiter.check_and_increment(ip1, current_time);
         limiter.check_and_increment(ip2, current_time + 30);
 
-        assert_eq!(limiter.len(), 2);
+        assert_eq!(limiter.tracked_ips(), 2);
 
         // Cleanup at time that makes ip1 old but ip2 recent
-        limiter.cleanup_old_entries(current_time + 70);
+        assert_eq!(limiter.cleanup_old_entries(current_time + 70), 1);
 
         // ip1 should be removed (70 seconds old), ip2 should remain (40 seconds old)
-        assert_eq!(limiter.len(), 1);
+        assert_eq!(limiter.tracked_ips(), 1);
     }
 
     #[test]
@@ -166,11 +313,11 @@ mod tests {
         let current_time = 1000;
 
         limiter.check_and_increment(ip, current_time);
-        assert_eq!(limiter.len(), 1);
+        assert_eq!(limiter.tracked_ips(), 1);
 
         // Cleanup after window expires
-        limiter.cleanup_old_entries(current_time + 100);
-        assert_eq!(limiter.len(), 0);
+        assert_eq!(limiter.cleanup_old_entries(current_time + 100), 1);
+        assert_eq!(limiter.tracked_ips(), 0);
     }
 
     #[test]
@@ -204,13 +351,76 @@ mod tests {
         assert!(!limiter.check_and_increment(ip, current_time));
     }
 
+    #[test]
+    fn test_max_entries_drops_expired_windows_first() {
+        let limiter = RateLimiter::with_max_entries(10, 2, RateLimitOverflow::Deny);
+        limiter.check_and_increment(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 1000);
+        limiter.check_and_increment(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 1030);
+
+        // 10.0.0.1's window has expired, so a new IP fits even under the deny policy
+        assert!(limiter.check_and_increment(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)), 1070));
+        assert_eq!(limiter.tracked_ips(), 2);
+    }
+
+    #[test]
+    fn test_max_entries_evict_oldest() {
+        let limiter = RateLimiter::with_max_entries(1, 2, RateLimitOverflow::EvictOldest);
+        let ip1 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let ip2 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let ip3 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        limiter.check_and_increment(ip1, 1000);
+        limiter.check_and_increment(ip2, 1010);
+
+        assert!(limiter.check_and_increment(ip3, 1020));
+        assert_eq!(limiter.tracked_ips(), 2);
+
+        // One of the existing windows made room for the new IP
+        assert!(limiter.reset(ip3));
+        assert!(limiter.reset(ip1) != limiter.reset(ip2));
+        assert_eq!(limiter.tracked_ips(), 0);
+    }
+
+    #[test]
+    fn test_max_entries_prunes_once_per_window() {
+        let limiter = RateLimiter::with_max_entries(10, 2, RateLimitOverflow::Deny);
+        limiter.check_and_increment(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 1000);
+        limiter.check_and_increment(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 1030);
+
+        // Drops 10.0.0.1's expired window
+        assert!(limiter.check_and_increment(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)), 1070));
+
+        // 10.0.0.2's window has expired too, but the map was pruned less than a window ago
+        let late = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4));
+        assert!(!limiter.check_and_increment(late, 1095));
+        // A window later both expired windows go and the new IP fits
+        assert!(limiter.check_and_increment(late, 1130));
+        assert_eq!(limiter.tracked_ips(), 1);
+    }
+
+    #[test]
+    fn test_max_entries_deny_and_allow() {
+        let existing = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let new_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+        let limiter = RateLimiter::with_max_entries(10, 1, RateLimitOverflow::Deny);
+        limiter.check_and_increment(existing, 1000);
+        assert!(!limiter.check_and_increment(new_ip, 1000));
+        // Tracked IPs are unaffected by the cap
+        assert!(limiter.check_and_increment(existing, 1000));
+
+        let limiter = RateLimiter::with_max_entries(10, 1, RateLimitOverflow::Allow);
+        limiter.check_and_increment(existing, 1000);
+        assert!(limiter.check_and_increment(new_ip, 1000));
+        assert_eq!(limiter.tracked_ips(), 1);
+    }
+
     #[test]
     fn test_is_empty() {
         let limiter = RateLimiter::new(10);
-        assert!(limiter.is_empty());
+        assert_eq!(limiter.tracked_ips(), 0);
 
         let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
         limiter.check_and_increment(ip, 1000);
-        assert!(!limiter.is_empty());
+        assert_ne!(limiter.tracked_ips(), 0);
     }
 }
@@ -0,0 +1,113 @@
+use dashmap::DashMap;
+
+/// Where a user ban came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserBanSource {
+    /// Banned through /user/ban, persisted in the WAL
+    Manual,
+    /// `security_locked` in the external API, re-derived on every sync
+    Api,
+}
+
+/// User blacklist keyed by user ID
+///
+/// Banned users stay in the user cache but their announces fail with a distinct
+/// error. Manual bans survive API syncs; API bans are replaced by each sync.
+#[derive(Debug, Default)]
+pub struct UserBlacklist {
+    users: DashMap<u32, UserBanSource>,
+}
+
+impl UserBlacklist {
+    pub fn new() -> Self {
+        Self {
+            users: DashMap::new(),
+        }
+    }
+
+    /// Ban a user manually. A ban that came from the API becomes a manual one.
+    pub fn ban(&self, user_id: u32) {
+        self.users.insert(user_id, UserBanSource::Manual);
+    }
+
+    /// Lift a ban of either source, returning whether the user was banned
+    ///
+    /// A user still `security_locked` in the API is banned again on the next sync.
+    pub fn unban(&self, user_id: u32) -> bool {
+        self.users.remove(&user_id).is_some()
+    }
+
+    pub fn is_banned(&self, user_id: u32) -> bool {
+        self.users.contains_key(&user_id)
+    }
+
+    /// Replace the API-sourced bans with `user_ids`, leaving manual bans alone
+    pub fn replace_api_bans(&self, user_ids: &[u32]) {
+        self.users.retain(|_, source| *source == UserBanSource::Manual);
+        for &user_id in user_ids {
+            self.users.entry(user_id).or_insert(UserBanSource::Api);
+        }
+    }
+
+    /// User IDs banned manually, sorted
+    pub fn manual_bans(&self) -> Vec<u32> {
+        let mut user_ids: Vec<u32> = self
+            .users
+            .iter()
+            .filter(|entry| *entry.value() == UserBanSource::Manual)
+            .map(|entry| *entry.key())
+            .collect();
+        user_ids.sort_unstable();
+        user_ids
+    }
+
+    /// Number of banned users, from either source
+    pub fn banned_count(&self) -> usize {
+        self.users.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ban_and_unban() {
+        let blacklist = UserBlacklist::new();
+        assert!(!blacklist.is_banned(1));
+
+        blacklist.ban(1);
+        assert!(blacklist.is_banned(1));
+
+        assert!(blacklist.unban(1));
+        assert!(!blacklist.is_banned(1));
+        assert!(!blacklist.unban(1));
+    }
+
+    #[test]
+    fn test_replace_api_bans_keeps_manual_bans() {
+        let blacklist = UserBlacklist::new();
+        blacklist.ban(1);
+        blacklist.replace_api_bans(&[1, 2, 3]);
+
+        assert!(blacklist.is_banned(2));
+        assert_eq!(blacklist.banned_count(), 3);
+        assert_eq!(blacklist.manual_bans(), vec![1]);
+
+        // The next sync no longer locks 1 or 3; the manual ban on 1 stays
+        blacklist.replace_api_bans(&[2]);
+        assert!(blacklist.is_banned(1));
+        assert!(blacklist.is_banned(2));
+        assert!(!blacklist.is_banned(3));
+    }
+
+    #[test]
+    fn test_manual_ban_overrides_api_ban() {
+        let blacklist = UserBlacklist::new();
+        blacklist.replace_api_bans(&[5]);
+        blacklist.ban(5);
+
+        blacklist.replace_api_bans(&[]);
+        assert!(blacklist.is_banned(5));
+    }
+}
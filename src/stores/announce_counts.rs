@@ -0,0 +1,77 @@
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Announces per torrent and per user since the previous /update
+///
+/// Only announces from cached users for cached torrents are counted, so both maps stay
+/// bounded by the caches, and /update takes and clears them on every call.
+#[derive(Default)]
+pub struct AnnounceCounts {
+    torrents: DashMap<u32, AtomicU64>,
+    users: DashMap<u32, AtomicU64>,
+}
+
+impl AnnounceCounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an announce by `user_id` for `torrent_id`
+    pub fn record(&self, user_id: u32, torrent_id: u32) {
+        increment(&self.torrents, torrent_id);
+        increment(&self.users, user_id);
+    }
+
+    /// Take the per-torrent counts and reset them
+    pub fn take_torrents(&self) -> Vec<(u32, u64)> {
+        take(&self.torrents)
+    }
+
+    /// Take the per-user counts and reset them
+    pub fn take_users(&self) -> Vec<(u32, u64)> {
+        take(&self.users)
+    }
+}
+
+/// Ids seen before only need a shard read lock
+fn increment(counts: &DashMap<u32, AtomicU64>, id: u32) {
+    if let Some(count) = counts.get(&id) {
+        count.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    counts.entry(id).or_default().fetch_add(1, Ordering::Relaxed);
+}
+
+fn take(counts: &DashMap<u32, AtomicU64>) -> Vec<(u32, u64)> {
+    let ids: Vec<u32> = counts.iter().map(|entry| *entry.key()).collect();
+
+    ids.into_iter()
+        .filter_map(|id| counts.remove(&id))
+        .map(|(id, count)| (id, count.into_inner()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_are_taken_as_deltas() {
+        let counts = AnnounceCounts::new();
+        counts.record(1, 10);
+        counts.record(1, 10);
+        counts.record(2, 10);
+        counts.record(1, 11);
+
+        let mut torrents = counts.take_torrents();
+        torrents.sort_unstable();
+        assert_eq!(torrents, vec![(10, 3), (11, 1)]);
+
+        let mut users = counts.take_users();
+        users.sort_unstable();
+        assert_eq!(users, vec![(1, 3), (2, 1)]);
+
+        assert!(counts.take_torrents().is_empty());
+        assert!(counts.take_users().is_empty());
+    }
+}
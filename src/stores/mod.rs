@@ -1,3 +1,6 @@
 pub mod peer_store;
 pub mod user_cache;
 pub mod torrent_cache;
+pub mod rejected_passkeys;
+pub mod unknown_torrents;
+pub mod announce_counts;
@@ -1,16 +1,20 @@
+use crate::core::error::PeerStoreError;
 use crate::models::peer::Peer;
+use crate::utils::time::{current_timestamp, is_expired};
 use dashmap::DashMap;
-use dashmap::DashSet;
+use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use anyhow::{Result, Context};
 use rand::seq::SliceRandom;
+use tracing::warn;
 
 #[derive(Debug)]
 pub struct TorrentStats {
     pub seeders: AtomicU32,
     pub leechers: AtomicU32,
+    /// Downloads completed since the last drain
+    pub completed: AtomicU32,
 }
 
 impl TorrentStats {
@@ -18,15 +22,59 @@ impl TorrentStats {
         Self {
             seeders: AtomicU32::new(0),
             leechers: AtomicU32::new(0),
+            completed: AtomicU32::new(0),
         }
     }
+
+    /// Count a peer joining the swarm
+    fn count(&self, is_seeder: bool) {
+        let counter = if is_seeder { &self.seeders } else { &self.leechers };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a peer leaving the swarm
+    fn uncount(&self, is_seeder: bool) {
+        let counter = if is_seeder { &self.seeders } else { &self.leechers };
+        counter.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// How `get_peers_and_stats` orders the shuffled peers before applying numwant
+#[derive(Debug, Clone, Copy)]
+pub enum PeerOrder {
+    /// Random order
+    Random,
+    /// Peers with an address in the requester's family first
+    PreferFamily { prefer_ipv6: bool },
+    /// `preferred_ratio` of the list reserved for the opposite role, optionally with the
+    /// family preference on top
+    Weighted {
+        requester_is_seeder: bool,
+        preferred_ratio: f64,
+        prefer_ipv6: Option<bool>,
+    },
+}
+
+/// An IP tracked for duplicate-peer detection
+#[derive(Debug)]
+struct TrackedIp {
+    last_seen: i64,
+    /// Client `key` last announced from this IP
+    key: Option<String>,
 }
 
 /// In-memory peer store 
 pub struct PeerStore {
     pub peers: DashMap<[u8; 20], DashMap<[u8; 20], Peer>>,
     stats: DashMap<[u8; 20], Arc<TorrentStats>>,
-    user_ips: DashMap<(u32, u32), DashSet<IpAddr>>,
+    /// IPs per (user_id, torrent_id) with the time each was last announced from
+    user_ips: DashMap<(u32, u32), DashMap<IpAddr, TrackedIp>>,
+    /// Maximum number of IPs tracked per (user_id, torrent_id)
+    max_tracked_ips: usize,
+    /// Seconds spent seeding per (user_id, torrent_id) since the last drain
+    seed_time: DashMap<(u32, u32), u64>,
+    /// Torrents each user has peers in, with the number of peers per torrent
+    user_torrents: DashMap<u32, HashMap<u32, u32>>,
 }
 
 impl PeerStore {
@@ -35,26 +83,104 @@ impl PeerStore {
             peers: DashMap::new(),
             stats: DashMap::new(),
             user_ips: DashMap::new(),
+            seed_time: DashMap::new(),
+            user_torrents: DashMap::new(),
+            max_tracked_ips: usize::MAX,
+        }
+    }
+
+    /// Create a peer store that tracks at most `max_tracked_ips` IPs per user and torrent,
+    /// evicting the least recently seen IP when the cap is exceeded
+    #[cfg(test)]
+    pub fn with_max_tracked_ips(max_tracked_ips: usize) -> Self {
+        Self {
+            max_tracked_ips: max_tracked_ips.max(1),
+            ..Self::new()
+        }
+    }
+
+    /// Create a peer store whose per-torrent maps are sized for `capacity` torrents and
+    /// split into `shard_amount` shards, which must be a power of two greater than 1
+    pub fn with_capacity_and_shard_amount(capacity: usize, shard_amount: usize, max_tracked_ips: usize) -> Self {
+        Self {
+            peers: DashMap::with_capacity_and_shard_amount(capacity, shard_amount),
+            stats: DashMap::with_capacity_and_shard_amount(capacity, shard_amount),
+            user_ips: DashMap::with_shard_amount(shard_amount),
+            seed_time: DashMap::with_shard_amount(shard_amount),
+            user_torrents: DashMap::with_shard_amount(shard_amount),
+            max_tracked_ips: max_tracked_ips.max(1),
+        }
+    }
+
+    /// Record the peer's IP for its user and torrent, keeping the set within the cap
+    fn track_ip(&self, peer: &Peer) {
+        let user_ips = self.user_ips
+            .entry((peer.user_id, peer.torrent_id))
+            .or_default();
+        user_ips.insert(peer.ip, TrackedIp {
+            last_seen: peer.last_announce,
+            key: peer.key.clone(),
+        });
+        
+        while user_ips.len() > self.max_tracked_ips {
+            let oldest = user_ips
+                .iter()
+                .filter(|entry| *entry.key() != peer.ip)
+                .min_by_key(|entry| entry.value().last_seen)
+                .map(|entry| *entry.key());
+            
+            match oldest {
+                Some(ip) => {
+                    user_ips.remove(&ip);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Forget an IP of a user in a torrent, dropping the set once it is empty
+    fn untrack_ip(&self, user_id: u32, torrent_id: u32, ip: IpAddr) {
+        if let Some(user_ips) = self.user_ips.get(&(user_id, torrent_id)) {
+            user_ips.remove(&ip);
+            
+            if user_ips.is_empty() {
+                drop(user_ips);
+                self.user_ips.remove(&(user_id, torrent_id));
+            }
         }
     }
 
     /// Add a new peer to the store
-    pub fn add_peer(&self, info_hash: [u8; 20], peer: Peer) -> Result<()> {
+    pub fn add_peer(&self, info_hash: [u8; 20], mut peer: Peer) -> Result<(), PeerStoreError> {
+        clamp_future_announce(&mut peer, current_timestamp());
         let peer_map = self.peers.entry(info_hash).or_insert_with(DashMap::new);
         let stats = self.stats.entry(info_hash).or_insert_with(|| Arc::new(TorrentStats::new()));
         
-        let user_ips = self.user_ips
-            .entry((peer.user_id, peer.torrent_id))
-            .or_insert_with(DashSet::new);
-        user_ips.insert(peer.ip);
+        self.track_ip(&peer);
         
-        let is_new = !peer_map.contains_key(&peer.peer_id);
+        let old_peer = peer_map.get(&peer.peer_id).map(|p| (p.user_id, p.is_seeder, p.last_announce, p.ip));
         
-        if is_new {
-            if peer.is_seeder {
-                stats.seeders.fetch_add(1, Ordering::Relaxed);
-            } else {
-                stats.leechers.fetch_add(1, Ordering::Relaxed);
+        match old_peer {
+            Some((old_user_id, was_seeder, last_announce, _)) if old_user_id == peer.user_id => {
+                self.accrue_seed_time(old_user_id, peer.torrent_id, was_seeder, last_announce, peer.last_announce);
+                if was_seeder != peer.is_seeder {
+                    stats.uncount(was_seeder);
+                    stats.count(peer.is_seeder);
+                }
+            }
+            Some((old_user_id, was_seeder, last_announce, old_ip)) => {
+                // Another user's client reusing the peer_id: the old entry is removed from
+                // its owner, who is credited the seed time up to now, and the new one added
+                self.accrue_seed_time(old_user_id, peer.torrent_id, was_seeder, last_announce, peer.last_announce);
+                stats.uncount(was_seeder);
+                self.untrack_user_torrent(old_user_id, peer.torrent_id);
+                self.untrack_ip(old_user_id, peer.torrent_id, old_ip);
+                stats.count(peer.is_seeder);
+                self.track_user_torrent(peer.user_id, peer.torrent_id);
+            }
+            None => {
+                stats.count(peer.is_seeder);
+                self.track_user_torrent(peer.user_id, peer.torrent_id);
             }
         }
         
@@ -64,21 +190,21 @@ impl PeerStore {
     }
 
     /// Update an existing peer in the store
-    pub fn update_peer(&self, info_hash: [u8; 20], peer_id: [u8; 20], peer: Peer) -> Result<()> {
+    pub fn update_peer(&self, info_hash: [u8; 20], peer_id: [u8; 20], mut peer: Peer) -> Result<(), PeerStoreError> {
+        clamp_future_announce(&mut peer, current_timestamp());
         let peer_map = self.peers
             .get(&info_hash)
-            .context("Torrent not found in peer store")?;
+            .ok_or(PeerStoreError::TorrentNotFound)?;
         
         let stats = self.stats
             .get(&info_hash)
-            .context("Stats not found for torrent")?;
+            .ok_or(PeerStoreError::StatsMissing)?;
         
-        let user_ips = self.user_ips
-            .entry((peer.user_id, peer.torrent_id))
-            .or_insert_with(DashSet::new);
-        user_ips.insert(peer.ip);
+        self.track_ip(&peer);
         
         if let Some(old_peer) = peer_map.get(&peer_id) {
+            self.accrue_seed_time(old_peer.user_id, peer.torrent_id, old_peer.is_seeder, old_peer.last_announce, peer.last_announce);
+            
             if old_peer.is_seeder != peer.is_seeder {
                 if peer.is_seeder {
                     stats.leechers.fetch_sub(1, Ordering::Relaxed);
@@ -88,6 +214,11 @@ impl PeerStore {
                     stats.leechers.fetch_add(1, Ordering::Relaxed);
                 }
             }
+        } else {
+            // Removed since the caller looked it up (stopped or reaped), so this
+            // re-adds it and has to count it like add_peer does
+            stats.count(peer.is_seeder);
+            self.track_user_torrent(peer.user_id, peer.torrent_id);
         }
         
         peer_map.insert(peer_id, peer);
@@ -96,45 +227,156 @@ impl PeerStore {
     }
 
     /// Remove a peer from the store
-    pub fn remove_peer(&self, info_hash: [u8; 20], peer_id: [u8; 20]) -> Result<()> {
+    ///
+    /// Fails with `TorrentNotFound` or `PeerNotFound` if the peer was not in the store
+    /// (e.g. a stop after a tracker restart), which callers usually treat as benign.
+    pub fn remove_peer(&self, info_hash: [u8; 20], peer_id: [u8; 20]) -> Result<(), PeerStoreError> {
         let peer_map = self.peers
             .get(&info_hash)
-            .context("Torrent not found in peer store")?;
+            .ok_or(PeerStoreError::TorrentNotFound)?;
         
         let stats = self.stats
             .get(&info_hash)
-            .context("Stats not found for torrent")?;
+            .ok_or(PeerStoreError::StatsMissing)?;
         
-        if let Some((_, peer)) = peer_map.remove(&peer_id) {
-            if peer.is_seeder {
-                stats.seeders.fetch_sub(1, Ordering::Relaxed);
-            } else {
-                stats.leechers.fetch_sub(1, Ordering::Relaxed);
+        let (_, peer) = peer_map
+            .remove(&peer_id)
+            .ok_or(PeerStoreError::PeerNotFound)?;
+        
+        let current_time = current_timestamp();
+        self.accrue_seed_time(peer.user_id, peer.torrent_id, peer.is_seeder, peer.last_announce, current_time);
+        
+        stats.uncount(peer.is_seeder);
+        self.untrack_user_torrent(peer.user_id, peer.torrent_id);
+        self.untrack_ip(peer.user_id, peer.torrent_id, peer.ip);
+        
+        Ok(())
+    }
+
+    /// Remove the user's other peers in the torrent announcing the same IP and port as `peer`
+    ///
+    /// A client restarting with a new peer_id leaves its old entry behind until it times
+    /// out; removing it keeps the swarm counts accurate. Scans the whole swarm, so it is
+    /// meant for new peers only. Returns the number of peers removed.
+    pub fn remove_endpoint_duplicates(&self, info_hash: [u8; 20], peer: &Peer) -> Result<usize, PeerStoreError> {
+        let duplicates: Vec<[u8; 20]> = match self.peers.get(&info_hash) {
+            Some(peer_map) => peer_map
+                .iter()
+                .filter(|entry| {
+                    let other = entry.value();
+                    other.peer_id != peer.peer_id
+                        && other.user_id == peer.user_id
+                        && other.ip == peer.ip
+                        && other.port == peer.port
+                })
+                .map(|entry| *entry.key())
+                .collect(),
+            None => return Ok(0),
+        };
+
+        let mut removed = 0;
+        for peer_id in duplicates {
+            match self.remove_peer(info_hash, peer_id) {
+                Ok(()) => removed += 1,
+                // Gone already, e.g. stopped concurrently
+                Err(e) if e.is_not_found() => {}
+                Err(e) => return Err(e),
             }
-            
-            if let Some(user_ips) = self.user_ips.get(&(peer.user_id, peer.torrent_id)) {
-                user_ips.remove(&peer.ip);
-                
-                if user_ips.is_empty() {
-                    drop(user_ips);
-                    self.user_ips.remove(&(peer.user_id, peer.torrent_id));
+        }
+        Ok(removed)
+    }
+
+    /// Remove a torrent's whole swarm, including its stats and IP tracking entries
+    ///
+    /// Returns the number of peers removed.
+    pub fn remove_torrent(&self, info_hash: [u8; 20]) -> usize {
+        self.stats.remove(&info_hash);
+        
+        let Some((_, peer_map)) = self.peers.remove(&info_hash) else {
+            return 0;
+        };
+        
+        for entry in peer_map.iter() {
+            let peer = entry.value();
+            self.user_ips.remove(&(peer.user_id, peer.torrent_id));
+            self.untrack_user_torrent(peer.user_id, peer.torrent_id);
+        }
+        
+        peer_map.len()
+    }
+
+    /// Count one more of a user's peers in a torrent
+    fn track_user_torrent(&self, user_id: u32, torrent_id: u32) {
+        *self.user_torrents.entry(user_id).or_default().entry(torrent_id).or_insert(0) += 1;
+    }
+
+    /// Forget one of a user's peers in a torrent, dropping the torrent with its last peer
+    fn untrack_user_torrent(&self, user_id: u32, torrent_id: u32) {
+        let Some(mut torrents) = self.user_torrents.get_mut(&user_id) else {
+            return;
+        };
+        if let Some(count) = torrents.get_mut(&torrent_id) {
+            *count -= 1;
+            if *count == 0 {
+                torrents.remove(&torrent_id);
+            }
+        }
+        if torrents.is_empty() {
+            drop(torrents);
+            self.user_torrents.remove_if(&user_id, |_, torrents| torrents.is_empty());
+        }
+    }
+
+    /// Number of torrents the user has at least one peer in
+    pub fn user_torrent_count(&self, user_id: u32) -> usize {
+        self.user_torrents.get(&user_id).map_or(0, |torrents| torrents.len())
+    }
+
+    /// Whether the user has at least one peer in the torrent
+    pub fn user_in_torrent(&self, user_id: u32, torrent_id: u32) -> bool {
+        self.user_torrents
+            .get(&user_id)
+            .is_some_and(|torrents| torrents.contains_key(&torrent_id))
+    }
+
+    /// Peer list and (seeders, leechers) for a torrent from a single peer map lookup
+    ///
+    /// The list and the counts are not one consistent view: a peer updated or removed
+    /// concurrently may be reflected in one and not the other.
+    pub fn get_peers_and_stats(
+        &self,
+        info_hash: [u8; 20],
+        num_want: u32,
+        exclude_peer_id: [u8; 20],
+        order: PeerOrder,
+    ) -> (Vec<Peer>, (u32, u32)) {
+        let (mut peers, stats) = self.shuffled_peers_and_stats(info_hash, num_want, exclude_peer_id);
+
+        match order {
+            PeerOrder::Random => {}
+            PeerOrder::PreferFamily { prefer_ipv6 } => sort_by_family(&mut peers, prefer_ipv6),
+            PeerOrder::Weighted { requester_is_seeder, preferred_ratio, prefer_ipv6 } => {
+                peers = weight_by_role(peers, num_want, requester_is_seeder, preferred_ratio);
+                if let Some(prefer_ipv6) = prefer_ipv6 {
+                    sort_by_family(&mut peers, prefer_ipv6);
                 }
             }
         }
-        
-        Ok(())
+
+        peers.truncate(num_want as usize);
+        (peers, stats)
     }
 
-    /// Get a list of peers for a torrent with random selection and numwant limit
-    pub fn get_peers(
+    /// All peers of a torrent except `exclude_peer_id` in random order, with its stats
+    fn shuffled_peers_and_stats(
         &self,
         info_hash: [u8; 20],
         num_want: u32,
         exclude_peer_id: [u8; 20],
-    ) -> Vec<Peer> {
+    ) -> (Vec<Peer>, (u32, u32)) {
         let peer_map = match self.peers.get(&info_hash) {
             Some(map) => map,
-            None => return Vec::new(),
+            None => return (Vec::new(), self.get_stats(info_hash)),
         };
         
         let estimated_size = peer_map.len().saturating_sub(1).min(num_want as usize);
@@ -145,15 +387,16 @@ impl PeerStore {
                 peers.push(entry.value().clone());
             }
         }
+        let stats = self.get_stats(info_hash);
         
         drop(peer_map);
         
         let mut rng = rand::thread_rng();
         peers.shuffle(&mut rng);
+        // Peers known not to accept connections go last; the stable sort keeps the shuffle
+        peers.sort_by_key(|peer| peer.connectable == Some(false));
         
-        peers.truncate(num_want as usize);
-        
-        peers
+        (peers, stats)
     }
 
     /// Get statistics (seeders, leechers) for a torrent
@@ -167,7 +410,48 @@ impl PeerStore {
         }
     }
 
+    /// Get a single peer by its peer ID
+    pub fn get_peer(&self, info_hash: [u8; 20], peer_id: [u8; 20]) -> Option<Peer> {
+        self.peers.get(&info_hash)?.get(&peer_id).map(|peer| peer.clone())
+    }
+
+    /// Record whether a peer accepts incoming connections, `None` meaning unknown
+    ///
+    /// Returns false if the peer is not in the store.
+    pub fn set_connectable(&self, info_hash: [u8; 20], peer_id: [u8; 20], connectable: Option<bool>) -> bool {
+        let Some(peer_map) = self.peers.get(&info_hash) else {
+            return false;
+        };
+        let Some(mut peer) = peer_map.get_mut(&peer_id) else {
+            return false;
+        };
+        peer.connectable = connectable;
+        true
+    }
+
+    /// Count a finished download for a torrent
+    pub fn record_completion(&self, info_hash: [u8; 20]) {
+        self.stats
+            .entry(info_hash)
+            .or_insert_with(|| Arc::new(TorrentStats::new()))
+            .completed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Drain the completed-download count for a torrent
+    ///
+    /// Returns the completions recorded since the previous call.
+    pub fn take_completed(&self, info_hash: [u8; 20]) -> u32 {
+        self.stats
+            .get(&info_hash)
+            .map(|stats| stats.completed.swap(0, Ordering::Relaxed))
+            .unwrap_or(0)
+    }
+
     /// Get the number of unique IPs a user is using for a torrent (for duplicate peer detection)
+    ///
+    /// The count saturates at the store's tracked-IP cap, which is configured above
+    /// the duplicate-peer limit so violations remain detectable.
     pub fn get_user_ip_count(&self, user_id: u32, torrent_id: u32) -> usize {
         match self.user_ips.get(&(user_id, torrent_id)) {
             Some(ips) => ips.len(),
@@ -175,12 +459,45 @@ impl PeerStore {
         }
     }
 
+    /// Like `get_user_ip_count`, but IPs announced with the same client `key` count once
+    ///
+    /// A client roaming between IPs keeps its key, so it is not mistaken for several
+    /// clients. IPs without a key each count separately.
+    pub fn get_user_client_count(&self, user_id: u32, torrent_id: u32) -> usize {
+        let Some(ips) = self.user_ips.get(&(user_id, torrent_id)) else {
+            return 0;
+        };
+        
+        let mut keys = HashSet::new();
+        let mut keyless = 0;
+        for entry in ips.iter() {
+            match &entry.value().key {
+                Some(key) => {
+                    keys.insert(key.clone());
+                }
+                None => keyless += 1,
+            }
+        }
+        
+        keys.len() + keyless
+    }
+
+    /// Count the distinct peer IDs a user has in a torrent's swarm
+    pub fn get_user_peer_count(&self, info_hash: [u8; 20], user_id: u32) -> usize {
+        match self.peers.get(&info_hash) {
+            Some(peer_map) => peer_map.iter().filter(|entry| entry.value().user_id == user_id).count(),
+            None => 0,
+        }
+    }
+
     /// Clean up stale peers that haven't announced within the timeout period
-    pub fn cleanup_stale_peers(&self, timeout: i64) -> usize {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+    ///
+    /// `timeout_override` is asked once per torrent for a timeout replacing `timeout`.
+    pub fn cleanup_stale_peers<F>(&self, timeout: i64, timeout_override: F) -> usize
+    where
+        F: Fn([u8; 20]) -> Option<i64>,
+    {
+        let current_time = current_timestamp();
         
         let mut removed_count = 0;
         
@@ -192,33 +509,39 @@ impl PeerStore {
                 Some(s) => s,
                 None => continue,
             };
+            let timeout = timeout_override(info_hash).unwrap_or(timeout);
             
             let estimated_stale = peer_map.len() / 10;
-            let mut stale_peers: Vec<([u8; 20], Peer)> = Vec::with_capacity(estimated_stale);
+            let mut stale_peers: Vec<[u8; 20]> = Vec::with_capacity(estimated_stale);
+            
+            let mut future_peers = Vec::new();
             
             for entry in peer_map.iter() {
-                if current_time - entry.value().last_announce > timeout {
-                    stale_peers.push((*entry.key(), entry.value().clone()));
+                if is_expired(entry.value().last_announce, timeout, current_time) {
+                    stale_peers.push(*entry.key());
+                } else if entry.value().last_announce > current_time {
+                    future_peers.push(*entry.key());
                 }
             }
             
-            for (peer_id, peer) in stale_peers {
-                peer_map.remove(&peer_id);
-                
-                if peer.is_seeder {
-                    stats.seeders.fetch_sub(1, Ordering::Relaxed);
-                } else {
-                    stats.leechers.fetch_sub(1, Ordering::Relaxed);
+            // A future timestamp would keep the peer from ever expiring
+            for peer_id in future_peers {
+                if let Some(mut peer) = peer_map.get_mut(&peer_id) {
+                    clamp_future_announce(&mut peer, current_time);
                 }
+            }
+            
+            for peer_id in stale_peers {
+                // Stopped or re-announced since the scan
+                let removed =
+                    peer_map.remove_if(&peer_id, |_, peer| is_expired(peer.last_announce, timeout, current_time));
+                let Some((_, peer)) = removed else {
+                    continue;
+                };
                 
-                if let Some(user_ips) = self.user_ips.get(&(peer.user_id, peer.torrent_id)) {
-                    user_ips.remove(&peer.ip);
-                    
-                    if user_ips.is_empty() {
-                        drop(user_ips);
-                        self.user_ips.remove(&(peer.user_id, peer.torrent_id));
-                    }
-                }
+                stats.uncount(peer.is_seeder);
+                self.untrack_user_torrent(peer.user_id, peer.torrent_id);
+                self.untrack_ip(peer.user_id, peer.torrent_id, peer.ip);
                 
                 removed_count += 1;
             }
@@ -227,6 +550,31 @@ impl PeerStore {
         removed_count
     }
 
+    /// Credit the interval since the previous announce as seed time.
+    ///
+    /// The interval is attributed to the state the peer was in when it started, so a
+    /// leecher that completes mid-interval earns seed time only from its next announce.
+    fn accrue_seed_time(&self, user_id: u32, torrent_id: u32, was_seeder: bool, last_announce: i64, now: i64) {
+        if !was_seeder {
+            return;
+        }
+        
+        let elapsed = (now - last_announce).max(0) as u64;
+        if elapsed > 0 {
+            *self.seed_time.entry((user_id, torrent_id)).or_insert(0) += elapsed;
+        }
+    }
+
+    /// Take the accumulated seed time per (user_id, torrent_id) and reset the counters
+    pub fn take_seed_time(&self) -> Vec<(u32, u32, u64)> {
+        let keys: Vec<(u32, u32)> = self.seed_time.iter().map(|entry| *entry.key()).collect();
+        
+        keys.into_iter()
+            .filter_map(|key| self.seed_time.remove(&key))
+            .map(|((user_id, torrent_id), seconds)| (user_id, torrent_id, seconds))
+            .collect()
+    }
+
     /// Get the total number of active peers across all torrents
     pub fn total_peers(&self) -> usize {
         self.peers.iter().map(|entry| entry.value().len()).sum()
@@ -244,10 +592,51 @@ impl Default for PeerStore {
     }
 }
 
+/// Move peers with an address in the preferred family to the front
+fn sort_by_family(peers: &mut [Peer], prefer_ipv6: bool) {
+    // Stable sort keeps the shuffle within each family
+    peers.sort_by_key(|peer| if prefer_ipv6 { peer.ipv6().is_none() } else { peer.ipv4().is_none() });
+}
+
+/// Put the first `preferred_ratio` share of peers of the opposite role in front
+fn weight_by_role(peers: Vec<Peer>, num_want: u32, requester_is_seeder: bool, preferred_ratio: f64) -> Vec<Peer> {
+    let quota = ((num_want as usize).min(peers.len()) as f64 * preferred_ratio).ceil() as usize;
+
+    // Everything else keeps its shuffled order behind them
+    let mut weighted = Vec::with_capacity(peers.len());
+    let mut rest = Vec::with_capacity(peers.len());
+    for peer in peers {
+        if weighted.len() < quota && peer.is_seeder != requester_is_seeder {
+            weighted.push(peer);
+        } else {
+            rest.push(peer);
+        }
+    }
+    weighted.append(&mut rest);
+    weighted
+}
+
+/// Pull a `last_announce` that lies in the future back to `now`
+///
+/// One bad timestamp (clock skew, a bug) would otherwise pin a ghost peer in the swarm
+/// forever and skew the interval and speed checks.
+fn clamp_future_announce(peer: &mut Peer, now: i64) {
+    if peer.last_announce > now {
+        warn!(
+            user_id = peer.user_id,
+            torrent_id = peer.torrent_id,
+            last_announce = peer.last_announce,
+            now,
+            "Peer announce time is in the future, clamping to now"
+        );
+        peer.last_announce = now;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::net::{IpAddr, Ipv4Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
     fn create_test_peer(
         user_id: u32,
@@ -269,6 +658,12 @@ mod tests {
             last_announce,
             user_agent: "TestClient/1.0".to_string(),
             is_seeder,
+            last_event: None,
+            key: None,
+            alt_ip: None,
+            connectable: None,
+            supportcrypto: false,
+            requirecrypto: false,
         }
     }
 
@@ -312,6 +707,26 @@ mod tests {
         assert_eq!(leechers, 0);
     }
 
+    #[test]
+    fn test_update_peer_removed_concurrently() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let peer_id = [2u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+
+        store.add_peer(info_hash, create_test_peer(1, 1, peer_id, ip, false, 1000)).unwrap();
+        store.remove_peer(info_hash, peer_id).unwrap();
+
+        // The update re-adds the peer, so it is counted again
+        store.update_peer(info_hash, peer_id, create_test_peer(1, 1, peer_id, ip, true, 2000)).unwrap();
+        assert_eq!(store.get_stats(info_hash), (1, 0));
+        assert_eq!(store.user_torrent_count(1), 1);
+
+        store.remove_peer(info_hash, peer_id).unwrap();
+        assert_eq!(store.get_stats(info_hash), (0, 0));
+        assert_eq!(store.user_torrent_count(1), 0);
+    }
+
     #[test]
     fn test_remove_peer() {
         let store = PeerStore::new();
@@ -333,6 +748,108 @@ mod tests {
         assert_eq!(leechers, 0);
     }
 
+    #[test]
+    fn test_remove_peer_not_present() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        
+        // Unknown torrent is reported as such and changes nothing
+        assert_eq!(store.remove_peer(info_hash, [2u8; 20]), Err(PeerStoreError::TorrentNotFound));
+        let peer = create_test_peer(1, 1, [2u8; 20], ip, false, 1000);
+        assert_eq!(store.update_peer(info_hash, [2u8; 20], peer), Err(PeerStoreError::TorrentNotFound));
+        
+        // Known torrent, unknown peer leaves stats untouched
+        let peer = create_test_peer(1, 1, [2u8; 20], ip, false, 1000);
+        store.add_peer(info_hash, peer).unwrap();
+        
+        assert_eq!(store.remove_peer(info_hash, [3u8; 20]), Err(PeerStoreError::PeerNotFound));
+        
+        let (seeders, leechers) = store.get_stats(info_hash);
+        assert_eq!(seeders, 0);
+        assert_eq!(leechers, 1);
+    }
+
+    #[test]
+    fn test_remove_torrent() {
+        let store = PeerStore::new();
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        
+        store.add_peer([1u8; 20], create_test_peer(1, 1, [2u8; 20], ip, true, 1000)).unwrap();
+        store.add_peer([1u8; 20], create_test_peer(2, 1, [3u8; 20], ip, false, 1000)).unwrap();
+        store.add_peer([4u8; 20], create_test_peer(1, 2, [2u8; 20], ip, false, 1000)).unwrap();
+        
+        assert_eq!(store.remove_torrent([1u8; 20]), 2);
+        
+        // Swarm, stats and IP tracking for the torrent are gone
+        assert_eq!(store.get_stats([1u8; 20]), (0, 0));
+        assert_eq!(store.get_user_ip_count(1, 1), 0);
+        assert_eq!(store.get_user_ip_count(2, 1), 0);
+        assert_eq!(store.active_torrents(), 1);
+        
+        // Other torrents are untouched
+        assert_eq!(store.get_stats([4u8; 20]), (0, 1));
+        assert_eq!(store.get_user_ip_count(1, 2), 1);
+        
+        // Removing an unknown torrent is a no-op
+        assert_eq!(store.remove_torrent([9u8; 20]), 0);
+    }
+
+    #[test]
+    fn test_seed_time_accumulation() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        
+        // Leecher interval is not credited when the peer completes
+        store.add_peer(info_hash, create_test_peer(1, 1, [2u8; 20], ip, false, 1000)).unwrap();
+        store.update_peer(info_hash, [2u8; 20], create_test_peer(1, 1, [2u8; 20], ip, true, 1600)).unwrap();
+        assert!(store.take_seed_time().is_empty());
+        
+        // Subsequent seeding intervals accumulate
+        store.update_peer(info_hash, [2u8; 20], create_test_peer(1, 1, [2u8; 20], ip, true, 2000)).unwrap();
+        store.update_peer(info_hash, [2u8; 20], create_test_peer(1, 1, [2u8; 20], ip, true, 2300)).unwrap();
+        assert_eq!(store.take_seed_time(), vec![(1, 1, 700)]);
+        
+        // Taking resets the accumulator
+        assert!(store.take_seed_time().is_empty());
+    }
+
+    #[test]
+    fn test_seed_time_of_reused_peer_id_goes_to_old_owner() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let peer_id = [2u8; 20];
+        let ip1 = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let ip2 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        store.add_peer(info_hash, create_test_peer(1, 1, peer_id, ip1, true, 1000)).unwrap();
+        // User 2 announces with the peer_id user 1 is seeding under
+        store.add_peer(info_hash, create_test_peer(2, 1, peer_id, ip2, true, 1600)).unwrap();
+        assert_eq!(store.take_seed_time(), vec![(1, 1, 600)]);
+    }
+
+    #[test]
+    fn test_reused_peer_id_moves_accounting_to_new_owner() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let peer_id = [2u8; 20];
+        let ip1 = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let ip2 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        store.add_peer(info_hash, create_test_peer(1, 1, peer_id, ip1, true, 1000)).unwrap();
+        store.add_peer(info_hash, create_test_peer(2, 1, peer_id, ip2, false, 1600)).unwrap();
+
+        assert_eq!(store.get_stats(info_hash), (0, 1));
+        assert_eq!(store.user_torrent_count(1), 0);
+        assert_eq!(store.user_torrent_count(2), 1);
+        assert!(!store.user_ips.contains_key(&(1, 1)));
+
+        store.remove_peer(info_hash, peer_id).unwrap();
+        assert_eq!(store.get_stats(info_hash), (0, 0));
+        assert_eq!(store.user_torrent_count(2), 0);
+    }
+
     #[test]
     fn test_get_peers() {
         let store = PeerStore::new();
@@ -347,13 +864,109 @@ mod tests {
         }
         
         // Request 3 peers, excluding peer 0
-        let peers = store.get_peers(info_hash, 3, [0u8; 20]);
+        let peers = store.get_peers_and_stats(info_hash, 3, [0u8; 20], PeerOrder::Random).0;
         assert_eq!(peers.len(), 3);
         
         // Verify excluded peer is not in the list
         assert!(!peers.iter().any(|p| p.peer_id == [0u8; 20]));
     }
 
+    #[test]
+    fn test_get_peers_preferring_family_order() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        
+        // 3 IPv4 and 3 IPv6 peers, each user on its own IP
+        for i in 0..6u8 {
+            let ip = if i < 3 {
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, i))
+            } else {
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, i as u16))
+            };
+            let peer = create_test_peer(i as u32, 1, [i + 1; 20], ip, false, 1000);
+            store.add_peer(info_hash, peer).unwrap();
+        }
+        
+        // Same family fills the list first
+        let peers = store.get_peers_and_stats(info_hash, 2, [0u8; 20], PeerOrder::PreferFamily { prefer_ipv6: true }).0;
+        assert!(peers.iter().all(|p| p.ip.is_ipv6()));
+        
+        // Then the other family tops it up
+        let peers = store.get_peers_and_stats(info_hash, 4, [0u8; 20], PeerOrder::PreferFamily { prefer_ipv6: false }).0;
+        assert_eq!(peers.len(), 4);
+        assert!(peers[..3].iter().all(|p| p.ip.is_ipv4()));
+        assert!(peers[3].ip.is_ipv6());
+        
+        // Exclusion still applies
+        let peers = store.get_peers_and_stats(info_hash, 50, [1u8; 20], PeerOrder::PreferFamily { prefer_ipv6: false }).0;
+        assert_eq!(peers.len(), 5);
+        assert!(!peers.iter().any(|p| p.peer_id == [1u8; 20]));
+    }
+
+    #[test]
+    fn test_get_peers_weighted_order() {
+        let weighted = |requester_is_seeder, preferred_ratio| PeerOrder::Weighted {
+            requester_is_seeder,
+            preferred_ratio,
+            prefer_ipv6: None,
+        };
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+
+        // 2 seeders and 8 leechers
+        for i in 0..10u8 {
+            let peer = create_test_peer(i as u32, 1, [i + 1; 20], ip, i < 2, 1000);
+            store.add_peer(info_hash, peer).unwrap();
+        }
+
+        // A leecher asking for 4 peers gets both seeders at a ratio of 0.5
+        for _ in 0..20 {
+            let peers = store.get_peers_and_stats(info_hash, 4, [0u8; 20], weighted(false, 0.5)).0;
+            assert_eq!(peers.len(), 4);
+            assert_eq!(peers.iter().filter(|p| p.is_seeder).count(), 2);
+        }
+
+        // A seeder gets only leechers at a ratio of 1
+        let peers = store.get_peers_and_stats(info_hash, 5, [0u8; 20], weighted(true, 1.0)).0;
+        assert_eq!(peers.len(), 5);
+        assert!(peers.iter().all(|p| !p.is_seeder));
+
+        // The quota is capped by what the swarm has; the rest is topped up
+        let peers = store.get_peers_and_stats(info_hash, 50, [1u8; 20], weighted(false, 1.0)).0;
+        assert_eq!(peers.len(), 9);
+        assert!(peers[0].is_seeder);
+        assert!(!peers.iter().any(|p| p.peer_id == [1u8; 20]));
+    }
+
+    #[test]
+    fn test_get_peers_and_stats() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+
+        let (peers, stats) = store.get_peers_and_stats(info_hash, 50, [0u8; 20], PeerOrder::Random);
+        assert!(peers.is_empty());
+        assert_eq!(stats, (0, 0));
+
+        // 1 seeder and 3 leechers
+        for i in 0..4u8 {
+            let peer = create_test_peer(i as u32, 1, [i + 1; 20], ip, i == 0, 1000);
+            store.add_peer(info_hash, peer).unwrap();
+        }
+
+        // The excluded peer still counts in the stats
+        let (peers, stats) = store.get_peers_and_stats(info_hash, 2, [1u8; 20], PeerOrder::Random);
+        assert_eq!(peers.len(), 2);
+        assert!(!peers.iter().any(|p| p.peer_id == [1u8; 20]));
+        assert_eq!(stats, (1, 3));
+
+        let order = PeerOrder::Weighted { requester_is_seeder: false, preferred_ratio: 1.0, prefer_ipv6: None };
+        let (peers, stats) = store.get_peers_and_stats(info_hash, 1, [0u8; 20], order);
+        assert!(peers[0].is_seeder);
+        assert_eq!(stats, store.get_stats(info_hash));
+    }
+
     #[test]
     fn test_cleanup_stale_peers() {
         let store = PeerStore::new();
@@ -383,7 +996,7 @@ mod tests {
         assert_eq!(leechers, 1);
         
         // Run cleanup with 1000 second timeout
-        let removed = store.cleanup_stale_peers(1000);
+        let removed = store.cleanup_stale_peers(1000, |_| None);
         assert_eq!(removed, 2);
         
         // Check stats after cleanup
@@ -392,11 +1005,29 @@ mod tests {
         assert_eq!(leechers, 0);
         
         // Verify only peer 1 remains
-        let peers = store.get_peers(info_hash, 10, [0u8; 20]);
+        let peers = store.get_peers_and_stats(info_hash, 10, [0u8; 20], PeerOrder::Random).0;
         assert_eq!(peers.len(), 1);
         assert_eq!(peers[0].peer_id, [1u8; 20]);
     }
 
+    #[test]
+    fn test_cleanup_per_torrent_timeout() {
+        let store = PeerStore::new();
+        let archival = [1u8; 20];
+        let regular = [2u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let current_time = current_timestamp();
+
+        store.add_peer(archival, create_test_peer(1, 1, [1u8; 20], ip, true, current_time - 5000)).unwrap();
+        store.add_peer(regular, create_test_peer(2, 2, [2u8; 20], ip, true, current_time - 5000)).unwrap();
+
+        // The archival torrent keeps its peers for a day, the other uses the global timeout
+        let removed = store.cleanup_stale_peers(1000, |info_hash| (info_hash == archival).then_some(86400));
+        assert_eq!(removed, 1);
+        assert_eq!(store.get_stats(archival), (1, 0));
+        assert_eq!(store.get_stats(regular), (0, 0));
+    }
+
     #[test]
     fn test_cleanup_no_stale_peers() {
         let store = PeerStore::new();
@@ -416,7 +1047,7 @@ mod tests {
         store.add_peer(info_hash, peer2).unwrap();
         
         // Run cleanup with 1000 second timeout
-        let removed = store.cleanup_stale_peers(1000);
+        let removed = store.cleanup_stale_peers(1000, |_| None);
         assert_eq!(removed, 0);
         
         // Verify all peers remain
@@ -451,6 +1082,30 @@ mod tests {
         assert_eq!(ip_count, 1);
     }
 
+    #[test]
+    fn test_user_ip_cap_evicts_oldest() {
+        let store = PeerStore::with_max_tracked_ips(2);
+        let info_hash = [1u8; 20];
+        let ip1 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let ip2 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let ip3 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        
+        store.add_peer(info_hash, create_test_peer(1, 1, [2u8; 20], ip1, false, 1000)).unwrap();
+        store.add_peer(info_hash, create_test_peer(1, 1, [3u8; 20], ip2, false, 2000)).unwrap();
+        store.add_peer(info_hash, create_test_peer(1, 1, [4u8; 20], ip3, false, 3000)).unwrap();
+        
+        assert_eq!(store.get_user_ip_count(1, 1), 2);
+        
+        // ip1 was the least recently seen and got evicted; re-announcing from it evicts ip2
+        store.update_peer(info_hash, [2u8; 20], create_test_peer(1, 1, [2u8; 20], ip1, false, 4000)).unwrap();
+        assert_eq!(store.get_user_ip_count(1, 1), 2);
+        
+        let ips = store.user_ips.get(&(1, 1)).unwrap();
+        assert!(ips.contains_key(&ip1));
+        assert!(ips.contains_key(&ip3));
+        assert!(!ips.contains_key(&ip2));
+    }
+
     #[test]
     fn test_total_peers_and_active_torrents() {
         let store = PeerStore::new();
@@ -473,4 +1128,132 @@ mod tests {
         assert_eq!(store.total_peers(), 5);
         assert_eq!(store.active_torrents(), 2);
     }
+
+    #[test]
+    fn test_completed_count_is_drained() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+
+        assert_eq!(store.take_completed(info_hash), 0);
+
+        store.record_completion(info_hash);
+        store.record_completion(info_hash);
+
+        assert_eq!(store.take_completed(info_hash), 2);
+        assert_eq!(store.take_completed(info_hash), 0);
+    }
+
+    #[test]
+    fn test_get_peer() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let peer = create_test_peer(1, 1, [2u8; 20], IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)), false, 1000);
+
+        assert!(store.get_peer(info_hash, [2u8; 20]).is_none());
+
+        store.add_peer(info_hash, peer).unwrap();
+
+        assert_eq!(store.get_peer(info_hash, [2u8; 20]).unwrap().user_id, 1);
+        assert!(store.get_peer(info_hash, [3u8; 20]).is_none());
+    }
+
+    #[test]
+    fn test_unconnectable_peers_listed_last() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        for i in 0..10u8 {
+            let mut peer = create_test_peer(i as u32, 1, [i; 20], ip, false, 1000);
+            // Peer 0 comes from a privileged port, peer 1 is marked by the backend below
+            if i == 0 {
+                peer = Peer::new(0, 1, [0; 20], ip, 80, 0, 0, 1000, 1000, String::new());
+            }
+            store.add_peer(info_hash, peer).unwrap();
+        }
+        assert!(store.set_connectable(info_hash, [1; 20], Some(false)));
+        assert!(store.set_connectable(info_hash, [2; 20], Some(true)));
+        assert!(!store.set_connectable(info_hash, [42; 20], Some(true)));
+
+        for _ in 0..20 {
+            let peers = store.get_peers_and_stats(info_hash, 50, [99; 20], PeerOrder::Random).0;
+            assert_eq!(peers.len(), 10);
+            assert!(peers[8..].iter().all(|peer| peer.connectable == Some(false)));
+
+            // Dropped first once numwant is short
+            let peers = store.get_peers_and_stats(info_hash, 8, [99; 20], PeerOrder::Random).0;
+            assert!(peers.iter().all(|peer| peer.connectable != Some(false)));
+        }
+    }
+
+    #[test]
+    fn test_user_torrent_tracking() {
+        let store = PeerStore::new();
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        store.add_peer([1u8; 20], create_test_peer(7, 1, [1u8; 20], ip, true, 1000)).unwrap();
+        store.add_peer([1u8; 20], create_test_peer(7, 1, [2u8; 20], ip, false, 1000)).unwrap();
+        store.add_peer([2u8; 20], create_test_peer(7, 2, [3u8; 20], ip, true, 1000)).unwrap();
+        // Re-adding an existing peer does not count twice
+        store.add_peer([2u8; 20], create_test_peer(7, 2, [3u8; 20], ip, true, 1100)).unwrap();
+        assert_eq!(store.user_torrent_count(7), 2);
+        assert!(store.user_in_torrent(7, 1));
+
+        // The torrent stays until the user's last peer in it goes
+        store.remove_peer([1u8; 20], [1u8; 20]).unwrap();
+        assert!(store.user_in_torrent(7, 1));
+        store.remove_peer([1u8; 20], [2u8; 20]).unwrap();
+        assert!(!store.user_in_torrent(7, 1));
+        assert_eq!(store.user_torrent_count(7), 1);
+
+        store.remove_torrent([2u8; 20]);
+        assert_eq!(store.user_torrent_count(7), 0);
+        assert!(store.user_torrents.is_empty());
+    }
+
+    #[test]
+    fn test_remove_endpoint_duplicates() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        // The same client before a restart, as a seeder, plus another user's peer behind the same address
+        store.add_peer(info_hash, create_test_peer(1, 1, [1u8; 20], ip, true, 1000)).unwrap();
+        store.add_peer(info_hash, create_test_peer(2, 1, [2u8; 20], ip, false, 1000)).unwrap();
+        assert_eq!(store.get_stats(info_hash), (1, 1));
+
+        let restarted = create_test_peer(1, 1, [3u8; 20], ip, false, 2000);
+        assert_eq!(store.remove_endpoint_duplicates(info_hash, &restarted).unwrap(), 1);
+        store.add_peer(info_hash, restarted).unwrap();
+
+        assert!(store.get_peer(info_hash, [1u8; 20]).is_none());
+        assert!(store.get_peer(info_hash, [2u8; 20]).is_some());
+        assert_eq!(store.get_stats(info_hash), (0, 2));
+        assert_eq!(store.user_torrent_count(1), 1);
+
+        // Nothing left to replace on the next restart from a different port
+        let mut moved = create_test_peer(1, 1, [4u8; 20], ip, false, 3000);
+        moved.port = 6882;
+        assert_eq!(store.remove_endpoint_duplicates(info_hash, &moved).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_future_announce_is_clamped_and_reaped() {
+        let store = PeerStore::new();
+        let info_hash = [1u8; 20];
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let now = current_timestamp();
+
+        store.add_peer(info_hash, create_test_peer(1, 1, [1u8; 20], ip, true, now + 86_400)).unwrap();
+        assert!(store.get_peer(info_hash, [1u8; 20]).unwrap().last_announce <= current_timestamp());
+
+        // A future timestamp that got in some other way is clamped by cleanup
+        store.peers.get(&info_hash).unwrap().get_mut(&[1u8; 20]).unwrap().last_announce = now + 86_400;
+        assert_eq!(store.cleanup_stale_peers(3600, |_| None), 0);
+        assert!(store.get_peer(info_hash, [1u8; 20]).unwrap().last_announce <= current_timestamp());
+
+        // Once clamped it expires like any other peer
+        assert_eq!(store.cleanup_stale_peers(-1, |_| None), 1);
+        assert_eq!(store.total_peers(), 0);
+    }
 }
@@ -0,0 +1,233 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use rand::Rng;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+/// Most passkeys remembered at once; an old rejection is evicted beyond this
+const MAX_REJECTED_PASSKEYS: usize = 4096;
+
+/// Rejections compared when picking one to evict from a full cache
+const EVICTION_SAMPLE: usize = 8;
+
+/// Why a passkey was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    UnknownPasskey,
+    UserDisabled,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RejectedEntry {
+    rejection: Rejection,
+    rejected_at: i64,
+    /// Announces short-circuited since the rejection was logged
+    repeats: u32,
+}
+
+/// Short-lived cache of passkeys that recently failed authentication
+///
+/// Clients of deactivated or deleted users keep announcing; remembering the failure for
+/// a few seconds skips the user lookup and the warn log on every repeat. Entries must be
+/// invalidated when a user is added or the user cache is replaced.
+pub struct RejectedPasskeyCache {
+    entries: DashMap<[u8; 32], RejectedEntry>,
+    /// Number of cached passkeys, kept alongside the map since `DashMap::len` locks every shard
+    count: AtomicUsize,
+    /// When expired rejections were last dropped
+    last_prune: AtomicI64,
+    ttl: i64,
+}
+
+impl RejectedPasskeyCache {
+    /// Create a cache remembering rejections for `ttl` seconds (0 disables it)
+    pub fn new(ttl: u64) -> Self {
+        Self {
+            entries: DashMap::new(),
+            count: AtomicUsize::new(0),
+            last_prune: AtomicI64::new(i64::MIN),
+            ttl: ttl as i64,
+        }
+    }
+
+    /// Cached rejection for a passkey, counting the repeat
+    ///
+    /// Expired entries are left for the next `insert` to replace, so it can report the
+    /// repeats they absorbed.
+    pub fn get(&self, passkey: &[u8; 32], now: i64) -> Option<Rejection> {
+        let mut entry = self.entries.get_mut(passkey)?;
+        if now - entry.rejected_at >= self.ttl {
+            return None;
+        }
+        entry.repeats += 1;
+        Some(entry.rejection)
+    }
+
+    /// Remember a rejection, returning how many repeats the previous entry for this
+    /// passkey absorbed so the caller can include them in its log line
+    pub fn insert(&self, passkey: [u8; 32], rejection: Rejection, now: i64) -> u32 {
+        if self.ttl <= 0 {
+            return 0;
+        }
+
+        if self.count.load(Ordering::Relaxed) >= MAX_REJECTED_PASSKEYS && !self.entries.contains_key(&passkey) {
+            self.make_room(now);
+        }
+
+        let entry = RejectedEntry { rejection, rejected_at: now, repeats: 0 };
+        match self.entries.entry(passkey) {
+            Entry::Occupied(mut occupied) => occupied.insert(entry).repeats,
+            Entry::Vacant(vacant) => {
+                self.count.fetch_add(1, Ordering::Relaxed);
+                vacant.insert(entry);
+                0
+            }
+        }
+    }
+
+    /// Free a slot for a new passkey in a full cache
+    ///
+    /// Expired rejections are dropped at most once per ttl, since every new passkey
+    /// arriving at a full cache ends up here; if that frees nothing, an old rejection
+    /// among a small sample is evicted.
+    fn make_room(&self, now: i64) {
+        let last_prune = self.last_prune.load(Ordering::Relaxed);
+        if now.saturating_sub(last_prune) >= self.ttl
+            && self
+                .last_prune
+                .compare_exchange(last_prune, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.remove_where(|entry| now - entry.rejected_at >= self.ttl);
+            if self.count.load(Ordering::Relaxed) < MAX_REJECTED_PASSKEYS {
+                return;
+            }
+        }
+
+        let shards = self.entries.shards();
+        let start = rand::rng().random_range(0..shards.len());
+        let oldest = (0..shards.len()).find_map(|offset| {
+            let shard = shards[(start + offset) % shards.len()].read();
+            shard
+                .iter()
+                .take(EVICTION_SAMPLE)
+                .min_by_key(|(_, entry)| entry.get().rejected_at)
+                .map(|(passkey, _)| *passkey)
+        });
+        if let Some(oldest) = oldest {
+            self.invalidate(&oldest);
+        }
+    }
+
+    /// Remove the rejections matching `remove`
+    fn remove_where(&self, remove: impl Fn(&RejectedEntry) -> bool) {
+        let mut removed = 0;
+        self.entries.retain(|_, entry| {
+            let keep = !remove(entry);
+            removed += usize::from(!keep);
+            keep
+        });
+        self.count.fetch_sub(removed, Ordering::Relaxed);
+    }
+
+    /// Forget a passkey, e.g. because its user was added or re-enabled
+    pub fn invalidate(&self, passkey: &[u8; 32]) {
+        if self.entries.remove(passkey).is_some() {
+            self.count.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Forget every rejection, e.g. after the user cache was reloaded
+    pub fn clear(&self) {
+        self.remove_where(|_| true);
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection_is_cached_until_ttl() {
+        let cache = RejectedPasskeyCache::new(5);
+        let passkey = [1u8; 32];
+
+        assert_eq!(cache.get(&passkey, 1000), None);
+        cache.insert(passkey, Rejection::UserDisabled, 1000);
+
+        assert_eq!(cache.get(&passkey, 1004), Some(Rejection::UserDisabled));
+        assert_eq!(cache.get(&passkey, 1005), None);
+    }
+
+    #[test]
+    fn test_insert_reports_repeats() {
+        let cache = RejectedPasskeyCache::new(5);
+        let passkey = [1u8; 32];
+
+        assert_eq!(cache.insert(passkey, Rejection::UnknownPasskey, 1000), 0);
+        cache.get(&passkey, 1001);
+        cache.get(&passkey, 1002);
+        assert_eq!(cache.get(&passkey, 1006), None);
+
+        assert_eq!(cache.insert(passkey, Rejection::UnknownPasskey, 1006), 2);
+    }
+
+    #[test]
+    fn test_invalidate_and_clear() {
+        let cache = RejectedPasskeyCache::new(5);
+        cache.insert([1u8; 32], Rejection::UnknownPasskey, 1000);
+        cache.insert([2u8; 32], Rejection::UserDisabled, 1000);
+
+        cache.invalidate(&[1u8; 32]);
+        assert_eq!(cache.get(&[1u8; 32], 1000), None);
+        assert_eq!(cache.len(), 1);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_zero_ttl_disables_cache() {
+        let cache = RejectedPasskeyCache::new(0);
+        cache.insert([1u8; 32], Rejection::UnknownPasskey, 1000);
+
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let cache = RejectedPasskeyCache::new(1_000_000);
+        for i in 0..MAX_REJECTED_PASSKEYS + 10 {
+            let mut passkey = [0u8; 32];
+            passkey[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            cache.insert(passkey, Rejection::UnknownPasskey, 1000 + i as i64);
+        }
+
+        assert_eq!(cache.len(), MAX_REJECTED_PASSKEYS);
+        assert_eq!(cache.entries.len(), MAX_REJECTED_PASSKEYS);
+    }
+
+    #[test]
+    fn test_full_cache_drops_expired_rejections() {
+        let cache = RejectedPasskeyCache::new(5);
+        for i in 0..MAX_REJECTED_PASSKEYS {
+            let mut passkey = [0u8; 32];
+            passkey[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            cache.insert(passkey, Rejection::UnknownPasskey, 1000);
+        }
+
+        // All rejections have expired, so one prune frees the whole cache
+        cache.insert([0xff; 32], Rejection::UnknownPasskey, 1005);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&[0xff; 32], 1005), Some(Rejection::UnknownPasskey));
+    }
+}
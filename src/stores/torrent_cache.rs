@@ -1,10 +1,43 @@
+use crate::core::error::CacheFullError;
 use crate::models::torrent::Torrent;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use tracing::warn;
+
+/// A torrent id / info_hash pair that disagrees with an existing cache entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TorrentConflict {
+    /// The info_hash is already cached under a different torrent id
+    InfoHashHasOtherId { existing_id: u32 },
+    /// The torrent id is already mapped to a different info_hash
+    IdHasOtherInfoHash { existing_info_hash: [u8; 20] },
+}
+
+impl fmt::Display for TorrentConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TorrentConflict::InfoHashHasOtherId { existing_id } => {
+                write!(f, "info_hash is already registered to torrent id {}", existing_id)
+            }
+            TorrentConflict::IdHasOtherInfoHash { existing_info_hash } => {
+                write!(f, "torrent id is already registered to info_hash {}", hex::encode(existing_info_hash))
+            }
+        }
+    }
+}
 
 /// In-memory cache for torrent data
 pub struct TorrentCache {
     torrents: DashMap<[u8; 20], Arc<Torrent>>,
+    /// Index of torrent id to info_hash
+    ids: DashMap<u32, [u8; 20]>,
+    /// Number of cached torrents, kept alongside the map since `DashMap::len` locks every shard
+    count: AtomicUsize,
+    /// Most torrents held at once (0 means unbounded)
+    max_torrents: usize,
 }
 
 impl TorrentCache {
@@ -12,26 +45,116 @@ impl TorrentCache {
     pub fn new() -> Self {
         Self {
             torrents: DashMap::new(),
+            ids: DashMap::new(),
+            count: AtomicUsize::new(0),
+            max_torrents: 0,
         }
     }
 
+    /// Create a cache holding at most `capacity` torrents
+    #[cfg(test)]
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             torrents: DashMap::with_capacity(capacity),
+            ids: DashMap::with_capacity(capacity),
+            count: AtomicUsize::new(0),
+            max_torrents: capacity,
+        }
+    }
+
+    /// Create a cache holding at most `capacity` torrents, split into `shard_amount` shards
+    pub fn with_capacity_and_shard_amount(capacity: usize, shard_amount: usize) -> Self {
+        Self {
+            torrents: DashMap::with_capacity_and_shard_amount(capacity, shard_amount),
+            ids: DashMap::with_capacity_and_shard_amount(capacity, shard_amount),
+            count: AtomicUsize::new(0),
+            max_torrents: capacity,
+        }
+    }
+
+    /// Check whether adding `id` under `info_hash` would conflict with an existing mapping
+    pub fn find_conflict(&self, id: u32, info_hash: [u8; 20]) -> Option<TorrentConflict> {
+        if let Some(existing) = self.torrents.get(&info_hash) {
+            if existing.id != id {
+                return Some(TorrentConflict::InfoHashHasOtherId { existing_id: existing.id });
+            }
+        }
+
+        match self.ids.get(&id) {
+            Some(existing_info_hash) if *existing_info_hash != info_hash => {
+                Some(TorrentConflict::IdHasOtherInfoHash { existing_info_hash: *existing_info_hash })
+            }
+            _ => None,
         }
     }
 
     /// Add a torrent to the cache
-    /// If a torrent with the same info_hash already exists, it will be replaced
-    pub fn add_torrent(&self, torrent: Torrent) {
+    /// If a torrent with the same info_hash already exists, it will be replaced.
+    /// Conflicting id mappings are logged and returned; the new mapping wins.
+    /// A new info_hash is rejected once the cache holds its maximum.
+    pub fn add_torrent(&self, torrent: Torrent) -> Result<Option<TorrentConflict>, CacheFullError> {
+        let id = torrent.id;
         let info_hash = torrent.info_hash;
-        self.torrents.insert(info_hash, Arc::new(torrent));
+
+        let conflict = self.find_conflict(id, info_hash);
+        let old = match self.torrents.entry(info_hash) {
+            Entry::Occupied(mut entry) => Some(entry.insert(Arc::new(torrent))),
+            Entry::Vacant(entry) => {
+                // The slot is reserved while the shard is locked, so concurrent adds
+                // cannot take the cache past its maximum
+                let reserved = self.count.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    (self.max_torrents == 0 || count < self.max_torrents).then_some(count + 1)
+                });
+                if reserved.is_err() {
+                    return Err(CacheFullError { capacity: self.max_torrents });
+                }
+                entry.insert(Arc::new(torrent));
+                None
+            }
+        };
+
+        if let Some(ref conflict) = conflict {
+            warn!(
+                torrent_id = id,
+                info_hash = %hex::encode(info_hash),
+                conflict = %conflict,
+                "Torrent id mapping conflict"
+            );
+        }
+        
+        if let Some(old) = old {
+            if old.id != id {
+                self.ids.remove_if(&old.id, |_, hash| *hash == info_hash);
+            }
+        }
+        self.ids.insert(id, info_hash);
+        
+        Ok(conflict)
     }
 
     /// Remove a torrent from the cache by info_hash
     /// Returns the removed torrent if it existed
     pub fn remove_torrent(&self, info_hash: [u8; 20]) -> Option<Arc<Torrent>> {
-        self.torrents.remove(&info_hash).map(|(_, torrent)| torrent)
+        let removed = self.torrents.remove(&info_hash).map(|(_, torrent)| torrent);
+        
+        if let Some(ref torrent) = removed {
+            self.count.fetch_sub(1, Ordering::Relaxed);
+            self.ids.remove_if(&torrent.id, |_, hash| *hash == info_hash);
+        }
+        
+        removed
+    }
+
+    /// Pause or resume a torrent in place
+    ///
+    /// The cached `Arc<Torrent>` is replaced, so announces already holding the old one
+    /// finish with it. Returns the updated torrent, or `None` if it is not cached.
+    pub fn set_active(&self, info_hash: [u8; 20], active: bool) -> Option<Arc<Torrent>> {
+        let mut entry = self.torrents.get_mut(&info_hash)?;
+        let mut torrent = Torrent::clone(&entry);
+        torrent.is_active = active;
+        *entry = Arc::new(torrent);
+        Some(Arc::clone(&entry))
     }
 
     /// Get a torrent from the cache by info_hash
@@ -40,17 +163,43 @@ impl TorrentCache {
         self.torrents.get(&info_hash).map(|entry| Arc::clone(entry.value()))
     }
 
+    /// Get a torrent from the cache by torrent id
+    pub fn get_torrent_by_id(&self, id: u32) -> Option<Arc<Torrent>> {
+        let info_hash = *self.ids.get(&id)?;
+        self.get_torrent(info_hash)
+    }
+
+    /// Get a point-in-time list of all cached torrents
+    pub fn snapshot(&self) -> Vec<Arc<Torrent>> {
+        self.torrents.iter().map(|entry| Arc::clone(entry.value())).collect()
+    }
+
+    /// Get up to `limit` cached torrents after skipping `offset`
+    ///
+    /// Iteration order is arbitrary but stays the same while the cache is unchanged.
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<Arc<Torrent>> {
+        self.torrents
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|entry| Arc::clone(entry.value()))
+            .collect()
+    }
+
+    #[cfg(test)]
     pub fn clear(&self) {
         self.torrents.clear();
+        self.ids.clear();
+        self.count.store(0, Ordering::Relaxed);
     }
 
 
     pub fn len(&self) -> usize {
-        self.torrents.len()
+        self.count.load(Ordering::Relaxed)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.torrents.is_empty()
+        self.len() == 0
     }
 }
 
@@ -59,3 +208,132 @@ impl Default for TorrentCache {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_torrent_respects_capacity() {
+        let cache = TorrentCache::with_capacity(2);
+        cache.add_torrent(Torrent::new(1, [1u8; 20], false, true)).unwrap();
+        cache.add_torrent(Torrent::new(2, [2u8; 20], false, true)).unwrap();
+
+        assert_eq!(
+            cache.add_torrent(Torrent::new(3, [3u8; 20], false, true)),
+            Err(CacheFullError { capacity: 2 })
+        );
+        assert!(cache.get_torrent([3u8; 20]).is_none());
+
+        // Updating an existing torrent is still allowed
+        cache.add_torrent(Torrent::new(1, [1u8; 20], true, true)).unwrap();
+        assert!(cache.get_torrent([1u8; 20]).unwrap().is_freeleech);
+        assert_eq!(cache.len(), 2);
+
+        // A removal frees a slot
+        cache.remove_torrent([2u8; 20]);
+        cache.add_torrent(Torrent::new(3, [3u8; 20], false, true)).unwrap();
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_concurrent_adds_stay_within_capacity() {
+        let cache = TorrentCache::with_capacity(50);
+        std::thread::scope(|scope| {
+            for thread in 0..4u32 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for i in 0..100u32 {
+                        let id = thread * 100 + i;
+                        let mut info_hash = [0u8; 20];
+                        info_hash[..4].copy_from_slice(&id.to_be_bytes());
+                        let _ = cache.add_torrent(Torrent::new(id, info_hash, false, true));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(cache.len(), 50);
+        assert_eq!(cache.snapshot().len(), 50);
+    }
+
+    #[test]
+    fn test_add_torrent_no_conflict() {
+        let cache = TorrentCache::new();
+        
+        assert!(cache.add_torrent(Torrent::new(1, [1u8; 20], false, true)).unwrap().is_none());
+        // Re-adding the same mapping is not a conflict
+        assert!(cache.add_torrent(Torrent::new(1, [1u8; 20], true, true)).unwrap().is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_add_torrent_info_hash_conflict() {
+        let cache = TorrentCache::new();
+        cache.add_torrent(Torrent::new(1, [1u8; 20], false, true)).unwrap();
+        
+        let conflict = cache.add_torrent(Torrent::new(2, [1u8; 20], false, true)).unwrap();
+        assert_eq!(conflict, Some(TorrentConflict::InfoHashHasOtherId { existing_id: 1 }));
+        
+        // The new mapping replaced the old one
+        assert_eq!(cache.get_torrent([1u8; 20]).unwrap().id, 2);
+        assert!(cache.find_conflict(1, [2u8; 20]).is_none());
+    }
+
+    #[test]
+    fn test_add_torrent_id_conflict() {
+        let cache = TorrentCache::new();
+        cache.add_torrent(Torrent::new(1, [1u8; 20], false, true)).unwrap();
+        
+        assert_eq!(
+            cache.find_conflict(1, [2u8; 20]),
+            Some(TorrentConflict::IdHasOtherInfoHash { existing_info_hash: [1u8; 20] })
+        );
+    }
+
+    #[test]
+    fn test_get_torrent_by_id() {
+        let cache = TorrentCache::new();
+        cache.add_torrent(Torrent::new(1, [1u8; 20], false, true)).unwrap();
+        
+        assert_eq!(cache.get_torrent_by_id(1).unwrap().info_hash, [1u8; 20]);
+        assert!(cache.get_torrent_by_id(2).is_none());
+        
+        // Replacing the torrent under a new id moves the index entry
+        cache.add_torrent(Torrent::new(2, [1u8; 20], false, true)).unwrap();
+        assert!(cache.get_torrent_by_id(1).is_none());
+        assert_eq!(cache.get_torrent_by_id(2).unwrap().info_hash, [1u8; 20]);
+        
+        cache.clear();
+        assert!(cache.get_torrent_by_id(2).is_none());
+    }
+
+    #[test]
+    fn test_remove_torrent_clears_id_index() {
+        let cache = TorrentCache::new();
+        cache.add_torrent(Torrent::new(1, [1u8; 20], false, true)).unwrap();
+        cache.remove_torrent([1u8; 20]);
+        
+        assert!(cache.find_conflict(1, [2u8; 20]).is_none());
+    }
+
+    #[test]
+    fn test_page() {
+        let cache = TorrentCache::new();
+        for id in 0..5u8 {
+            cache.add_torrent(Torrent::new(id as u32, [id; 20], false, true)).unwrap();
+        }
+
+        let first = cache.page(0, 2);
+        let second = cache.page(2, 2);
+        let last = cache.page(4, 2);
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 2);
+        assert_eq!(last.len(), 1);
+        assert!(cache.page(5, 2).is_empty());
+
+        let mut ids: Vec<u32> = first.iter().chain(&second).chain(&last).map(|t| t.id).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,103 @@
+use dashmap::DashMap;
+
+/// Most info_hashes remembered at once; the oldest is evicted beyond this
+const MAX_UNKNOWN_TORRENTS: usize = 4096;
+
+/// Short-lived cache of info_hashes the external API did not know
+///
+/// On-demand torrent lookups consult this first, so clients announcing a deleted or
+/// made-up torrent cause one backend request per TTL rather than one per announce.
+pub struct UnknownTorrentCache {
+    /// Info_hash to the Unix time of the lookup that missed
+    entries: DashMap<[u8; 20], i64>,
+    ttl: i64,
+}
+
+impl UnknownTorrentCache {
+    /// Create a cache remembering misses for `ttl` seconds (0 disables it)
+    pub fn new(ttl: u64) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl: ttl as i64,
+        }
+    }
+
+    /// Whether a lookup for this info_hash missed within the TTL
+    pub fn contains(&self, info_hash: &[u8; 20], now: i64) -> bool {
+        self.entries
+            .get(info_hash)
+            .is_some_and(|missed_at| now - *missed_at < self.ttl)
+    }
+
+    /// Remember a missed lookup
+    pub fn insert(&self, info_hash: [u8; 20], now: i64) {
+        if self.ttl <= 0 {
+            return;
+        }
+
+        if self.entries.len() >= MAX_UNKNOWN_TORRENTS && !self.entries.contains_key(&info_hash) {
+            self.entries.retain(|_, missed_at| now - *missed_at < self.ttl);
+            if self.entries.len() >= MAX_UNKNOWN_TORRENTS {
+                let oldest = self
+                    .entries
+                    .iter()
+                    .min_by_key(|entry| *entry.value())
+                    .map(|entry| *entry.key());
+                if let Some(oldest) = oldest {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+
+        self.entries.insert(info_hash, now);
+    }
+
+    /// Forget every miss, e.g. after the torrent cache was reloaded
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_miss_is_cached_until_ttl() {
+        let cache = UnknownTorrentCache::new(5);
+        let info_hash = [1u8; 20];
+
+        assert!(!cache.contains(&info_hash, 1000));
+        cache.insert(info_hash, 1000);
+        assert!(cache.contains(&info_hash, 1004));
+        assert!(!cache.contains(&info_hash, 1005));
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_and_bounded() {
+        let cache = UnknownTorrentCache::new(0);
+        cache.insert([1u8; 20], 1000);
+        assert!(cache.is_empty());
+
+        let cache = UnknownTorrentCache::new(5);
+        for i in 0..=MAX_UNKNOWN_TORRENTS as u32 {
+            let mut info_hash = [0u8; 20];
+            info_hash[..4].copy_from_slice(&i.to_be_bytes());
+            cache.insert(info_hash, 1000 + i as i64 % 3);
+        }
+        assert_eq!(cache.len(), MAX_UNKNOWN_TORRENTS);
+    }
+}
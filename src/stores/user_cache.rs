@@ -1,10 +1,17 @@
+use crate::core::error::CacheFullError;
 use crate::models::user::User;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 /// In-memory cache for user data
 pub struct UserCache {
     users: DashMap<[u8; 32], Arc<User>>,
+    /// Number of cached users, kept alongside the map since `DashMap::len` locks every shard
+    count: AtomicUsize,
+    /// Most users held at once (0 means unbounded)
+    max_users: usize,
 }
 
 impl UserCache {
@@ -12,27 +19,49 @@ impl UserCache {
     pub fn new() -> Self {
         Self {
             users: DashMap::new(),
+            count: AtomicUsize::new(0),
+            max_users: 0,
         }
     }
 
-
-    pub fn with_capacity(capacity: usize) -> Self {
+    /// Create a cache holding at most `capacity` users, split into `shard_amount` shards
+    pub fn with_capacity_and_shard_amount(capacity: usize, shard_amount: usize) -> Self {
         Self {
-            users: DashMap::with_capacity(capacity),
+            users: DashMap::with_capacity_and_shard_amount(capacity, shard_amount),
+            count: AtomicUsize::new(0),
+            max_users: capacity,
         }
     }
 
     /// Add a user to the cache
-    /// If a user with the same passkey already exists, it will be replaced
-    pub fn add_user(&self, user: User) {
-        let passkey = user.passkey;
-        self.users.insert(passkey, Arc::new(user));
+    /// If a user with the same passkey already exists, it will be replaced.
+    /// A new passkey is rejected once the cache holds its maximum.
+    pub fn add_user(&self, user: User) -> Result<(), CacheFullError> {
+        match self.users.entry(user.passkey) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(Arc::new(user));
+            }
+            Entry::Vacant(entry) => {
+                // The slot is reserved while the shard is locked, so concurrent adds
+                // cannot take the cache past its maximum
+                let reserved = self.count.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    (self.max_users == 0 || count < self.max_users).then_some(count + 1)
+                });
+                if reserved.is_err() {
+                    return Err(CacheFullError { capacity: self.max_users });
+                }
+                entry.insert(Arc::new(user));
+            }
+        }
+        Ok(())
     }
 
     /// Remove a user from the cache by passkey
     /// Returns the removed user if it existed
     pub fn remove_user(&self, passkey: [u8; 32]) -> Option<Arc<User>> {
-        self.users.remove(&passkey).map(|(_, user)| user)
+        let (_, user) = self.users.remove(&passkey)?;
+        self.count.fetch_sub(1, Ordering::Relaxed);
+        Some(user)
     }
 
     /// Get a user from the cache by passkey
@@ -52,18 +81,19 @@ impl UserCache {
     }
 
 
-    pub fn clear(&self) {
-        self.users.clear();
+    /// Get a point-in-time list of all cached users
+    pub fn snapshot(&self) -> Vec<Arc<User>> {
+        self.users.iter().map(|entry| Arc::clone(entry.value())).collect()
     }
 
 
     pub fn len(&self) -> usize {
-        self.users.len()
+        self.count.load(Ordering::Relaxed)
     }
 
 
     pub fn is_empty(&self) -> bool {
-        self.users.is_empty()
+        self.len() == 0
     }
 }
 
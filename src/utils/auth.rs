@@ -1,3 +1,9 @@
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
 /// Verify API key using constant-time comparison to prevent timing attacks
 /// 
 /// This function compares two strings in constant time to prevent timing attacks
@@ -12,6 +18,76 @@ pub fn verify_api_key(provided: &str, expected: &str) -> bool {
             == 0
 }
 
+/// Stateless passkey issued by the backend in the form `user_id.expiry.signature`
+///
+/// The signature is the hex-encoded HMAC-SHA256 of `user_id.expiry`, keyed with the
+/// shared secret from the `[auth]` config section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedPasskey {
+    pub user_id: u32,
+    pub expiry: i64,
+    pub signature: [u8; 32],
+}
+
+impl SignedPasskey {
+    /// Parse a signed passkey token without verifying it
+    pub fn parse(token: &str) -> Result<Self> {
+        let mut parts = token.split('.');
+
+        let (Some(user_id), Some(expiry), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Signed passkey must have the form user_id.expiry.signature");
+        };
+
+        let user_id = user_id.parse::<u32>().context("Invalid user ID in signed passkey")?;
+        let expiry = expiry.parse::<i64>().context("Invalid expiry in signed passkey")?;
+
+        let signature_bytes = hex::decode(signature).context("Invalid signature hex")?;
+        let signature: [u8; 32] = signature_bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Signature must be 32 bytes"))?;
+
+        Ok(Self {
+            user_id,
+            expiry,
+            signature,
+        })
+    }
+
+    /// Verify the signature using a constant-time comparison
+    ///
+    /// Expiry is checked separately with `is_expired` so callers can report it distinctly.
+    pub fn verify_signature(&self, secret: &str) -> Result<()> {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+            .context("Invalid passkey secret")?;
+        mac.update(format!("{}.{}", self.user_id, self.expiry).as_bytes());
+
+        mac.verify_slice(&self.signature)
+            .map_err(|_| anyhow::anyhow!("Signature mismatch"))
+    }
+
+    /// Whether the token has expired
+    pub fn is_expired(&self, current_time: i64) -> bool {
+        self.expiry < current_time
+    }
+}
+
+/// Issue a signed passkey token for a user, as the backend does
+#[cfg(test)]
+pub fn sign_passkey(user_id: u32, expiry: i64, secret: &str) -> String {
+    let signature = hmac_hex(secret, format!("{}.{}", user_id, expiry).as_bytes());
+    format!("{}.{}.{}", user_id, expiry, signature)
+}
+
+/// Hex-encoded HMAC-SHA256 of `data`, keyed with `secret`
+pub fn hmac_hex(secret: &str, data: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(data);
+    hex::encode(mac.finalize().into_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,4 +121,50 @@ mod tests {
     fn test_verify_api_key_special_chars() {
         assert!(verify_api_key("key-with-$pecial!", "key-with-$pecial!"));
     }
+
+    #[test]
+    fn test_signed_passkey_roundtrip() {
+        let token = sign_passkey(42, 2000, "secret");
+        let signed = SignedPasskey::parse(&token).unwrap();
+
+        assert_eq!(signed.user_id, 42);
+        assert_eq!(signed.expiry, 2000);
+        assert!(signed.verify_signature("secret").is_ok());
+        assert!(!signed.is_expired(1000));
+    }
+
+    #[test]
+    fn test_signed_passkey_wrong_secret() {
+        let token = sign_passkey(42, 2000, "secret");
+        let signed = SignedPasskey::parse(&token).unwrap();
+
+        assert!(signed.verify_signature("other-secret").is_err());
+    }
+
+    #[test]
+    fn test_signed_passkey_expired() {
+        let token = sign_passkey(42, 2000, "secret");
+        let signed = SignedPasskey::parse(&token).unwrap();
+
+        assert!(signed.verify_signature("secret").is_ok());
+        assert!(signed.is_expired(2001));
+    }
+
+    #[test]
+    fn test_signed_passkey_tampered_user_id() {
+        let token = sign_passkey(42, 2000, "secret");
+        let tampered = token.replacen("42", "43", 1);
+        let signed = SignedPasskey::parse(&tampered).unwrap();
+
+        assert!(signed.verify_signature("secret").is_err());
+    }
+
+    #[test]
+    fn test_signed_passkey_parse_invalid() {
+        assert!(SignedPasskey::parse("").is_err());
+        assert!(SignedPasskey::parse("42.2000").is_err());
+        assert!(SignedPasskey::parse("42.2000.abcd").is_err());
+        assert!(SignedPasskey::parse("x.2000.00").is_err());
+        assert!(SignedPasskey::parse("42.2000.00.00").is_err());
+    }
 }
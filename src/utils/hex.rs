@@ -37,18 +37,17 @@ pub fn bytes_to_hex(bytes: &[u8]) -> String {
         .collect()
 }
 
-/// Decode URL-encoded bytes (percent-encoding)
+/// Decode percent-encoded binary data like info_hash and peer_id
 /// 
-/// This function handles URL-encoded strings like those used for info_hash and peer_id
-/// in BitTorrent announce requests. It decodes percent-encoded sequences (%XX) and
-/// returns the raw bytes.
+/// Clients only have to percent-encode unsafe bytes, so raw characters are taken as
+/// their byte value and `+` is kept as 0x2B rather than turned into a space.
 /// 
 /// # Arguments
-/// * `encoded` - A URL-encoded string
+/// * `encoded` - A percent-encoded string
 /// 
 /// # Returns
 /// * `Result<Vec<u8>>` - The decoded bytes or an error if the encoding is invalid
-pub fn url_decode(encoded: &str) -> Result<Vec<u8>> {
+pub fn url_decode_binary(encoded: &str) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(encoded.len());
     let mut chars = encoded.chars().peekable();
 
@@ -67,10 +66,6 @@ pub fn url_decode(encoded: &str) -> Result<Vec<u8>> {
                 
                 decoded.push(byte);
             }
-            '+' => {
-                // '+' is decoded as space in URL encoding
-                decoded.push(b' ');
-            }
             _ => {
                 // Regular ASCII character
                 decoded.push(ch as u8);
@@ -116,40 +111,67 @@ mod tests {
     }
 
     #[test]
-    fn test_url_decode() {
+    fn test_url_decode_binary() {
         // Simple ASCII
-        assert_eq!(url_decode("hello").unwrap(), b"hello");
+        assert_eq!(url_decode_binary("hello").unwrap(), b"hello");
         
         // Percent-encoded
-        assert_eq!(url_decode("%48%65%6c%6c%6f").unwrap(), b"Hello");
+        assert_eq!(url_decode_binary("%48%65%6c%6c%6f").unwrap(), b"Hello");
         
         // Mixed
-        assert_eq!(url_decode("hello%20world").unwrap(), b"hello world");
-        
-        // Plus sign as space
-        assert_eq!(url_decode("hello+world").unwrap(), b"hello world");
+        assert_eq!(url_decode_binary("hello%20world").unwrap(), b"hello world");
         
         // Binary data (like info_hash)
         assert_eq!(
-            url_decode("%de%ad%be%ef").unwrap(),
+            url_decode_binary("%de%ad%be%ef").unwrap(),
             vec![0xde, 0xad, 0xbe, 0xef]
         );
     }
 
     #[test]
     fn test_url_decode_invalid() {
-        assert!(url_decode("%").is_err()); // Incomplete
-        assert!(url_decode("%1").is_err()); // Incomplete
-        assert!(url_decode("%GG").is_err()); // Invalid hex
+        assert!(url_decode_binary("%").is_err()); // Incomplete
+        assert!(url_decode_binary("%1").is_err()); // Incomplete
+        assert!(url_decode_binary("%GG").is_err()); // Invalid hex
     }
 
     #[test]
     fn test_url_decode_info_hash() {
         // Simulate a real info_hash (20 bytes)
         let encoded = "%12%34%56%78%9a%bc%de%f0%11%22%33%44%55%66%77%88%99%aa%bb%cc";
-        let decoded = url_decode(encoded).unwrap();
+        let decoded = url_decode_binary(encoded).unwrap();
         assert_eq!(decoded.len(), 20);
         assert_eq!(decoded[0], 0x12);
         assert_eq!(decoded[19], 0xcc);
     }
+
+    #[test]
+    fn test_url_decode_binary_keeps_plus() {
+        assert_eq!(url_decode_binary("a+b").unwrap(), b"a+b");
+        assert_eq!(url_decode_binary("%2B+").unwrap(), b"++");
+        assert_eq!(url_decode_binary("%de%ad").unwrap(), vec![0xde, 0xad]);
+        assert!(url_decode_binary("%G0").is_err());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_url_decode_never_panics(encoded in "\\PC{0,256}") {
+            if let Ok(decoded) = url_decode_binary(&encoded) {
+                proptest::prop_assert!(decoded.len() <= encoded.len());
+            }
+        }
+
+        #[test]
+        fn prop_url_decode_percent_sequences(encoded in "[%0-9a-fA-Fg+]{0,256}") {
+            if let Ok(decoded) = url_decode_binary(&encoded) {
+                proptest::prop_assert!(decoded.len() <= encoded.len());
+            }
+        }
+
+        #[test]
+        fn prop_url_decode_roundtrip(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..64)) {
+            let encoded: String = bytes.iter().map(|b| format!("%{:02X}", b)).collect();
+            proptest::prop_assert_eq!(url_decode_binary(&encoded).unwrap(), bytes);
+        }
+    }
 }
@@ -0,0 +1,119 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Convert an IPv4-mapped IPv6 address (`::ffff:a.b.c.d`) back to plain IPv4
+///
+/// Dual-stack listeners deliver IPv4 clients in mapped form; without this they would
+/// be stored and encoded as IPv6 peers and miss IPv4 bans. Other addresses are
+/// returned unchanged.
+pub fn normalize_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// Check whether an IP address is reachable from the public internet
+///
+/// Returns false for loopback, unspecified, multicast, documentation and private
+/// ranges, including IPv6 link-local (`fe80::/10`) and unique-local (`fc00::/7`).
+/// IPv4-mapped IPv6 addresses are classified by their IPv4 address.
+pub fn is_globally_routable(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_globally_routable_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_globally_routable_v4(v4),
+            None => is_globally_routable_v6(v6),
+        },
+    }
+}
+
+fn is_globally_routable_v4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+
+    // 0.0.0.0/8 ("this network") and 100.64.0.0/10 (carrier-grade NAT)
+    let is_this_network = octets[0] == 0;
+    let is_shared = octets[0] == 100 && (octets[1] & 0xc0) == 64;
+
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || is_this_network
+        || is_shared)
+}
+
+fn is_globally_routable_v6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+
+    let is_link_local = (segments[0] & 0xffc0) == 0xfe80;
+    let is_unique_local = (segments[0] & 0xfe00) == 0xfc00;
+    let is_documentation = segments[0] == 0x2001 && segments[1] == 0x0db8;
+
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || is_link_local
+        || is_unique_local
+        || is_documentation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_ip() {
+        let mapped: IpAddr = "::ffff:10.0.0.1".parse().unwrap();
+        assert_eq!(normalize_ip(mapped), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+
+        // Plain addresses, and IPv4-compatible ones, are left alone
+        for ip in ["10.0.0.1", "2001:db8::1", "::10.0.0.1", "::1"] {
+            let ip: IpAddr = ip.parse().unwrap();
+            assert_eq!(normalize_ip(ip), ip);
+        }
+    }
+
+    fn routable(ip: &str) -> bool {
+        is_globally_routable(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_ipv4_routable() {
+        assert!(routable("8.8.8.8"));
+        assert!(routable("203.0.114.1"));
+    }
+
+    #[test]
+    fn test_ipv4_non_routable() {
+        assert!(!routable("10.0.0.1"));
+        assert!(!routable("172.16.5.4"));
+        assert!(!routable("192.168.1.1"));
+        assert!(!routable("127.0.0.1"));
+        assert!(!routable("169.254.1.1"));
+        assert!(!routable("100.64.0.1"));
+        assert!(!routable("0.0.0.0"));
+        assert!(!routable("255.255.255.255"));
+        assert!(!routable("224.0.0.1"));
+    }
+
+    #[test]
+    fn test_ipv6_routable() {
+        assert!(routable("2a00:1450:4001::1"));
+        assert!(routable("::ffff:8.8.8.8"));
+    }
+
+    #[test]
+    fn test_ipv6_non_routable() {
+        assert!(!routable("fe80::1"));
+        assert!(!routable("febf::1"));
+        assert!(!routable("fc00::1"));
+        assert!(!routable("fd12:3456::1"));
+        assert!(!routable("::1"));
+        assert!(!routable("::"));
+        assert!(!routable("ff02::1"));
+        assert!(!routable("2001:db8::1"));
+        assert!(!routable("::ffff:192.168.1.1"));
+    }
+}
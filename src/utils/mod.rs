@@ -1,3 +1,4 @@
 pub mod hex;
 pub mod time;
 pub mod auth;
+pub mod ip;
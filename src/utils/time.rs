@@ -1,21 +1,24 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use rand::Rng;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Time since the Unix epoch, saturating to zero for a clock set before it
+///
+/// A misconfigured clock must not panic the announce path.
+fn since_epoch() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO)
+}
 
 pub fn current_timestamp() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("System time is before Unix epoch")
-        .as_secs() as i64
+    since_epoch().as_secs() as i64
 }
 
 pub fn current_timestamp_millis() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("System time is before Unix epoch")
-        .as_millis() as i64
+    since_epoch().as_millis() as i64
 }
 
+/// Seconds from `start` to `end`, clamped to zero when the clock stepped backwards
 pub fn elapsed_seconds(start: i64, end: i64) -> i64 {
-    end - start
+    (end - start).max(0)
 }
 
 
@@ -23,6 +26,28 @@ pub fn is_expired(timestamp: i64, timeout: i64, current_time: i64) -> bool {
     elapsed_seconds(timestamp, current_time) > timeout
 }
 
+/// Randomize `base` by up to `±jitter_fraction` of its length
+///
+/// The jitter is uniform, so the average over many calls equals `base`.
+pub fn jittered(base: Duration, jitter_fraction: f64) -> Duration {
+    let jitter = base.as_secs_f64() * jitter_fraction.clamp(0.0, 1.0);
+    if jitter <= 0.0 {
+        return base;
+    }
+
+    let offset = rand::rng().random_range(-jitter..=jitter);
+    Duration::from_secs_f64((base.as_secs_f64() + offset).max(0.0))
+}
+
+/// Pick a uniformly random delay in `[0, max)`, used to offset the first run of periodic tasks
+pub fn random_phase(max: Duration) -> Duration {
+    if max.is_zero() {
+        return max;
+    }
+
+    Duration::from_secs_f64(rand::rng().random_range(0.0..max.as_secs_f64()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,7 +75,8 @@ mod tests {
     fn test_elapsed_seconds() {
         assert_eq!(elapsed_seconds(100, 150), 50);
         assert_eq!(elapsed_seconds(1000, 1000), 0);
-        assert_eq!(elapsed_seconds(200, 100), -100);
+        // A backward clock step never yields negative elapsed time
+        assert_eq!(elapsed_seconds(200, 100), 0);
     }
 
     #[test]
@@ -70,6 +96,30 @@ mod tests {
         assert!(is_expired(899, 100, current));
     }
 
+    #[test]
+    fn test_jittered_within_bounds() {
+        let base = Duration::from_secs(300);
+        
+        for _ in 0..1000 {
+            let delay = jittered(base, 0.1);
+            assert!(delay >= Duration::from_secs(270));
+            assert!(delay <= Duration::from_secs(330));
+        }
+        
+        assert_eq!(jittered(base, 0.0), base);
+    }
+
+    #[test]
+    fn test_random_phase_within_bounds() {
+        let max = Duration::from_secs(60);
+        
+        for _ in 0..1000 {
+            assert!(random_phase(max) < max);
+        }
+        
+        assert_eq!(random_phase(Duration::ZERO), Duration::ZERO);
+    }
+
     #[test]
     fn test_is_expired_peer_timeout() {
         // Simulate peer timeout scenario
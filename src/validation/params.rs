@@ -1,12 +1,25 @@
-use crate::utils::hex::url_decode;
+use crate::utils::auth::SignedPasskey;
+use crate::utils::hex::url_decode_binary;
 use anyhow::{anyhow, bail, Context, Result};
 use serde::Deserialize;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Longest URL encoding of a 20-byte info_hash or peer_id: every byte as a `%XX` escape
+const MAX_ENCODED_ID_LEN: usize = 60;
+
+/// Reject an info_hash or peer_id whose encoded length cannot decode to 20 bytes,
+/// before spending an allocation on decoding it
+fn check_encoded_id_length(encoded: &str, name: &str) -> Result<()> {
+    if encoded.len() > MAX_ENCODED_ID_LEN {
+        bail!("{} is too long to encode 20 bytes", name);
+    }
+    Ok(())
+}
 
 /// Query parameters for announce requests
 #[derive(Debug, Deserialize)]
 pub struct AnnounceParams {
-    /// User's 32-character hexadecimal passkey
+    /// User's 32-character hexadecimal passkey, or a signed `user_id.expiry.signature` token
     pub passkey: String,
     
     /// URL-encoded 20-byte info_hash
@@ -41,6 +54,23 @@ pub struct AnnounceParams {
     
     /// Optional IP address override
     pub ip: Option<String>,
+
+    /// Optional client `key`, stable across the client's IP changes
+    pub key: Option<String>,
+
+    /// Optional IPv4 address declared by a dual-stack client (BEP 7)
+    pub ipv4: Option<String>,
+
+    /// Optional IPv6 address declared by a dual-stack client (BEP 7)
+    pub ipv6: Option<String>,
+
+    /// Client supports encrypted peer connections (`supportcrypto=1`)
+    #[serde(default)]
+    pub supportcrypto: bool,
+
+    /// Client only accepts encrypted peer connections (`requirecrypto=1`)
+    #[serde(default)]
+    pub requirecrypto: bool,
 }
 
 fn default_numwant() -> u32 {
@@ -51,9 +81,26 @@ fn default_compact() -> u8 {
     1
 }
 
+/// Longest `key` kept; longer values are ignored as if the key was absent
+const MAX_KEY_LEN: usize = 64;
+
+/// Parse a percent-encoded `ipv4`/`ipv6` value
+///
+/// BEP 7 allows either a bare address or an endpoint such as `[2001:db8::1]:6881`;
+/// the port of an endpoint is ignored in favour of the `port` parameter.
+fn parse_declared_ip(value: &str) -> Result<IpAddr> {
+    let decoded = url_decode_binary(value)?;
+    let decoded = std::str::from_utf8(&decoded).context("Address is not valid UTF-8")?;
+    
+    decoded
+        .parse::<IpAddr>()
+        .or_else(|_| decoded.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .with_context(|| format!("'{}' is not an IP address", decoded))
+}
+
 #[derive(Debug)]
 pub struct ValidatedAnnounceParams {
-    pub passkey: [u8; 32],
+    pub passkey: Passkey,
     pub info_hash: [u8; 20],
     pub peer_id: [u8; 20],
     pub port: u16,
@@ -64,6 +111,29 @@ pub struct ValidatedAnnounceParams {
     pub numwant: u32,
     pub compact: bool,
     pub ip: Option<IpAddr>,
+    pub key: Option<String>,
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+    pub supportcrypto: bool,
+    pub requirecrypto: bool,
+}
+
+/// Credential supplied in the `passkey` parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Passkey {
+    /// 32-character passkey looked up in the user cache
+    Stored([u8; 32]),
+    /// Signed token verified statelessly (only accepted when `passkey_mode = "signed"`)
+    Signed(SignedPasskey),
+}
+
+/// Character set stored passkeys are issued in (`auth.passkey_format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasskeyFormat {
+    /// Any ASCII letters and digits
+    Alphanumeric,
+    /// Hex digits only
+    Hex,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -73,7 +143,69 @@ pub enum AnnounceEvent {
     Completed,
 }
 
+impl AnnounceEvent {
+    /// Event name as sent in the announce query
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnounceEvent::Started => "started",
+            AnnounceEvent::Stopped => "stopped",
+            AnnounceEvent::Completed => "completed",
+        }
+    }
+}
+
 impl AnnounceParams {
+    /// Parse a raw announce query string
+    ///
+    /// Values are kept percent-encoded; decoding happens in `validate`. Unknown keys and
+    /// pairs without `=` are ignored, and unparseable numbers fall back to their defaults.
+    pub fn from_query(query: &str) -> Self {
+        let mut params = Self {
+            passkey: String::new(),
+            info_hash: String::new(),
+            peer_id: String::new(),
+            port: 0,
+            uploaded: 0,
+            downloaded: 0,
+            left: 0,
+            event: String::new(),
+            numwant: default_numwant(),
+            compact: default_compact(),
+            ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
+        };
+
+        for pair in query.split('&') {
+            if let Some((key, value)) = pair.split_once('=') {
+                match key {
+                    "passkey" => params.passkey = value.to_string(),
+                    "info_hash" => params.info_hash = value.to_string(),
+                    "peer_id" => params.peer_id = value.to_string(),
+                    "port" => params.port = value.parse().unwrap_or(0),
+                    "uploaded" => params.uploaded = value.parse().unwrap_or(0),
+                    "downloaded" => params.downloaded = value.parse().unwrap_or(0),
+                    "left" => params.left = value.parse().unwrap_or(0),
+                    "event" => params.event = value.to_string(),
+                    "numwant" => params.numwant = value.parse().unwrap_or(default_numwant()),
+                    "compact" => params.compact = value.parse().unwrap_or(default_compact()),
+                    "ip" => params.ip = Some(value.to_string()),
+                    "key" => params.key = Some(value.to_string()),
+                    "ipv4" => params.ipv4 = Some(value.to_string()),
+                    "ipv6" => params.ipv6 = Some(value.to_string()),
+                    "supportcrypto" => params.supportcrypto = value == "1",
+                    "requirecrypto" => params.requirecrypto = value == "1",
+                    _ => {}
+                }
+            }
+        }
+
+        params
+    }
+
     /// Check if request has suspicious headers that indicate it's not a real torrent client
     /// This should be called by the handler with the actual HTTP headers
     pub fn has_suspicious_headers(headers: &[(String, String)]) -> bool {
@@ -84,9 +216,9 @@ impl AnnounceParams {
         })
     }
     
-    pub fn validate(self) -> Result<ValidatedAnnounceParams> {
-        // Validate passkey (32 hex characters)
-        let passkey = self.validate_passkey()
+    pub fn validate(self, passkey_format: PasskeyFormat) -> Result<ValidatedAnnounceParams> {
+        // Validate passkey (32 characters in the configured format, or a signed token)
+        let passkey = self.validate_passkey(passkey_format)
             .context("Invalid passkey")?;
         
         // Validate info_hash (20 bytes)
@@ -120,6 +252,24 @@ impl AnnounceParams {
             None
         };
         
+        let ipv4 = match &self.ipv4 {
+            Some(value) => match parse_declared_ip(value).context("Invalid ipv4")? {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => bail!("ipv4 parameter holds an IPv6 address"),
+            },
+            None => None,
+        };
+        
+        let ipv6 = match &self.ipv6 {
+            Some(value) => match parse_declared_ip(value).context("Invalid ipv6")? {
+                IpAddr::V6(ip) => Some(ip),
+                IpAddr::V4(_) => bail!("ipv6 parameter holds an IPv4 address"),
+            },
+            None => None,
+        };
+        
+        let key = self.key.filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN);
+        
         Ok(ValidatedAnnounceParams {
             passkey,
             info_hash,
@@ -132,10 +282,19 @@ impl AnnounceParams {
             numwant,
             compact,
             ip,
+            key,
+            ipv4,
+            ipv6,
+            supportcrypto: self.supportcrypto,
+            requirecrypto: self.requirecrypto,
         })
     }
     
-    fn validate_passkey(&self) -> Result<[u8; 32]> {
+    fn validate_passkey(&self, passkey_format: PasskeyFormat) -> Result<Passkey> {
+        if self.passkey.contains('.') {
+            return SignedPasskey::parse(&self.passkey).map(Passkey::Signed);
+        }
+        
         let bytes = self.passkey.as_bytes();
         
         if bytes.len() != 32 {
@@ -146,14 +305,19 @@ impl AnnounceParams {
             bail!("Passkey must contain only alphanumeric characters");
         }
         
+        if passkey_format == PasskeyFormat::Hex && !bytes.iter().all(|b| b.is_ascii_hexdigit()) {
+            bail!("Passkey must contain only hex characters");
+        }
+        
         let mut passkey = [0u8; 32];
         passkey.copy_from_slice(bytes);
         
-        Ok(passkey)
+        Ok(Passkey::Stored(passkey))
     }
     
     fn validate_info_hash(&self) -> Result<[u8; 20]> {
-        let bytes = url_decode(&self.info_hash)
+        check_encoded_id_length(&self.info_hash, "Info hash")?;
+        let bytes = url_decode_binary(&self.info_hash)
             .context("Failed to URL decode info_hash")?;
         
         if bytes.len() != 20 {
@@ -165,7 +329,8 @@ impl AnnounceParams {
     }
     
     fn validate_peer_id(&self) -> Result<[u8; 20]> {
-        let bytes = url_decode(&self.peer_id)
+        check_encoded_id_length(&self.peer_id, "Peer ID")?;
+        let bytes = url_decode_binary(&self.peer_id)
             .context("Failed to URL decode peer_id")?;
         
         if bytes.len() != 20 {
@@ -248,12 +413,51 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
-        let result = params.validate_passkey();
+        let result = params.validate_passkey(PasskeyFormat::Alphanumeric);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_validate_passkey_format() {
+        let params_with = |passkey: &str| AnnounceParams {
+            passkey: passkey.to_string(),
+            info_hash: "".to_string(),
+            peer_id: "".to_string(),
+            port: 6881,
+            uploaded: 0,
+            downloaded: 0,
+            left: 0,
+            event: "".to_string(),
+            numwant: 50,
+            compact: 1,
+            ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
+        };
+
+        let hex = params_with("abcdef0123456789ABCDEF0123456789");
+        assert!(hex.validate_passkey(PasskeyFormat::Alphanumeric).is_ok());
+        assert!(hex.validate_passkey(PasskeyFormat::Hex).is_ok());
+
+        let alphanumeric = params_with("ghijklmnopqrstuvwxyz0123456789AB");
+        assert!(alphanumeric.validate_passkey(PasskeyFormat::Alphanumeric).is_ok());
+        assert!(alphanumeric.validate_passkey(PasskeyFormat::Hex).is_err());
+
+        let special = params_with("0123456789abcdef!@#$%^&*()123456");
+        assert!(special.validate_passkey(PasskeyFormat::Alphanumeric).is_err());
+        assert!(special.validate_passkey(PasskeyFormat::Hex).is_err());
+    }
+
     #[test]
     fn test_validate_passkey_too_short() {
         let params = AnnounceParams {
@@ -268,9 +472,14 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
-        let result = params.validate_passkey();
+        let result = params.validate_passkey(PasskeyFormat::Alphanumeric);
         assert!(result.is_err());
     }
 
@@ -288,12 +497,48 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
-        let result = params.validate_passkey();
+        let result = params.validate_passkey(PasskeyFormat::Alphanumeric);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_validate_passkey_signed() {
+        let token = crate::utils::auth::sign_passkey(42, 2000, "secret");
+        let params = AnnounceParams {
+            passkey: token,
+            info_hash: "".to_string(),
+            peer_id: "".to_string(),
+            port: 6881,
+            uploaded: 0,
+            downloaded: 0,
+            left: 0,
+            event: "".to_string(),
+            numwant: 50,
+            compact: 1,
+            ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
+        };
+        
+        match params.validate_passkey(PasskeyFormat::Alphanumeric).unwrap() {
+            Passkey::Signed(signed) => {
+                assert_eq!(signed.user_id, 42);
+                assert_eq!(signed.expiry, 2000);
+            }
+            Passkey::Stored(_) => panic!("Expected signed passkey"),
+        }
+    }
+
     #[test]
     fn test_validate_info_hash_valid() {
         let params = AnnounceParams {
@@ -308,6 +553,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_info_hash();
@@ -316,6 +566,18 @@ mod tests {
         assert_eq!(hash.len(), 20);
     }
 
+    #[test]
+    fn test_validate_info_hash_raw_plus_byte() {
+        // Hash with 0x2B bytes: one left raw as '+', one percent-encoded
+        let mut expected = [0x41u8; 20];
+        expected[0] = 0x2b;
+        expected[19] = 0x2b;
+
+        let params = AnnounceParams::from_query(&format!("info_hash=+{}%2B", "A".repeat(18)));
+
+        assert_eq!(params.validate_info_hash().unwrap(), expected);
+    }
+
     #[test]
     fn test_validate_info_hash_wrong_length() {
         let params = AnnounceParams {
@@ -330,12 +592,36 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_info_hash();
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_validate_ids_reject_overlong_encoding_before_decoding() {
+        // Invalid escapes would fail decoding; the length check must reject them first
+        let params = AnnounceParams::from_query(&format!(
+            "info_hash={}&peer_id={}",
+            "%zz".repeat(1000),
+            "%41".repeat(21),
+        ));
+
+        let err = params.validate_info_hash().unwrap_err();
+        assert_eq!(err.to_string(), "Info hash is too long to encode 20 bytes");
+        let err = params.validate_peer_id().unwrap_err();
+        assert_eq!(err.to_string(), "Peer ID is too long to encode 20 bytes");
+
+        // The longest valid encoding still passes
+        let params = AnnounceParams::from_query(&format!("info_hash={}", "%41".repeat(20)));
+        assert_eq!(params.validate_info_hash().unwrap(), [0x41; 20]);
+    }
+
     #[test]
     fn test_validate_peer_id_valid() {
         let params = AnnounceParams {
@@ -350,6 +636,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_peer_id();
@@ -372,6 +663,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_port();
@@ -393,6 +689,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_port();
@@ -417,6 +718,11 @@ mod tests {
                 numwant: 50,
                 compact: 1,
                 ip: None,
+                key: None,
+                ipv4: None,
+                ipv6: None,
+                supportcrypto: false,
+                requirecrypto: false,
             };
             
             let result = params.validate_port();
@@ -438,6 +744,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_numwant();
@@ -459,6 +770,11 @@ mod tests {
             numwant: 200,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_numwant();
@@ -480,6 +796,11 @@ mod tests {
             numwant: 201,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_numwant();
@@ -500,6 +821,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_event();
@@ -521,6 +847,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_event();
@@ -542,6 +873,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_event();
@@ -563,6 +899,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_event();
@@ -584,6 +925,11 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: None,
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
         let result = params.validate_event();
@@ -604,9 +950,14 @@ mod tests {
             numwant: 50,
             compact: 1,
             ip: Some("192.168.1.1".to_string()),
+            key: None,
+            ipv4: None,
+            ipv6: None,
+            supportcrypto: false,
+            requirecrypto: false,
         };
         
-        let result = params.validate();
+        let result = params.validate(PasskeyFormat::Alphanumeric);
         assert!(result.is_ok());
         
         let validated = result.unwrap();
@@ -619,6 +970,108 @@ mod tests {
         assert_eq!(validated.compact, true);
         assert!(validated.ip.is_some());
     }
+
+    #[test]
+    fn test_from_query() {
+        let params = AnnounceParams::from_query(
+            "passkey=abc&info_hash=%41%42&port=6881&left=10&event=started&numwant=5&compact=0&ip=1.2.3.4",
+        );
+        assert_eq!(params.passkey, "abc");
+        assert_eq!(params.info_hash, "%41%42");
+        assert_eq!(params.port, 6881);
+        assert_eq!(params.left, 10);
+        assert_eq!(params.event, "started");
+        assert_eq!(params.numwant, 5);
+        assert_eq!(params.compact, 0);
+        assert_eq!(params.ip.as_deref(), Some("1.2.3.4"));
+        assert!(!params.supportcrypto);
+        assert!(!params.requirecrypto);
+    }
+
+    #[test]
+    fn test_from_query_crypto_flags() {
+        let params = AnnounceParams::from_query("supportcrypto=1&requirecrypto=0");
+        assert!(params.supportcrypto);
+        assert!(!params.requirecrypto);
+
+        let params = AnnounceParams::from_query("supportcrypto=yes&requirecrypto=1");
+        assert!(!params.supportcrypto);
+        assert!(params.requirecrypto);
+    }
+
+    #[test]
+    fn test_validate_dual_stack_addresses() {
+        let query = format!(
+            "passkey={}&info_hash={}&peer_id={}&port=6881",
+            "a".repeat(32),
+            "A".repeat(20),
+            "B".repeat(20),
+        );
+
+        let validated = AnnounceParams::from_query(&format!("{}&ipv4=1.2.3.4&ipv6=2001%3Adb8%3A%3A1", query))
+            .validate(PasskeyFormat::Alphanumeric)
+            .unwrap();
+        assert_eq!(validated.ipv4, Some(Ipv4Addr::new(1, 2, 3, 4)));
+        assert_eq!(validated.ipv6, Some("2001:db8::1".parse().unwrap()));
+
+        // Endpoint form, port ignored
+        let validated = AnnounceParams::from_query(&format!("{}&ipv6=%5B2001%3Adb8%3A%3A2%5D%3A6881", query))
+            .validate(PasskeyFormat::Alphanumeric)
+            .unwrap();
+        assert_eq!(validated.ipv6, Some("2001:db8::2".parse().unwrap()));
+
+        // Wrong family or garbage is rejected
+        assert!(AnnounceParams::from_query(&format!("{}&ipv4=2001%3Adb8%3A%3A1", query)).validate(PasskeyFormat::Alphanumeric).is_err());
+        assert!(AnnounceParams::from_query(&format!("{}&ipv6=1.2.3.4", query)).validate(PasskeyFormat::Alphanumeric).is_err());
+        assert!(AnnounceParams::from_query(&format!("{}&ipv4=nope", query)).validate(PasskeyFormat::Alphanumeric).is_err());
+    }
+
+    #[test]
+    fn test_validate_key() {
+        let query = format!(
+            "passkey={}&info_hash={}&peer_id={}&port=6881&key=",
+            "a".repeat(32),
+            "A".repeat(20),
+            "B".repeat(20),
+        );
+
+        let validated = AnnounceParams::from_query(&format!("{}8A3F29C1", query)).validate(PasskeyFormat::Alphanumeric).unwrap();
+        assert_eq!(validated.key.as_deref(), Some("8A3F29C1"));
+
+        // Empty and overlong keys are treated as absent
+        assert!(AnnounceParams::from_query(&query).validate(PasskeyFormat::Alphanumeric).unwrap().key.is_none());
+        let long_key = "k".repeat(MAX_KEY_LEN + 1);
+        assert!(AnnounceParams::from_query(&format!("{}{}", query, long_key)).validate(PasskeyFormat::Alphanumeric).unwrap().key.is_none());
+    }
+
+    #[test]
+    fn test_from_query_malformed_pairs() {
+        // Embedded '=' stays in the value, pairs without '=' and bad numbers are ignored
+        let params = AnnounceParams::from_query("passkey=a=b&&peer_id&port=99999&numwant=x&=&unknown=1");
+        assert_eq!(params.passkey, "a=b");
+        assert!(params.peer_id.is_empty());
+        assert_eq!(params.port, 0);
+        assert_eq!(params.numwant, 50);
+        assert_eq!(params.compact, 1);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_from_query_never_panics(query in "\\PC{0,512}") {
+            let params = AnnounceParams::from_query(&query);
+            proptest::prop_assert!(params.passkey.len() <= query.len());
+            proptest::prop_assert!(params.info_hash.len() <= query.len());
+            proptest::prop_assert!(params.peer_id.len() <= query.len());
+            let _ = params.validate(PasskeyFormat::Alphanumeric);
+        }
+
+        #[test]
+        fn prop_announce_like_query_never_panics(
+            query in "((passkey|info_hash|peer_id|port|left|event|numwant|compact|ip)=[%0-9a-fA-F&=+.:\\-]{0,48}&?){0,12}",
+        ) {
+            let _ = AnnounceParams::from_query(&query).validate(PasskeyFormat::Alphanumeric);
+        }
+    }
 }
 
     #[test]
@@ -1,7 +1,8 @@
 use anyhow::{bail, Context, Result};
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
 /// WAL operation types
@@ -11,10 +12,19 @@ pub enum WalOperation {
         id: u32,
         info_hash: [u8; 20],
         freeleech: bool,
+        /// Written as an optional trailing field, so older lines still parse
+        announce_interval: Option<i64>,
+        /// Written after announce_interval, which is left empty if only this is set
+        peer_timeout: Option<i64>,
     },
     RemoveTorrent {
         info_hash: [u8; 20],
     },
+    /// Pause or resume a torrent without touching its swarm
+    SetTorrentActive {
+        info_hash: [u8; 20],
+        active: bool,
+    },
     AddUser {
         id: u32,
         passkey: [u8; 32],
@@ -23,6 +33,12 @@ pub enum WalOperation {
     RemoveUser {
         passkey: [u8; 32],
     },
+    BanUser {
+        id: u32,
+    },
+    UnbanUser {
+        id: u32,
+    },
 }
 
 impl WalOperation {
@@ -32,15 +48,33 @@ impl WalOperation {
                 id,
                 info_hash,
                 freeleech,
+                announce_interval,
+                peer_timeout,
             } => {
                 let hex_hash = hex::encode(info_hash);
                 let freeleech_flag = if *freeleech { "1" } else { "0" };
-                format!("ADD_TORRENT|{}|{}|{}", id, hex_hash, freeleech_flag)
+                match (announce_interval, peer_timeout) {
+                    (_, Some(timeout)) => format!(
+                        "ADD_TORRENT|{}|{}|{}|{}|{}",
+                        id,
+                        hex_hash,
+                        freeleech_flag,
+                        announce_interval.map(|interval| interval.to_string()).unwrap_or_default(),
+                        timeout
+                    ),
+                    (Some(interval), None) => format!("ADD_TORRENT|{}|{}|{}|{}", id, hex_hash, freeleech_flag, interval),
+                    (None, None) => format!("ADD_TORRENT|{}|{}|{}", id, hex_hash, freeleech_flag),
+                }
             }
             WalOperation::RemoveTorrent { info_hash } => {
                 let hex_hash = hex::encode(info_hash);
                 format!("REMOVE_TORRENT|{}", hex_hash)
             }
+            WalOperation::SetTorrentActive { info_hash, active } => {
+                let hex_hash = hex::encode(info_hash);
+                let active_flag = if *active { "1" } else { "0" };
+                format!("SET_TORRENT_ACTIVE|{}|{}", hex_hash, active_flag)
+            }
             WalOperation::AddUser { id, passkey, class } => {
                 let hex_passkey = hex::encode(passkey);
                 format!("ADD_USER|{}|{}|{}", id, hex_passkey, class)
@@ -49,6 +83,8 @@ impl WalOperation {
                 let hex_passkey = hex::encode(passkey);
                 format!("REMOVE_USER|{}", hex_passkey)
             }
+            WalOperation::BanUser { id } => format!("BAN_USER|{}", id),
+            WalOperation::UnbanUser { id } => format!("UNBAN_USER|{}", id),
         }
     }
 
@@ -57,7 +93,7 @@ impl WalOperation {
 
         match parts.get(0) {
             Some(&"ADD_TORRENT") => {
-                if parts.len() != 4 {
+                if !(4..=6).contains(&parts.len()) {
                     bail!("Invalid ADD_TORRENT format");
                 }
                 let id = parts[1].parse::<u32>().context("Invalid torrent ID")?;
@@ -68,11 +104,22 @@ impl WalOperation {
                 let mut info_hash = [0u8; 20];
                 info_hash.copy_from_slice(&info_hash_bytes);
                 let freeleech = parts[3] == "1";
+                let announce_interval = parts
+                    .get(4)
+                    .filter(|interval| !interval.is_empty())
+                    .map(|interval| interval.parse::<i64>().context("Invalid announce interval"))
+                    .transpose()?;
+                let peer_timeout = parts
+                    .get(5)
+                    .map(|timeout| timeout.parse::<i64>().context("Invalid peer timeout"))
+                    .transpose()?;
 
                 Ok(WalOperation::AddTorrent {
                     id,
                     info_hash,
                     freeleech,
+                    announce_interval,
+                    peer_timeout,
                 })
             }
             Some(&"REMOVE_TORRENT") => {
@@ -88,6 +135,20 @@ impl WalOperation {
 
                 Ok(WalOperation::RemoveTorrent { info_hash })
             }
+            Some(&"SET_TORRENT_ACTIVE") => {
+                if parts.len() != 3 {
+                    bail!("Invalid SET_TORRENT_ACTIVE format");
+                }
+                let info_hash_bytes = hex::decode(parts[1]).context("Invalid info_hash hex")?;
+                if info_hash_bytes.len() != 20 {
+                    bail!("info_hash must be 20 bytes");
+                }
+                let mut info_hash = [0u8; 20];
+                info_hash.copy_from_slice(&info_hash_bytes);
+                let active = parts[2] == "1";
+
+                Ok(WalOperation::SetTorrentActive { info_hash, active })
+            }
             Some(&"ADD_USER") => {
                 if parts.len() != 4 {
                     bail!("Invalid ADD_USER format");
@@ -116,6 +177,22 @@ impl WalOperation {
 
                 Ok(WalOperation::RemoveUser { passkey })
             }
+            Some(&"BAN_USER") => {
+                if parts.len() != 2 {
+                    bail!("Invalid BAN_USER format");
+                }
+                let id = parts[1].parse::<u32>().context("Invalid user ID")?;
+
+                Ok(WalOperation::BanUser { id })
+            }
+            Some(&"UNBAN_USER") => {
+                if parts.len() != 2 {
+                    bail!("Invalid UNBAN_USER format");
+                }
+                let id = parts[1].parse::<u32>().context("Invalid user ID")?;
+
+                Ok(WalOperation::UnbanUser { id })
+            }
             _ => bail!("Unknown operation type"),
         }
     }
@@ -124,6 +201,8 @@ impl WalOperation {
 pub struct Wal {
     file: Arc<Mutex<File>>,
     path: PathBuf,
+    /// Failed writes since the last successful one
+    consecutive_failures: AtomicU64,
 }
 
 impl Wal {
@@ -137,20 +216,63 @@ impl Wal {
         Ok(Wal {
             file: Arc::new(Mutex::new(file)),
             path,
+            consecutive_failures: AtomicU64::new(0),
         })
     }
 
+    /// Append an operation to the WAL
+    ///
+    /// Callers carry on with the in-memory change when this fails, so the first failure
+    /// of a run is logged at error level here and the streak is tracked for `is_healthy`.
     pub fn log_operation(&self, op: WalOperation) -> Result<()> {
         let line = op.to_string();
         let mut file = self.file.lock().unwrap();
-        writeln!(file, "{}", line).context("Failed to write to WAL")?;
-        file.flush().context("Failed to flush WAL")?;
-        Ok(())
+        let result = writeln!(file, "{}", line)
+            .context("Failed to write to WAL")
+            .and_then(|()| file.flush().context("Failed to flush WAL"));
+        self.record_write(&result);
+        result
+    }
+
+    fn record_write(&self, result: &Result<()>) {
+        match result {
+            Ok(()) => {
+                let failures = self.consecutive_failures.swap(0, Ordering::Relaxed);
+                if failures > 0 {
+                    tracing::info!(failed_writes = failures, "WAL writes recovered");
+                }
+            }
+            Err(e) => {
+                if self.consecutive_failures.fetch_add(1, Ordering::Relaxed) == 0 {
+                    tracing::error!(
+                        path = %self.path.display(),
+                        error = %e,
+                        "WAL write failed, admin changes are no longer persisted"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Whether the last WAL write succeeded
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures() == 0
+    }
+
+    /// Failed writes since the last successful one
+    pub fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures.load(Ordering::Relaxed)
     }
 
 
     pub fn replay(&self) -> Result<Vec<WalOperation>> {
-        let file = File::open(&self.path).context("Failed to open WAL for replay")?;
+        self.replay_from(0)
+    }
+
+    /// Operations logged after the WAL had grown to `offset` bytes, as returned by `size`
+    pub fn replay_from(&self, offset: u64) -> Result<Vec<WalOperation>> {
+        let mut file = File::open(&self.path).context("Failed to open WAL for replay")?;
+        file.seek(SeekFrom::Start(offset)).context("Failed to seek in WAL")?;
         let reader = BufReader::new(file);
         let mut operations = Vec::new();
 
@@ -179,6 +301,48 @@ impl Wal {
     }
 
 
+    /// Replace the WAL contents with the operations returned by `snapshot`
+    ///
+    /// The WAL lock is held from before the snapshot is taken until the new file is in
+    /// place, so an operation logged concurrently is either part of the snapshot or
+    /// appended after the rewrite, never lost. The new contents are written to a
+    /// temporary file and renamed over the WAL, so a crash leaves one version intact.
+    /// Returns the number of operations written.
+    pub fn compact<F>(&self, snapshot: F) -> Result<usize>
+    where
+        F: FnOnce() -> Vec<WalOperation>,
+    {
+        let mut file = self.file.lock().unwrap();
+        let operations = snapshot();
+
+        let tmp_path = self.path.with_extension("compact.tmp");
+        let mut tmp = File::create(&tmp_path).context("Failed to create temporary WAL")?;
+        let mut contents = String::new();
+        for op in &operations {
+            contents.push_str(&op.to_string());
+            contents.push('\n');
+        }
+        tmp.write_all(contents.as_bytes()).context("Failed to write temporary WAL")?;
+        tmp.sync_all().context("Failed to sync temporary WAL")?;
+
+        std::fs::rename(&tmp_path, &self.path).context("Failed to replace WAL")?;
+        *file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .context("Failed to reopen WAL")?;
+        // The rewritten file holds everything, including changes whose writes failed
+        self.record_write(&Ok(()));
+
+        Ok(operations.len())
+    }
+
+    /// Current size of the WAL in bytes, i.e. operations logged since the last truncate
+    pub fn size(&self) -> Result<u64> {
+        let file = self.file.lock().unwrap();
+        Ok(file.metadata().context("Failed to read WAL metadata")?.len())
+    }
+
+    #[cfg(test)]
     pub fn truncate(&self) -> Result<()> {
         let mut file = self.file.lock().unwrap();
         file.set_len(0).context("Failed to truncate WAL")?;
@@ -193,6 +357,20 @@ mod tests {
     use std::fs;
     use tempfile::TempDir;
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_write_failures_mark_wal_unhealthy() {
+        // Every write to /dev/full fails with ENOSPC
+        let wal = Wal::new(PathBuf::from("/dev/full")).unwrap();
+        assert!(wal.is_healthy());
+
+        for _ in 0..3 {
+            assert!(wal.log_operation(WalOperation::UnbanUser { id: 1 }).is_err());
+        }
+        assert!(!wal.is_healthy());
+        assert_eq!(wal.consecutive_failures(), 3);
+    }
+
     #[test]
     fn test_wal_operation_serialization() {
         let info_hash = [1u8; 20];
@@ -203,6 +381,8 @@ mod tests {
             id: 123,
             info_hash,
             freeleech: true,
+            announce_interval: None,
+            peer_timeout: None,
         };
         let serialized = op.to_string();
         assert_eq!(
@@ -212,6 +392,38 @@ mod tests {
         let deserialized = WalOperation::from_string(&serialized).unwrap();
         assert_eq!(op, deserialized);
 
+        // Test AddTorrent with an announce interval override
+        let op = WalOperation::AddTorrent {
+            id: 123,
+            info_hash,
+            freeleech: false,
+            announce_interval: Some(3600),
+            peer_timeout: None,
+        };
+        let serialized = op.to_string();
+        assert_eq!(
+            serialized,
+            format!("ADD_TORRENT|123|{}|0|3600", hex::encode(info_hash))
+        );
+        let deserialized = WalOperation::from_string(&serialized).unwrap();
+        assert_eq!(op, deserialized);
+
+        // Test AddTorrent with only a peer timeout override
+        let op = WalOperation::AddTorrent {
+            id: 123,
+            info_hash,
+            freeleech: false,
+            announce_interval: None,
+            peer_timeout: Some(86400),
+        };
+        let serialized = op.to_string();
+        assert_eq!(
+            serialized,
+            format!("ADD_TORRENT|123|{}|0||86400", hex::encode(info_hash))
+        );
+        let deserialized = WalOperation::from_string(&serialized).unwrap();
+        assert_eq!(op, deserialized);
+
         // Test RemoveTorrent
         let op = WalOperation::RemoveTorrent { info_hash };
         let serialized = op.to_string();
@@ -239,6 +451,24 @@ mod tests {
         assert_eq!(serialized, format!("REMOVE_USER|{}", hex::encode(passkey)));
         let deserialized = WalOperation::from_string(&serialized).unwrap();
         assert_eq!(op, deserialized);
+
+        // Test BanUser and UnbanUser
+        let op = WalOperation::BanUser { id: 456 };
+        assert_eq!(op.to_string(), "BAN_USER|456");
+        assert_eq!(WalOperation::from_string("BAN_USER|456").unwrap(), op);
+
+        let op = WalOperation::UnbanUser { id: 456 };
+        assert_eq!(op.to_string(), "UNBAN_USER|456");
+        assert_eq!(WalOperation::from_string("UNBAN_USER|456").unwrap(), op);
+    }
+
+    #[test]
+    fn test_set_torrent_active_round_trip() {
+        let op = WalOperation::SetTorrentActive { info_hash: [0xab; 20], active: false };
+        let line = op.to_string();
+        assert_eq!(line, format!("SET_TORRENT_ACTIVE|{}|0", "ab".repeat(20)));
+        assert_eq!(WalOperation::from_string(&line).unwrap(), op);
+        assert!(WalOperation::from_string("SET_TORRENT_ACTIVE|abcd|1").is_err());
     }
 
     #[test]
@@ -256,6 +486,8 @@ mod tests {
             id: 123,
             info_hash,
             freeleech: true,
+            announce_interval: None,
+            peer_timeout: None,
         })
         .unwrap();
 
@@ -281,6 +513,7 @@ mod tests {
                 id,
                 info_hash: h,
                 freeleech,
+                ..
             } => {
                 assert_eq!(*id, 123);
                 assert_eq!(*h, info_hash);
@@ -331,12 +564,15 @@ mod tests {
             id: 123,
             info_hash,
             freeleech: false,
+            announce_interval: None,
+            peer_timeout: None,
         })
         .unwrap();
 
         // Verify it was logged
         let operations = wal.replay().unwrap();
         assert_eq!(operations.len(), 1);
+        assert!(wal.size().unwrap() > 0);
 
         // Truncate
         wal.truncate().unwrap();
@@ -344,6 +580,49 @@ mod tests {
         // Verify it's empty
         let operations = wal.replay().unwrap();
         assert_eq!(operations.len(), 0);
+        assert_eq!(wal.size().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_wal_compact() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal = Wal::new(temp_dir.path().join("test.wal")).unwrap();
+
+        for id in 0..10 {
+            wal.log_operation(WalOperation::AddTorrent {
+                id,
+                info_hash: [id as u8; 20],
+                freeleech: false,
+                announce_interval: None,
+                peer_timeout: None,
+            })
+            .unwrap();
+            wal.log_operation(WalOperation::RemoveTorrent { info_hash: [id as u8; 20] })
+                .unwrap();
+        }
+        let before = wal.size().unwrap();
+
+        let survivor = WalOperation::AddTorrent {
+            id: 42,
+            info_hash: [42u8; 20],
+            freeleech: true,
+            announce_interval: None,
+            peer_timeout: None,
+        };
+        let written = wal.compact(|| vec![survivor.clone()]).unwrap();
+        assert_eq!(written, 1);
+        assert!(wal.size().unwrap() < before);
+        assert_eq!(wal.replay().unwrap(), vec![survivor.clone()]);
+
+        // Later operations are appended to the compacted file
+        let compacted = wal.size().unwrap();
+        wal.log_operation(WalOperation::RemoveTorrent { info_hash: [42u8; 20] })
+            .unwrap();
+        assert_eq!(wal.replay().unwrap().len(), 2);
+        assert_eq!(
+            wal.replay_from(compacted).unwrap(),
+            vec![WalOperation::RemoveTorrent { info_hash: [42u8; 20] }]
+        );
     }
 
     #[test]